@@ -0,0 +1,18 @@
+CREATE TABLE sessions (
+    session_id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    expires_at timestamp with time zone NOT NULL,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    CONSTRAINT fk__sessions__users FOREIGN KEY(user_id) REFERENCES users(user_id)
+);
+
+CREATE INDEX idx__sessions__user_id ON sessions (user_id);
+
+CREATE TABLE refresh_tokens (
+    token_hash char(64) PRIMARY KEY,
+    session_id UUID NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    used BOOLEAN NOT NULL DEFAULT FALSE,
+    CONSTRAINT fk__refresh_tokens__sessions FOREIGN KEY(session_id) REFERENCES sessions(session_id)
+);
@@ -0,0 +1,12 @@
+CREATE TABLE personal_tokens (
+    token_id UUID PRIMARY KEY,
+    user_id UUID NOT NULL,
+    token_name varchar(255) NOT NULL,
+    token_hash char(64) NOT NULL UNIQUE,
+    scopes smallint[] NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    expires_at timestamp with time zone NOT NULL,
+    last_used_at timestamp with time zone,
+    UNIQUE(user_id, token_name),
+    CONSTRAINT fk__personal_tokens__users FOREIGN KEY(user_id) REFERENCES users(user_id)
+);
@@ -0,0 +1,15 @@
+CREATE TABLE user_identities (
+    issuer varchar(255) NOT NULL,
+    subject varchar(255) NOT NULL,
+    user_id UUID NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    PRIMARY KEY(issuer, subject),
+    CONSTRAINT fk__user_identities__users FOREIGN KEY(user_id) REFERENCES users(user_id)
+);
+
+CREATE TABLE oidc_logins (
+    state char(64) PRIMARY KEY,
+    code_verifier char(64) NOT NULL,
+    nonce char(64) NOT NULL,
+    created_at timestamp with time zone DEFAULT now()
+);
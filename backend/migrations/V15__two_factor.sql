@@ -0,0 +1,23 @@
+CREATE TABLE user_totp (
+    user_id UUID PRIMARY KEY,
+    secret varchar(64) NOT NULL,
+    enabled boolean NOT NULL DEFAULT FALSE,
+    last_used_step bigint,
+    created_at timestamp with time zone DEFAULT now(),
+    CONSTRAINT fk__user_totp__users FOREIGN KEY(user_id) REFERENCES users(user_id)
+);
+
+CREATE TABLE totp_recovery_codes (
+    code_hash char(64) PRIMARY KEY,
+    user_id UUID NOT NULL,
+    used boolean NOT NULL DEFAULT FALSE,
+    CONSTRAINT fk__totp_recovery_codes__users FOREIGN KEY(user_id) REFERENCES users(user_id)
+);
+
+CREATE TABLE login_challenges (
+    challenge_hash char(64) PRIMARY KEY,
+    user_id UUID NOT NULL,
+    attempts smallint NOT NULL DEFAULT 0,
+    created_at timestamp with time zone DEFAULT now(),
+    CONSTRAINT fk__login_challenges__users FOREIGN KEY(user_id) REFERENCES users(user_id)
+);
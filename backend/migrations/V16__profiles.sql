@@ -0,0 +1,4 @@
+ALTER TABLE users
+    ADD COLUMN display_name varchar(255),
+    ADD COLUMN email varchar(255) UNIQUE,
+    ADD COLUMN deleted_at timestamp with time zone;
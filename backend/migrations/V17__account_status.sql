@@ -0,0 +1,11 @@
+ALTER TABLE users
+    ADD COLUMN disabled boolean NOT NULL DEFAULT FALSE,
+    ADD COLUMN locked_until timestamp with time zone;
+
+CREATE TABLE password_resets (
+    token_hash char(64) PRIMARY KEY,
+    user_id UUID NOT NULL,
+    created_at timestamp with time zone DEFAULT now(),
+    expires_at timestamp with time zone NOT NULL,
+    CONSTRAINT fk__password_resets__users FOREIGN KEY(user_id) REFERENCES users(user_id)
+);
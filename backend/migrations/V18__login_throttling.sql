@@ -0,0 +1,17 @@
+CREATE TABLE login_throttles (
+    scope smallint NOT NULL,
+    key varchar(255) NOT NULL,
+    failures integer NOT NULL DEFAULT 0,
+    last_failure_at timestamp with time zone NOT NULL DEFAULT now(),
+    blocked_until timestamp with time zone,
+    PRIMARY KEY (scope, key)
+);
+
+CREATE TABLE security_events (
+    event_id UUID PRIMARY KEY,
+    event_type smallint NOT NULL,
+    user_id UUID,
+    address varchar(64),
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    CONSTRAINT fk__security_events__users FOREIGN KEY(user_id) REFERENCES users(user_id)
+);
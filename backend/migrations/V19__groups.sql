@@ -0,0 +1,34 @@
+CREATE TABLE groups (
+    group_id UUID PRIMARY KEY,
+    group_name varchar(255) NOT NULL UNIQUE,
+    created_at timestamp with time zone DEFAULT now()
+);
+
+CREATE TABLE group_members (
+    group_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    is_manager boolean NOT NULL DEFAULT FALSE,
+    PRIMARY KEY(group_id, user_id),
+    CONSTRAINT fk__group_members__groups FOREIGN KEY(group_id) REFERENCES groups(group_id) ON DELETE CASCADE,
+    CONSTRAINT fk__group_members__users FOREIGN KEY(user_id) REFERENCES users(user_id)
+);
+
+CREATE TABLE group_document_version_roles (
+    group_id UUID NOT NULL,
+    document_id UUID NOT NULL,
+    version_id UUID NOT NULL,
+    role_id smallint NOT NULL,
+    PRIMARY KEY(group_id, document_id, version_id, role_id),
+    CONSTRAINT fk__group_document_version_roles__groups FOREIGN KEY(group_id) REFERENCES groups(group_id) ON DELETE CASCADE,
+    CONSTRAINT fk__group_document_version_roles__document_versions FOREIGN KEY(document_id, version_id) REFERENCES document_versions(document_id, version_id),
+    CONSTRAINT fk__group_document_version_roles__document_version_roles FOREIGN KEY(role_id) REFERENCES document_version_roles(role_id)
+);
+
+-- Roles granted directly or through any group the user is a member of
+CREATE VIEW effective_document_version_roles AS
+    SELECT user_id, document_id, version_id, role_id
+    FROM user_document_version_roles
+    UNION
+    SELECT m.user_id, g.document_id, g.version_id, g.role_id
+    FROM group_document_version_roles g
+    JOIN group_members m ON m.group_id = g.group_id;
@@ -0,0 +1,23 @@
+CREATE TABLE document_set_roles (
+    role_id smallint PRIMARY KEY,
+    role_name varchar(255) NOT NULL UNIQUE
+);
+
+INSERT INTO document_set_roles VALUES (0, 'Owner'), (1, 'Editor'), (2, 'Viewer');
+
+CREATE TABLE user_document_set_roles (
+    user_id UUID NOT NULL,
+    document_set_id UUID NOT NULL,
+    role_id smallint NOT NULL,
+    PRIMARY KEY(user_id, document_set_id, role_id),
+    CONSTRAINT fk__user_document_set_roles__users FOREIGN KEY(user_id) REFERENCES users(user_id),
+    CONSTRAINT fk__user_document_set_roles__document_sets FOREIGN KEY(document_set_id) REFERENCES document_sets(document_set_id),
+    CONSTRAINT fk__user_document_set_roles__document_set_roles FOREIGN KEY(role_id) REFERENCES document_set_roles(role_id)
+);
+
+-- Sets created before roles existed are handed over to the admins
+INSERT INTO user_document_set_roles (user_id, document_set_id, role_id)
+SELECT r.user_id, s.document_set_id, 0
+FROM document_sets s
+CROSS JOIN user_roles r
+WHERE r.role_id = 0;
@@ -0,0 +1,17 @@
+ALTER TABLE user_document_version_roles
+    ADD COLUMN valid_from timestamp with time zone,
+    ADD COLUMN valid_until timestamp with time zone,
+    ADD CONSTRAINT ck__user_document_version_roles__validity CHECK (valid_until > valid_from);
+
+CREATE INDEX idx__user_document_version_roles__valid_until ON user_document_version_roles (valid_until) WHERE valid_until IS NOT NULL;
+
+-- Direct grants only count within their validity window
+CREATE OR REPLACE VIEW effective_document_version_roles AS
+    SELECT user_id, document_id, version_id, role_id
+    FROM user_document_version_roles
+    WHERE (valid_from IS NULL OR valid_from <= now())
+    AND (valid_until IS NULL OR valid_until > now())
+    UNION
+    SELECT m.user_id, g.document_id, g.version_id, g.role_id
+    FROM group_document_version_roles g
+    JOIN group_members m ON m.group_id = g.group_id;
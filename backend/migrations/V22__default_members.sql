@@ -0,0 +1,9 @@
+CREATE TABLE document_default_members (
+    document_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    role_id smallint NOT NULL,
+    PRIMARY KEY(document_id, user_id, role_id),
+    CONSTRAINT fk__document_default_members__documents FOREIGN KEY(document_id) REFERENCES documents(document_id),
+    CONSTRAINT fk__document_default_members__users FOREIGN KEY(user_id) REFERENCES users(user_id),
+    CONSTRAINT fk__document_default_members__document_version_roles FOREIGN KEY(role_id) REFERENCES document_version_roles(role_id)
+);
@@ -0,0 +1,24 @@
+CREATE TABLE user_document_roles (
+    user_id UUID NOT NULL,
+    document_id UUID NOT NULL,
+    role_id smallint NOT NULL,
+    PRIMARY KEY(user_id, document_id, role_id),
+    CONSTRAINT fk__user_document_roles__users FOREIGN KEY(user_id) REFERENCES users(user_id),
+    CONSTRAINT fk__user_document_roles__documents FOREIGN KEY(document_id) REFERENCES documents(document_id),
+    CONSTRAINT fk__user_document_roles__document_version_roles FOREIGN KEY(role_id) REFERENCES document_version_roles(role_id)
+);
+
+-- Document roles apply to every version of the document on top of version and group roles
+CREATE OR REPLACE VIEW effective_document_version_roles AS
+    SELECT user_id, document_id, version_id, role_id
+    FROM user_document_version_roles
+    WHERE (valid_from IS NULL OR valid_from <= now())
+    AND (valid_until IS NULL OR valid_until > now())
+    UNION
+    SELECT m.user_id, g.document_id, g.version_id, g.role_id
+    FROM group_document_version_roles g
+    JOIN group_members m ON m.group_id = g.group_id
+    UNION
+    SELECT r.user_id, v.document_id, v.version_id, r.role_id
+    FROM user_document_roles r
+    JOIN document_versions v ON v.document_id = r.document_id;
@@ -0,0 +1,14 @@
+CREATE TABLE ownership_transfers (
+    transfer_id UUID PRIMARY KEY,
+    document_id UUID NOT NULL,
+    version_id UUID NOT NULL,
+    from_user_id UUID NOT NULL,
+    to_user_id UUID NOT NULL,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    UNIQUE(document_id, version_id, to_user_id),
+    CONSTRAINT fk__ownership_transfers__document_versions FOREIGN KEY(document_id, version_id) REFERENCES document_versions(document_id, version_id),
+    CONSTRAINT fk__ownership_transfers__from_users FOREIGN KEY(from_user_id) REFERENCES users(user_id),
+    CONSTRAINT fk__ownership_transfers__to_users FOREIGN KEY(to_user_id) REFERENCES users(user_id)
+);
+
+CREATE INDEX idx__ownership_transfers__to_user_id ON ownership_transfers (to_user_id);
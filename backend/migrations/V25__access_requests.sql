@@ -0,0 +1,28 @@
+CREATE TABLE access_request_statuses (
+    status_id smallint PRIMARY KEY,
+    status_name varchar(255) NOT NULL UNIQUE
+);
+
+INSERT INTO access_request_statuses VALUES (0, 'Pending'), (1, 'Approved'), (2, 'Denied');
+
+CREATE TABLE access_requests (
+    request_id UUID PRIMARY KEY,
+    document_id UUID NOT NULL,
+    version_id UUID NOT NULL,
+    user_id UUID NOT NULL,
+    role_id smallint NOT NULL,
+    message varchar(1000),
+    status_id smallint NOT NULL DEFAULT 0,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    decided_at timestamp with time zone,
+    decided_by UUID,
+    CONSTRAINT fk__access_requests__document_versions FOREIGN KEY(document_id, version_id) REFERENCES document_versions(document_id, version_id),
+    CONSTRAINT fk__access_requests__users FOREIGN KEY(user_id) REFERENCES users(user_id),
+    CONSTRAINT fk__access_requests__document_version_roles FOREIGN KEY(role_id) REFERENCES document_version_roles(role_id),
+    CONSTRAINT fk__access_requests__access_request_statuses FOREIGN KEY(status_id) REFERENCES access_request_statuses(status_id),
+    CONSTRAINT fk__access_requests__deciders FOREIGN KEY(decided_by) REFERENCES users(user_id)
+);
+
+-- One pending request per user and role
+CREATE UNIQUE INDEX idx__access_requests__pending ON access_requests (document_id, version_id, user_id, role_id) WHERE status_id = 0;
+CREATE INDEX idx__access_requests__user_id ON access_requests (user_id);
@@ -0,0 +1,14 @@
+CREATE TABLE share_links (
+    link_id UUID PRIMARY KEY,
+    document_id UUID NOT NULL,
+    version_id UUID NOT NULL,
+    token_hash varchar(64) NOT NULL UNIQUE,
+    password_hash varchar(255),
+    created_by UUID NOT NULL,
+    created_at timestamp with time zone NOT NULL DEFAULT now(),
+    expires_at timestamp with time zone,
+    CONSTRAINT fk__share_links__document_versions FOREIGN KEY(document_id, version_id) REFERENCES document_versions(document_id, version_id),
+    CONSTRAINT fk__share_links__users FOREIGN KEY(created_by) REFERENCES users(user_id)
+);
+
+CREATE INDEX idx__share_links__version ON share_links (document_id, version_id);
@@ -0,0 +1,48 @@
+CREATE TABLE sensitivity_labels (
+    label_id smallint PRIMARY KEY,
+    label_name varchar(255) NOT NULL UNIQUE
+);
+
+INSERT INTO sensitivity_labels VALUES (0, 'Public'), (1, 'Internal'), (2, 'Confidential'), (3, 'Secret');
+
+ALTER TABLE documents ADD label_id smallint NOT NULL DEFAULT 0;
+ALTER TABLE documents ADD CONSTRAINT fk__documents__sensitivity_labels FOREIGN KEY (label_id) REFERENCES sensitivity_labels (label_id);
+
+ALTER TABLE users ADD clearance_id smallint NOT NULL DEFAULT 0;
+ALTER TABLE users ADD CONSTRAINT fk__users__sensitivity_labels FOREIGN KEY (clearance_id) REFERENCES sensitivity_labels (label_id);
+
+CREATE TABLE document_label_changes (
+    change_id UUID PRIMARY KEY,
+    document_id UUID NOT NULL,
+    old_label_id smallint NOT NULL,
+    new_label_id smallint NOT NULL,
+    changed_by UUID NOT NULL,
+    changed_at timestamp with time zone NOT NULL DEFAULT now(),
+    CONSTRAINT fk__document_label_changes__documents FOREIGN KEY(document_id) REFERENCES documents(document_id),
+    CONSTRAINT fk__document_label_changes__old_labels FOREIGN KEY(old_label_id) REFERENCES sensitivity_labels(label_id),
+    CONSTRAINT fk__document_label_changes__new_labels FOREIGN KEY(new_label_id) REFERENCES sensitivity_labels(label_id),
+    CONSTRAINT fk__document_label_changes__users FOREIGN KEY(changed_by) REFERENCES users(user_id)
+);
+
+CREATE INDEX idx__document_label_changes__document_id ON document_label_changes (document_id);
+
+-- Roles only count for users cleared for the label of the document
+CREATE OR REPLACE VIEW effective_document_version_roles AS
+    SELECT g.user_id, g.document_id, g.version_id, g.role_id
+    FROM (
+        SELECT user_id, document_id, version_id, role_id
+        FROM user_document_version_roles
+        WHERE (valid_from IS NULL OR valid_from <= now())
+        AND (valid_until IS NULL OR valid_until > now())
+        UNION
+        SELECT m.user_id, g.document_id, g.version_id, g.role_id
+        FROM group_document_version_roles g
+        JOIN group_members m ON m.group_id = g.group_id
+        UNION
+        SELECT r.user_id, v.document_id, v.version_id, r.role_id
+        FROM user_document_roles r
+        JOIN document_versions v ON v.document_id = r.document_id
+    ) g
+    JOIN documents d ON d.document_id = g.document_id
+    JOIN users u ON u.user_id = g.user_id
+    WHERE d.label_id <= u.clearance_id;
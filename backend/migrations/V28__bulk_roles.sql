@@ -0,0 +1,2 @@
+-- Versions covered by a consolidated event, its version_id is one of them
+ALTER TABLE events ADD version_ids UUID[] NOT NULL DEFAULT '{}';
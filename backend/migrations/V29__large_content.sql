@@ -0,0 +1,10 @@
+ALTER TABLE document_versions ALTER COLUMN content TYPE text;
+
+-- Content is kept in exactly one place: as plain text in content,
+-- as deflated bytes in content_data or as an object in the bucket
+ALTER TABLE document_versions ADD content_data bytea;
+ALTER TABLE document_versions ADD content_object varchar(255);
+-- 0 plain, 1 deflate, applies to content_data and content_object
+ALTER TABLE document_versions ADD content_encoding smallint NOT NULL DEFAULT 0;
+-- Byte length of content not held in content
+ALTER TABLE document_versions ADD content_length bigint;
@@ -0,0 +1,10 @@
+-- Content of a version with a delta base is a delta against the content of that version
+ALTER TABLE document_versions ADD delta_base_id UUID;
+ALTER TABLE document_versions ADD CONSTRAINT fk__document_versions__delta_bases FOREIGN KEY (document_id, delta_base_id) REFERENCES document_versions (document_id, version_id);
+-- Number of deltas applied on top of the nearest full copy
+ALTER TABLE document_versions ADD delta_depth smallint NOT NULL DEFAULT 0;
+-- Set once the repacking job no longer needs to look at the version
+ALTER TABLE document_versions ADD content_packed boolean NOT NULL DEFAULT false;
+
+CREATE INDEX idx__document_versions__delta_base_id ON document_versions (document_id, delta_base_id);
+CREATE INDEX idx__document_versions__unpacked ON document_versions (created_at) WHERE NOT content_packed;
@@ -0,0 +1,26 @@
+ALTER TABLE documents_dependencies
+    ADD CONSTRAINT ck__documents_dependencies__not_self CHECK (parent_version_id <> child_version_id);
+
+-- Rejects dependencies making a version its own ancestor
+CREATE FUNCTION check_documents_dependencies_acyclic() RETURNS trigger AS $$
+BEGIN
+    IF EXISTS (
+        WITH RECURSIVE ancestors (version_id) AS (
+            SELECT NEW.parent_version_id
+            UNION
+            SELECT d.parent_version_id
+            FROM ancestors a
+            JOIN documents_dependencies d ON d.document_id = NEW.document_id AND d.child_version_id = a.version_id
+        )
+        SELECT * FROM ancestors WHERE version_id = NEW.child_version_id
+    ) THEN
+        RAISE EXCEPTION 'Version % would become its own ancestor', NEW.child_version_id
+            USING ERRCODE = 'check_violation', CONSTRAINT = 'ck__documents_dependencies__acyclic';
+    END IF;
+    RETURN NEW;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER tr__documents_dependencies__acyclic
+    BEFORE INSERT OR UPDATE ON documents_dependencies
+    FOR EACH ROW EXECUTE FUNCTION check_documents_dependencies_acyclic();
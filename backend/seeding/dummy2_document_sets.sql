@@ -36,3 +36,10 @@ INSERT INTO document_sets_dependencies (document_set_id, parent_version_id, chil
     ('65a45040-f418-11ed-a05b-0242ac120003', '88c2e4be-f419-11ed-a05b-0242ac120003', '88c2e78e-f419-11ed-a05b-0242ac120003'),
     ('65a45040-f418-11ed-a05b-0242ac120003', '88c2e4be-f419-11ed-a05b-0242ac120003', '88c2e8d8-f419-11ed-a05b-0242ac120003'),
     ('65a45040-f418-11ed-a05b-0242ac120003', '88c2e78e-f419-11ed-a05b-0242ac120003', '88c2e8d8-f419-11ed-a05b-0242ac120003');
+
+/* Document set roles */
+INSERT INTO user_document_set_roles (user_id, document_set_id, role_id) VALUES
+    ('65a45040-f418-11ed-a05b-0242ac120003', '65a45040-f418-11ed-a05b-0242ac120003', 0),
+    ('65a452ca-f418-11ed-a05b-0242ac120003', '65a45040-f418-11ed-a05b-0242ac120003', 1),
+    ('65a4581a-f418-11ed-a05b-0242ac120003', '65a45040-f418-11ed-a05b-0242ac120003', 2),
+    ('65a452ca-f418-11ed-a05b-0242ac120003', '65a452ca-f418-11ed-a05b-0242ac120003', 0);
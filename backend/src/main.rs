@@ -2,13 +2,17 @@ pub mod models;
 mod routing;
 mod services;
 
+use std::net::SocketAddr;
+
 use ::tracing::info;
 
 use crate::{
     routing::main_route,
     services::{
-        config::setup_config, database::setup_database, s3storage::setup_s3storage,
-        signals::shutdown_signal, state::AppState, tracing::setup_tracing,
+        auth::oidc::setup_oidc, config::setup_config, content_repack::spawn_content_repack,
+        content_storage::ContentStorage, database::setup_database,
+        grant_expiry::spawn_grant_expiry, s3storage::setup_s3storage, signals::shutdown_signal,
+        state::AppState, tracing::setup_tracing,
     },
 };
 
@@ -17,18 +21,38 @@ async fn main() {
     let config = setup_config();
     setup_tracing(&config);
     let auth_keys = (&config.auth_keys).try_into().expect("Missing PEMs");
+    let password_hashing = (&config.password_hashing)
+        .try_into()
+        .expect("Invalid password hashing parameters");
     let database = setup_database(&config).await;
     let s3storage = setup_s3storage(&config).await;
+    let content_storage = ContentStorage::new(&config.content_storage, s3storage.clone());
+    let oidc = setup_oidc(&config).await;
+    spawn_grant_expiry(database.clone(), &config.grant_expiry);
+    spawn_content_repack(
+        database.clone(),
+        content_storage.clone(),
+        &config.content_storage,
+    );
 
     let state = AppState {
         auth_keys,
+        password_hashing,
+        session: config.session.clone(),
+        throttling: config.throttling.clone(),
+        oidc,
         database,
         s3storage,
+        content_storage,
     };
 
     info!("Hosting started. Listening on: {}", &config.webserver.url);
     axum::Server::bind(&config.webserver.url)
-        .serve(main_route(&config).with_state(state).into_make_service())
+        .serve(
+            main_route(&config)
+                .with_state(state)
+                .into_make_service_with_connect_info::<SocketAddr>(),
+        )
         .with_graceful_shutdown(shutdown_signal())
         .await
         .unwrap();
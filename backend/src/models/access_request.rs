@@ -0,0 +1,90 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{role::DocumentVersionRole, user::UserProfile, RowError};
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAccessRequest {
+    pub role: DocumentVersionRole,
+    #[validate(length(max = 1000))]
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[repr(i16)]
+pub enum AccessRequestStatus {
+    Pending = 0,
+    Approved = 1,
+    Denied = 2,
+}
+
+impl TryFrom<i16> for AccessRequestStatus {
+    type Error = i16;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Pending),
+            1 => Ok(Self::Approved),
+            2 => Ok(Self::Denied),
+            _ => Err(value),
+        }
+    }
+}
+
+impl From<AccessRequestStatus> for i16 {
+    fn from(value: AccessRequestStatus) -> Self {
+        value as i16
+    }
+}
+
+/// Request of a user for a role on a version, decided by its owners
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccessRequest {
+    pub request_id: Uuid,
+    pub document_id: Uuid,
+    pub version_id: Uuid,
+    #[serde(flatten)]
+    pub user: UserProfile,
+    pub role: DocumentVersionRole,
+    pub message: Option<String>,
+    pub status: AccessRequestStatus,
+    pub created_at: DateTime<Utc>,
+    pub decided_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<Row> for AccessRequest {
+    type Error = RowError;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let request_id = value.try_get(3)?;
+        let document_id = value.try_get(4)?;
+        let version_id = value.try_get(5)?;
+        let role: i16 = value.try_get(6)?;
+        let role =
+            DocumentVersionRole::try_from(role).map_err(RowError::unknown_value("role_id"))?;
+        let message = value.try_get(7)?;
+        let status: i16 = value.try_get(8)?;
+        let status =
+            AccessRequestStatus::try_from(status).map_err(RowError::unknown_value("status_id"))?;
+        let created_at = value.try_get(9)?;
+        let decided_at = value.try_get(10)?;
+        let user = UserProfile::try_from(value)?;
+        Ok(Self {
+            request_id,
+            document_id,
+            version_id,
+            user,
+            role,
+            message,
+            status,
+            created_at,
+            decided_at,
+        })
+    }
+}
@@ -15,6 +15,7 @@ pub struct DocumentVersionComment {
     pub comment_id: Uuid,
     pub user_id: Uuid,
     pub username: String,
+    pub display_name: Option<String>,
     pub created_at: DateTime<Utc>,
     pub content: String,
 }
@@ -26,12 +27,14 @@ impl TryFrom<Row> for DocumentVersionComment {
         let comment_id = value.try_get(0)?;
         let user_id = value.try_get(1)?;
         let username = value.try_get(2)?;
-        let created_at = value.try_get(3)?;
-        let content = value.try_get(4)?;
+        let display_name = value.try_get(3)?;
+        let created_at = value.try_get(4)?;
+        let content = value.try_get(5)?;
         Ok(Self {
             comment_id,
             user_id,
             username,
+            display_name,
             created_at,
             content,
         })
@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+/// Versions to compare, a single one is compared with its parent
+#[derive(Debug, Deserialize)]
+pub struct DiffQuery {
+    pub from: Option<Uuid>,
+    pub to: Option<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionDiff {
+    /// Missing when a version without parents is compared with empty content
+    pub from: Option<Uuid>,
+    pub to: Uuid,
+    /// Unified diff with 3 lines of context
+    pub unified: String,
+    pub hunks: Vec<DiffHunk>,
+}
+
+/// Lines are numbered from 1 like in the unified diff
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffHunk {
+    pub old_start: usize,
+    pub old_lines: usize,
+    pub new_start: usize,
+    pub new_lines: usize,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DiffTag {
+    Equal,
+    Delete,
+    Insert,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub tag: DiffTag,
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub content: String,
+    /// Word level changes of a line replacing another, empty otherwise
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub words: Vec<DiffWord>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffWord {
+    pub changed: bool,
+    pub value: String,
+}
@@ -3,7 +3,11 @@ use tokio_postgres::Row;
 use uuid::Uuid;
 use validator::Validate;
 
-use super::version::{CreateInitialVersion, DocumentVersion};
+use super::{
+    sensitivity_label::SensitivityLabel,
+    version::{CreateInitialVersion, DocumentVersion},
+    RowError,
+};
 
 #[derive(Debug, Validate, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -18,17 +22,21 @@ pub struct CreateDocument {
 pub struct Document {
     pub document_id: Uuid,
     pub document_name: String,
+    pub label: SensitivityLabel,
 }
 
 impl TryFrom<Row> for Document {
-    type Error = tokio_postgres::Error;
+    type Error = RowError;
 
     fn try_from(value: Row) -> Result<Self, Self::Error> {
         let document_id: Uuid = value.try_get(0)?;
         let document_name: String = value.try_get(1)?;
+        let label: i16 = value.try_get(2)?;
         Ok(Self {
             document_id,
             document_name,
+            label: SensitivityLabel::try_from(label)
+                .map_err(RowError::unknown_value("label_id"))?,
         })
     }
 }
@@ -3,7 +3,11 @@ use tokio_postgres::Row;
 use uuid::Uuid;
 use validator::Validate;
 
-use super::set_version::{CreateInitialSetVersion, SetVersion};
+use super::{
+    role::DocumentSetRole,
+    set_version::{CreateInitialSetVersion, SetVersion},
+    user::UserProfile,
+};
 
 #[derive(Debug, Validate, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -40,3 +44,26 @@ pub struct DocumentSetWithInitialVersion {
     pub document_set: DocumentSet,
     pub initial_version: SetVersion,
 }
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentSetMember {
+    #[serde(flatten)]
+    pub user: UserProfile,
+    pub roles: Vec<DocumentSetRole>,
+}
+
+impl TryFrom<Row> for DocumentSetMember {
+    type Error = tokio_postgres::Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let roles: Vec<i16> = value.try_get(3)?;
+        let roles = roles
+            .into_iter()
+            .filter_map(|v| DocumentSetRole::try_from(v).ok())
+            .collect();
+        let user = UserProfile::try_from(value)?;
+
+        Ok(Self { user, roles })
+    }
+}
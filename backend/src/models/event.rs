@@ -12,16 +12,27 @@ pub struct Event {
     pub document_id: Uuid,
     pub version_id: Uuid,
     pub event_type: EventType,
+    /// Every version covered by a consolidated event, empty for other events
+    pub versions: Vec<Uuid>,
     pub seen: bool,
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub enum EventType {
     RoleAdded(DocumentVersionRole),
     RoleRemoved(DocumentVersionRole),
     StatusChange(DocumentVersionState),
+    /// An owner offered to transfer the version to the user
+    OwnershipOffered,
+    /// Someone asked the owner for a role
+    AccessRequested(DocumentVersionRole),
+    AccessDenied(DocumentVersionRole),
+    /// Role granted on several versions at once
+    RolesAdded(DocumentVersionRole),
+    /// Role, or every role but owner if missing, revoked from several versions at once
+    RolesRemoved(Option<DocumentVersionRole>),
 }
 
 impl TryFrom<Row> for Event {
@@ -40,12 +51,14 @@ impl TryFrom<Row> for Event {
         let event_type = from_sql(event_type_id, role_id, state_id);
         let seen = value.try_get(7)?;
         let created_at = value.try_get(8)?;
+        let versions = value.try_get(9)?;
         Ok(Self {
             event_id,
             user_id,
             document_id,
             version_id,
             event_type,
+            versions,
             seen,
             created_at,
         })
@@ -61,6 +74,11 @@ pub fn from_sql(
         0 => EventType::RoleAdded(role.unwrap()),
         1 => EventType::RoleRemoved(role.unwrap()),
         2 => EventType::StatusChange(state.unwrap()),
+        3 => EventType::OwnershipOffered,
+        4 => EventType::AccessRequested(role.unwrap()),
+        5 => EventType::AccessDenied(role.unwrap()),
+        6 => EventType::RolesAdded(role.unwrap()),
+        7 => EventType::RolesRemoved(role),
         _ => unreachable!(),
     }
 }
@@ -70,5 +88,10 @@ pub fn to_sql(event_type: &EventType) -> (i16, Option<i16>, Option<i16>) {
         EventType::RoleAdded(role) => (0, Some(i16::from(*role)), None),
         EventType::RoleRemoved(role) => (1, Some(i16::from(*role)), None),
         EventType::StatusChange(state) => (2, None, Some(i16::from(*state))),
+        EventType::OwnershipOffered => (3, None, None),
+        EventType::AccessRequested(role) => (4, Some(i16::from(*role)), None),
+        EventType::AccessDenied(role) => (5, Some(i16::from(*role)), None),
+        EventType::RolesAdded(role) => (6, Some(i16::from(*role)), None),
+        EventType::RolesRemoved(role) => (7, role.map(i16::from), None),
     }
 }
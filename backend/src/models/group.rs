@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::{role::DocumentVersionRole, user::UserProfile, RowError};
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateGroup {
+    #[validate(length(min = 1, max = 255))]
+    pub group_name: String,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateGroup {
+    #[validate(length(min = 1, max = 255))]
+    pub group_name: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AddGroupMember {
+    #[serde(default)]
+    pub is_manager: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Group {
+    pub group_id: Uuid,
+    pub group_name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<Row> for Group {
+    type Error = tokio_postgres::Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let group_id = value.try_get(0)?;
+        let group_name = value.try_get(1)?;
+        let created_at = value.try_get(2)?;
+        Ok(Self {
+            group_id,
+            group_name,
+            created_at,
+        })
+    }
+}
+
+/// Managers can rename the group and change its membership
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupMember {
+    #[serde(flatten)]
+    pub user: UserProfile,
+    pub is_manager: bool,
+}
+
+impl TryFrom<Row> for GroupMember {
+    type Error = tokio_postgres::Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let is_manager = value.try_get(3)?;
+        let user = UserProfile::try_from(value)?;
+        Ok(Self { user, is_manager })
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupWithMembers {
+    #[serde(flatten)]
+    pub group: Group,
+    pub members: Vec<GroupMember>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GroupWithRoles {
+    #[serde(flatten)]
+    pub group: Group,
+    pub roles: Vec<DocumentVersionRole>,
+}
+
+impl TryFrom<Row> for GroupWithRoles {
+    type Error = RowError;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let roles: Vec<i16> = value.try_get(3)?;
+        let roles = roles
+            .into_iter()
+            .map(DocumentVersionRole::try_from)
+            .collect::<Result<_, _>>()
+            .map_err(RowError::unknown_value("role_id"))?;
+        let group = Group::try_from(value)?;
+        Ok(Self { group, roles })
+    }
+}
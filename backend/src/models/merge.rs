@@ -0,0 +1,34 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+/// The first parent is merged with the second one
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeParents {
+    #[validate(length(min = 2, max = 2))]
+    pub parents: Vec<Uuid>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergePreview {
+    /// Lowest common ancestor of the parents, the merge starts from empty content without one
+    pub base: Option<Uuid>,
+    pub parents: Vec<Uuid>,
+    pub clean: bool,
+    /// Merged content, conflicting changes are wrapped in conflict markers
+    pub content: String,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MergeConflict {
+    /// Line of the opening conflict marker in the merged content, numbered from 1
+    pub line: usize,
+    /// Lines of the first parent
+    pub ours: String,
+    /// Lines of the second parent
+    pub theirs: String,
+}
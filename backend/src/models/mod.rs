@@ -1,17 +1,66 @@
+pub mod access_request;
 pub mod attachment;
 pub mod comment;
+pub mod diff;
 pub mod document;
 pub mod document_set;
 pub mod event;
+pub mod group;
+pub mod merge;
+pub mod ownership;
+pub mod personal_token;
 pub mod role;
+pub mod security_event;
+pub mod sensitivity_label;
+pub mod session;
 pub mod set_version;
+pub mod share_link;
+pub mod totp;
 pub mod user;
 pub mod version;
 pub mod version_state;
 
+use std::{error::Error, fmt::Display};
+
 use lazy_static::lazy_static;
 use regex::Regex;
 
 lazy_static! {
     static ref VERSION_NAME_REGEX: Regex = Regex::new(r"^\d+(\.\d+)*$").unwrap();
 }
+
+/// Failure to read a model from a database row
+#[derive(Debug)]
+pub enum RowError {
+    Database(tokio_postgres::Error),
+    /// A column held a value its enum doesn't know
+    UnknownValue {
+        column: &'static str,
+        value: i16,
+    },
+}
+
+impl RowError {
+    pub fn unknown_value(column: &'static str) -> impl Fn(i16) -> Self {
+        move |value| Self::UnknownValue { column, value }
+    }
+}
+
+impl From<tokio_postgres::Error> for RowError {
+    fn from(value: tokio_postgres::Error) -> Self {
+        Self::Database(value)
+    }
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RowError::Database(error) => Display::fmt(error, f),
+            RowError::UnknownValue { column, value } => {
+                write!(f, "Unknown value {value} in column {column}")
+            }
+        }
+    }
+}
+
+impl Error for RowError {}
@@ -0,0 +1,37 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+/// Offer to hand the ownership of a version over, pending until the recipient accepts
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OwnershipTransfer {
+    pub transfer_id: Uuid,
+    pub document_id: Uuid,
+    pub version_id: Uuid,
+    pub from_user_id: Uuid,
+    pub to_user_id: Uuid,
+    pub created_at: DateTime<Utc>,
+}
+
+impl TryFrom<Row> for OwnershipTransfer {
+    type Error = tokio_postgres::Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let transfer_id = value.try_get(0)?;
+        let document_id = value.try_get(1)?;
+        let version_id = value.try_get(2)?;
+        let from_user_id = value.try_get(3)?;
+        let to_user_id = value.try_get(4)?;
+        let created_at = value.try_get(5)?;
+        Ok(Self {
+            transfer_id,
+            document_id,
+            version_id,
+            from_user_id,
+            to_user_id,
+            created_at,
+        })
+    }
+}
@@ -0,0 +1,204 @@
+use axum::http::Method;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::RowError;
+
+/// Prefix which tells personal tokens apart from JWTs
+pub const PERSONAL_TOKEN_PREFIX: &str = "apsi_pat_";
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[repr(i16)]
+pub enum TokenScope {
+    /// Any read-only request
+    Read = 0,
+    /// Creating and modifying documents, versions, comments, sets and members
+    Write = 1,
+    /// Uploading and removing file attachments
+    Attachments = 2,
+    /// Changing version states
+    States = 3,
+}
+
+impl TryFrom<i16> for TokenScope {
+    type Error = i16;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Read),
+            1 => Ok(Self::Write),
+            2 => Ok(Self::Attachments),
+            3 => Ok(Self::States),
+            _ => Err(value),
+        }
+    }
+}
+
+impl From<TokenScope> for i16 {
+    fn from(value: TokenScope) -> Self {
+        value as i16
+    }
+}
+
+impl TokenScope {
+    /// Scope required to call a route, `None` if personal tokens can't call it at all
+    pub fn required_for(method: &Method, route: &str) -> Option<Self> {
+        if route.starts_with("/api/admin") {
+            return (method == Method::GET).then_some(Self::Read);
+        }
+        if route.starts_with("/api/auth") {
+            let allowed = method == Method::GET && !route.starts_with("/api/auth/tokens");
+            return allowed.then_some(Self::Read);
+        }
+        if method == Method::GET {
+            Some(Self::Read)
+        } else if route.contains("/files") {
+            Some(Self::Attachments)
+        } else if route.ends_with("/change-state") {
+            Some(Self::States)
+        } else {
+            Some(Self::Write)
+        }
+    }
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatePersonalToken {
+    #[validate(length(min = 1, max = 255))]
+    pub token_name: String,
+    #[validate(length(min = 1))]
+    pub scopes: Vec<TokenScope>,
+    #[validate(range(min = 1, max = 365))]
+    pub expires_in_days: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PersonalToken {
+    pub token_id: Uuid,
+    pub token_name: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub last_used_at: Option<DateTime<Utc>>,
+}
+
+impl TryFrom<Row> for PersonalToken {
+    type Error = RowError;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let token_id = value.try_get(0)?;
+        let token_name = value.try_get(1)?;
+        let scopes: Vec<i16> = value.try_get(2)?;
+        let scopes = scopes
+            .into_iter()
+            .map(TokenScope::try_from)
+            .collect::<Result<_, _>>()
+            .map_err(RowError::unknown_value("scopes"))?;
+        let created_at = value.try_get(3)?;
+        let expires_at = value.try_get(4)?;
+        let last_used_at = value.try_get(5)?;
+        Ok(Self {
+            token_id,
+            token_name,
+            scopes,
+            created_at,
+            expires_at,
+            last_used_at,
+        })
+    }
+}
+
+/// Newly created token, the only time its secret is revealed
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedPersonalToken {
+    #[serde(flatten)]
+    pub personal_token: PersonalToken,
+    pub token: String,
+}
+
+/// Token owner and permissions resolved from a presented secret
+pub struct AuthenticatedPersonalToken {
+    pub token_id: Uuid,
+    pub user_id: Uuid,
+    pub username: String,
+    pub scopes: Vec<TokenScope>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl TryFrom<Row> for AuthenticatedPersonalToken {
+    type Error = RowError;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let token_id = value.try_get(0)?;
+        let user_id = value.try_get(1)?;
+        let username = value.try_get(2)?;
+        let scopes: Vec<i16> = value.try_get(3)?;
+        let scopes = scopes
+            .into_iter()
+            .map(TokenScope::try_from)
+            .collect::<Result<_, _>>()
+            .map_err(RowError::unknown_value("scopes"))?;
+        let created_at = value.try_get(4)?;
+        let expires_at = value.try_get(5)?;
+        Ok(Self {
+            token_id,
+            user_id,
+            username,
+            scopes,
+            created_at,
+            expires_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+
+    use super::TokenScope;
+
+    #[test]
+    fn required_scopes() {
+        let required = |method, route| TokenScope::required_for(&method, route);
+        assert_eq!(
+            required(Method::GET, "/api/documents/:document_id/versions"),
+            Some(TokenScope::Read)
+        );
+        assert_eq!(
+            required(
+                Method::PATCH,
+                "/api/documents/:document_id/:version_id/files"
+            ),
+            Some(TokenScope::Attachments)
+        );
+        assert_eq!(
+            required(
+                Method::POST,
+                "/api/documents/:document_id/:version_id/change-state"
+            ),
+            Some(TokenScope::States)
+        );
+        assert_eq!(
+            required(Method::POST, "/api/documents/:document_id"),
+            Some(TokenScope::Write)
+        );
+        assert_eq!(required(Method::GET, "/api/auth/tokens"), None);
+        assert_eq!(required(Method::POST, "/api/auth/logout"), None);
+        assert_eq!(
+            required(Method::GET, "/api/admin/users"),
+            Some(TokenScope::Read)
+        );
+        assert_eq!(
+            required(Method::POST, "/api/admin/users/:user_id/disable"),
+            None
+        );
+    }
+}
@@ -1,6 +1,9 @@
-use std::convert::Infallible;
-
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use validator::Validate;
+
+use super::version_state::DocumentVersionState;
 
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -25,9 +28,8 @@ pub enum DocumentVersionRole {
     Reviewer = 3,
 }
 
-// TODO: handle a very unlikely case of invalid value properly
 impl TryFrom<i16> for DocumentVersionRole {
-    type Error = Infallible;
+    type Error = i16;
 
     fn try_from(value: i16) -> Result<Self, Self::Error> {
         match value {
@@ -35,7 +37,7 @@ impl TryFrom<i16> for DocumentVersionRole {
             1 => Ok(Self::Viewer),
             2 => Ok(Self::Editor),
             3 => Ok(Self::Reviewer),
-            _ => unreachable!(),
+            _ => Err(value),
         }
     }
 }
@@ -45,3 +47,152 @@ impl From<DocumentVersionRole> for i16 {
         value as i16
     }
 }
+
+impl DocumentVersionRole {
+    /// Roles out of `held` (version and role pairs) which are held on every one of the versions
+    pub fn held_on_every_version(versions: &[Uuid], held: &[(Uuid, Self)]) -> Vec<Self> {
+        let mut roles: Vec<Self> = vec![];
+        for (_, role) in held {
+            if !roles.contains(role)
+                && versions
+                    .iter()
+                    .all(|version_id| held.contains(&(*version_id, *role)))
+            {
+                roles.push(*role);
+            }
+        }
+        roles
+    }
+}
+
+/// Window in which a granted role is in effect, unbounded by default
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GrantValidity {
+    pub valid_from: Option<DateTime<Utc>>,
+    pub valid_until: Option<DateTime<Utc>>,
+}
+
+impl GrantValidity {
+    /// Whether the window ends after it starts and is not over yet
+    pub fn is_valid(&self) -> bool {
+        match (self.valid_from, self.valid_until) {
+            (Some(from), Some(until)) => from < until && Utc::now() < until,
+            (None, Some(until)) => Utc::now() < until,
+            _ => true,
+        }
+    }
+}
+
+/// Versions of a document a bulk role change applies to
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VersionSelector {
+    All,
+    /// Versions without children
+    Heads,
+    State(DocumentVersionState),
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkGrant {
+    #[validate(length(min = 1, max = 100))]
+    pub users: Vec<Uuid>,
+    pub role: DocumentVersionRole,
+    pub versions: VersionSelector,
+    #[serde(flatten)]
+    pub validity: GrantValidity,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkRevoke {
+    #[validate(length(min = 1, max = 100))]
+    pub users: Vec<Uuid>,
+    /// Every role except owner if missing
+    pub role: Option<DocumentVersionRole>,
+    pub versions: VersionSelector,
+}
+
+/// Versions a bulk role change actually modified for a user
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BulkRoleChange {
+    pub user_id: Uuid,
+    pub versions: Vec<Uuid>,
+    /// Versions where a bulk grant only replaced the validity window of a role the user held
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub updated_versions: Vec<Uuid>,
+    /// Roles revoked on the whole document, only when revoking from all versions
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub document_roles: Vec<DocumentVersionRole>,
+    /// Revoked roles still held on the selected versions through groups or the whole document
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub retained_roles: Vec<DocumentVersionRole>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[repr(i16)]
+pub enum DocumentSetRole {
+    Owner = 0,
+    Editor = 1,
+    Viewer = 2,
+}
+
+impl TryFrom<i16> for DocumentSetRole {
+    type Error = i16;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Owner),
+            1 => Ok(Self::Editor),
+            2 => Ok(Self::Viewer),
+            _ => Err(value),
+        }
+    }
+}
+
+impl From<DocumentSetRole> for i16 {
+    fn from(value: DocumentSetRole) -> Self {
+        value as i16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+
+    use crate::models::version_state::DocumentVersionState;
+
+    use super::{GrantValidity, VersionSelector};
+
+    #[test]
+    fn grant_validity() {
+        let now = Utc::now();
+        let validity = |from: Option<i64>, until: Option<i64>| GrantValidity {
+            valid_from: from.map(|h| now + Duration::hours(h)),
+            valid_until: until.map(|h| now + Duration::hours(h)),
+        };
+        assert!(GrantValidity::default().is_valid());
+        assert!(validity(Some(1), None).is_valid());
+        assert!(validity(Some(-1), None).is_valid());
+        assert!(validity(None, Some(1)).is_valid());
+        assert!(validity(Some(1), Some(2)).is_valid());
+        assert!(!validity(None, Some(-1)).is_valid());
+        assert!(!validity(Some(2), Some(1)).is_valid());
+        assert!(!validity(Some(-2), Some(-1)).is_valid());
+    }
+
+    #[test]
+    fn version_selectors() {
+        let parse = |json| serde_json::from_str::<VersionSelector>(json).unwrap();
+        assert!(matches!(parse(r#""all""#), VersionSelector::All));
+        assert!(matches!(parse(r#""heads""#), VersionSelector::Heads));
+        assert!(matches!(
+            parse(r#"{"state": "published"}"#),
+            VersionSelector::State(DocumentVersionState::Published)
+        ));
+    }
+}
@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tokio_postgres::Row;
+use uuid::Uuid;
+
+use super::RowError;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SecurityEvent {
+    pub event_id: Uuid,
+    pub event_type: SecurityEventType,
+    pub user_id: Option<Uuid>,
+    pub username: Option<String>,
+    pub address: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "camelCase")]
+#[repr(i16)]
+pub enum SecurityEventType {
+    /// Too many failed logins for an account
+    AccountLocked = 0,
+    /// Too many failed logins from an address
+    AddressBlocked = 1,
+}
+
+impl TryFrom<i16> for SecurityEventType {
+    type Error = i16;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::AccountLocked),
+            1 => Ok(Self::AddressBlocked),
+            _ => Err(value),
+        }
+    }
+}
+
+impl From<SecurityEventType> for i16 {
+    fn from(value: SecurityEventType) -> Self {
+        value as i16
+    }
+}
+
+impl TryFrom<Row> for SecurityEvent {
+    type Error = RowError;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let event_type: i16 = value.try_get(1)?;
+        Ok(Self {
+            event_id: value.try_get(0)?,
+            event_type: SecurityEventType::try_from(event_type)
+                .map_err(RowError::unknown_value("event_type"))?,
+            user_id: value.try_get(2)?,
+            username: value.try_get(3)?,
+            address: value.try_get(4)?,
+            created_at: value.try_get(5)?,
+        })
+    }
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+use uuid::Uuid;
+use validator::Validate;
+
+use super::RowError;
+
+/// Classification of a document, or clearance of a user when used as such.
+/// Users only hold roles on documents labeled at or below their clearance
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+#[repr(i16)]
+pub enum SensitivityLabel {
+    Public = 0,
+    Internal = 1,
+    Confidential = 2,
+    Secret = 3,
+}
+
+impl TryFrom<i16> for SensitivityLabel {
+    type Error = i16;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Public),
+            1 => Ok(Self::Internal),
+            2 => Ok(Self::Confidential),
+            3 => Ok(Self::Secret),
+            _ => Err(value),
+        }
+    }
+}
+
+impl From<SensitivityLabel> for i16 {
+    fn from(value: SensitivityLabel) -> Self {
+        value as i16
+    }
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateDocumentLabel {
+    pub label: SensitivityLabel,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateClearance {
+    pub clearance: SensitivityLabel,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DocumentLabelChange {
+    pub change_id: Uuid,
+    pub document_id: Uuid,
+    pub old_label: SensitivityLabel,
+    pub new_label: SensitivityLabel,
+    pub changed_by: Uuid,
+    pub changed_at: DateTime<Utc>,
+}
+
+impl TryFrom<Row> for DocumentLabelChange {
+    type Error = RowError;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let change_id = value.try_get(0)?;
+        let document_id = value.try_get(1)?;
+        let old_label: i16 = value.try_get(2)?;
+        let new_label: i16 = value.try_get(3)?;
+        let changed_by = value.try_get(4)?;
+        let changed_at = value.try_get(5)?;
+        Ok(Self {
+            change_id,
+            document_id,
+            old_label: SensitivityLabel::try_from(old_label)
+                .map_err(RowError::unknown_value("old_label_id"))?,
+            new_label: SensitivityLabel::try_from(new_label)
+                .map_err(RowError::unknown_value("new_label_id"))?,
+            changed_by,
+            changed_at,
+        })
+    }
+}
@@ -0,0 +1,9 @@
+use uuid::Uuid;
+
+/// Session along with its freshly minted refresh token
+pub struct Session {
+    pub session_id: Uuid,
+    pub user_id: Uuid,
+    pub username: String,
+    pub refresh_token: String,
+}
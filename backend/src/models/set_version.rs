@@ -52,10 +52,7 @@ impl TryFrom<Row> for SetVersion {
         let created_at = value.try_get(3)?;
         let document_ids: Vec<Uuid> = value.try_get(4)?;
         let version_ids: Vec<Uuid> = value.try_get(5)?;
-        let document_version_ids = document_ids
-            .into_iter()
-            .zip(version_ids.into_iter())
-            .collect();
+        let document_version_ids = document_ids.into_iter().zip(version_ids).collect();
         let children = value.try_get(6)?;
         let parents = value.try_get(7)?;
 
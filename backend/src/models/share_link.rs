@@ -0,0 +1,97 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+use uuid::Uuid;
+use validator::Validate;
+
+/// Header carrying the password of a protected share link
+pub const SHARE_PASSWORD_HEADER: &str = "x-share-password";
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateShareLink {
+    /// Link never expires if missing
+    pub expires_at: Option<DateTime<Utc>>,
+    /// Link is open to anyone knowing it if missing
+    #[validate(length(min = 8, max = 255))]
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ShareLink {
+    pub link_id: Uuid,
+    pub document_id: Uuid,
+    pub version_id: Uuid,
+    pub created_by: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub password_protected: bool,
+}
+
+impl TryFrom<Row> for ShareLink {
+    type Error = tokio_postgres::Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let link_id = value.try_get(0)?;
+        let document_id = value.try_get(1)?;
+        let version_id = value.try_get(2)?;
+        let created_by = value.try_get(3)?;
+        let created_at = value.try_get(4)?;
+        let expires_at = value.try_get(5)?;
+        let password_protected = value.try_get(6)?;
+        Ok(Self {
+            link_id,
+            document_id,
+            version_id,
+            created_by,
+            created_at,
+            expires_at,
+            password_protected,
+        })
+    }
+}
+
+/// Newly created link, the only time its token is revealed
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreatedShareLink {
+    #[serde(flatten)]
+    pub share_link: ShareLink,
+    pub token: String,
+}
+
+/// Version and password of a presented, still valid link
+pub struct ResolvedShareLink {
+    pub link_id: Uuid,
+    pub document_id: Uuid,
+    pub version_id: Uuid,
+    pub password_hash: Option<String>,
+}
+
+impl TryFrom<Row> for ResolvedShareLink {
+    type Error = tokio_postgres::Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let link_id = value.try_get(0)?;
+        let document_id = value.try_get(1)?;
+        let version_id = value.try_get(2)?;
+        let password_hash = value.try_get(3)?;
+        Ok(Self {
+            link_id,
+            document_id,
+            version_id,
+            password_hash,
+        })
+    }
+}
+
+/// Read-only view of a shared version
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SharedVersion {
+    pub document_name: String,
+    pub version_name: String,
+    pub content: String,
+    pub updated_at: DateTime<Utc>,
+}
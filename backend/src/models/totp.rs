@@ -0,0 +1,64 @@
+use serde::{Deserialize, Serialize};
+use tokio_postgres::Row;
+use validator::Validate;
+
+/// Secret of a pending enrolment, valid once confirmed with a code
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpEnrolment {
+    pub secret: String,
+    pub otpauth_uri: String,
+}
+
+/// Single use codes, only ever shown when generated
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryCodes {
+    pub recovery_codes: Vec<String>,
+}
+
+/// TOTP code or, where allowed, a recovery code
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TotpCode {
+    #[validate(length(min = 1, max = 32))]
+    pub code: String,
+}
+
+/// Second login step which must be answered with a code
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LoginChallenge {
+    pub challenge: String,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChallengeAnswer {
+    #[validate(length(equal = 64))]
+    pub challenge: String,
+    #[validate(length(min = 1, max = 32))]
+    pub code: String,
+}
+
+/// Stored TOTP settings of a user
+pub struct UserTotp {
+    pub secret: String,
+    pub enabled: bool,
+    pub last_used_step: Option<i64>,
+}
+
+impl TryFrom<Row> for UserTotp {
+    type Error = tokio_postgres::Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let secret = value.try_get(0)?;
+        let enabled = value.try_get(1)?;
+        let last_used_step = value.try_get(2)?;
+        Ok(Self {
+            secret,
+            enabled,
+            last_used_step,
+        })
+    }
+}
@@ -1,14 +1,16 @@
-use serde::Serialize;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Deserializer, Serialize};
 use tokio_postgres::Row;
 use uuid::Uuid;
+use validator::Validate;
 
-use super::role::DocumentVersionRole;
+use super::{role::DocumentVersionRole, sensitivity_label::SensitivityLabel, RowError};
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PublicUserWithRoles {
     #[serde(flatten)]
-    pub user: PublicUser,
+    pub user: UserProfile,
     pub roles: Vec<DocumentVersionRole>,
 }
 
@@ -16,18 +18,14 @@ impl TryFrom<Row> for PublicUserWithRoles {
     type Error = tokio_postgres::Error;
 
     fn try_from(value: Row) -> Result<Self, Self::Error> {
-        let user_id: Uuid = value.try_get(0)?;
-        let username: String = value.try_get(1)?;
-        let roles: Vec<i16> = value.try_get(2)?;
+        let roles: Vec<i16> = value.try_get(3)?;
         let roles: Vec<DocumentVersionRole> = roles
             .into_iter()
             .map(|v| DocumentVersionRole::try_from(v).unwrap())
             .collect();
+        let user = UserProfile::try_from(value)?;
 
-        Ok(Self {
-            user: PublicUser { user_id, username },
-            roles,
-        })
+        Ok(Self { user, roles })
     }
 }
 
@@ -48,11 +46,92 @@ impl TryFrom<Row> for PublicUser {
     }
 }
 
+/// Public user along with the name they'd like to be shown as
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UserProfile {
+    #[serde(flatten)]
+    pub user: PublicUser,
+    pub display_name: Option<String>,
+}
+
+impl TryFrom<Row> for UserProfile {
+    type Error = tokio_postgres::Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let display_name: Option<String> = value.try_get(2)?;
+        let user = PublicUser::try_from(value)?;
+        Ok(Self { user, display_name })
+    }
+}
+
+/// Profile as seen by its owner
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountDetails {
+    #[serde(flatten)]
+    pub profile: UserProfile,
+    pub email: Option<String>,
+}
+
+impl TryFrom<Row> for AccountDetails {
+    type Error = tokio_postgres::Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let email: Option<String> = value.try_get(3)?;
+        let profile = UserProfile::try_from(value)?;
+        Ok(Self { profile, email })
+    }
+}
+
+/// Distinguishes a field set to `null` from a missing one
+fn double_option<'de, D, T>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::deserialize(deserializer).map(Some)
+}
+
+/// Missing fields are left as they are, `null` clears them
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct UpdateProfile {
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(length(min = 1, max = 255))]
+    pub display_name: Option<Option<String>>,
+    #[serde(default, deserialize_with = "double_option")]
+    #[validate(email, length(max = 255))]
+    pub email: Option<Option<String>>,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChangePassword {
+    pub old_password: String,
+    #[validate(length(min = 8, max = 255))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeleteAccount {
+    pub password: String,
+}
+
 pub struct User {
     pub user_id: Uuid,
-    pub salt: Uuid,
+    pub salt: Option<Uuid>,
     pub username: String,
     pub password_hash: String,
+    pub disabled: bool,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+impl User {
+    pub fn is_locked(&self) -> bool {
+        matches!(self.locked_until, Some(until) if until > Utc::now())
+    }
 }
 
 impl TryFrom<Row> for User {
@@ -60,14 +139,74 @@ impl TryFrom<Row> for User {
 
     fn try_from(value: Row) -> Result<Self, Self::Error> {
         let user_id: Uuid = value.try_get(0)?;
-        let salt: Uuid = value.try_get(1)?;
+        let salt: Option<Uuid> = value.try_get(1)?;
         let username: String = value.try_get(2)?;
         let password_hash: String = value.try_get(3)?;
+        let disabled: bool = value.try_get(7)?;
+        let locked_until: Option<DateTime<Utc>> = value.try_get(8)?;
         Ok(Self {
             user_id,
             salt,
             username,
             password_hash,
+            disabled,
+            locked_until,
+        })
+    }
+}
+
+/// Account as seen by admins
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ManagedUser {
+    #[serde(flatten)]
+    pub account: AccountDetails,
+    pub is_admin: bool,
+    pub disabled: bool,
+    pub locked_until: Option<DateTime<Utc>>,
+    /// Most sensitive label of documents the user may access
+    pub clearance: SensitivityLabel,
+}
+
+impl TryFrom<Row> for ManagedUser {
+    type Error = RowError;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let is_admin: bool = value.try_get(4)?;
+        let disabled: bool = value.try_get(5)?;
+        let locked_until: Option<DateTime<Utc>> = value.try_get(6)?;
+        let clearance: i16 = value.try_get(7)?;
+        let account = AccountDetails::try_from(value)?;
+        Ok(Self {
+            account,
+            is_admin,
+            disabled,
+            locked_until,
+            clearance: SensitivityLabel::try_from(clearance)
+                .map_err(RowError::unknown_value("clearance_id"))?,
         })
     }
 }
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LockUser {
+    #[validate(range(min = 1, max = 525600))]
+    pub duration_minutes: i64,
+}
+
+/// One-time token an admin hands over to let a user choose a new password
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PasswordReset {
+    pub reset_token: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Validate, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RedeemPasswordReset {
+    pub reset_token: String,
+    #[validate(length(min = 8, max = 255))]
+    pub new_password: String,
+}
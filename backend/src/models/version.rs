@@ -4,21 +4,24 @@ use tokio_postgres::Row;
 use uuid::Uuid;
 use validator::Validate;
 
-use super::{version_state::DocumentVersionState, VERSION_NAME_REGEX};
+use super::{role::DocumentVersionRole, version_state::DocumentVersionState, VERSION_NAME_REGEX};
+
+/// Longest accepted version content in characters
+pub const MAX_CONTENT_LENGTH: u64 = 8 * 1024 * 1024;
 
 #[derive(Debug, Validate, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CreateInitialVersion {
     #[validate(regex = "VERSION_NAME_REGEX")]
     pub version_name: String,
-    #[validate(length(max = 2046))]
+    #[validate(length(max = "MAX_CONTENT_LENGTH"))]
     pub content: String,
 }
 
 #[derive(Debug, Validate, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UpdateVersion {
-    #[validate(length(max = 2046))]
+    #[validate(length(max = "MAX_CONTENT_LENGTH"))]
     pub content: String,
     pub updated_at: DateTime<Utc>,
 }
@@ -28,10 +31,27 @@ pub struct UpdateVersion {
 pub struct CreateVersionWithParents {
     #[validate(regex = "VERSION_NAME_REGEX")]
     pub version_name: String,
-    #[validate(length(max = 2046))]
+    /// Ignored when merging
+    #[serde(default)]
+    #[validate(length(max = "MAX_CONTENT_LENGTH"))]
     pub content: String,
     #[validate(length(min = 1))]
     pub parents: Vec<Uuid>,
+    /// Use the three-way merge of the two parents as content, nothing is created on conflicts
+    #[serde(default)]
+    pub merge: bool,
+    /// Copy members of the parent versions, none are copied if missing
+    pub inherit_members: Option<InheritMembers>,
+}
+
+/// Which roles of the parent versions are copied to a new one, owners are never copied
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct InheritMembers {
+    /// Only copy these roles, all of them if missing
+    pub roles: Option<Vec<DocumentVersionRole>>,
+    /// Only copy roles of these users, everyone's along with group roles if missing
+    pub users: Option<Vec<Uuid>>,
 }
 
 #[derive(Debug, Serialize)]
@@ -41,7 +61,11 @@ pub struct DocumentVersion {
     pub version_id: Uuid,
     pub version_name: String,
     pub created_at: DateTime<Utc>,
-    pub content: String,
+    /// Missing when versions are listed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+    /// Size of the content in bytes
+    pub content_length: i64,
     pub version_state: DocumentVersionState,
     pub children: Vec<Uuid>,
     pub parents: Vec<Uuid>,
@@ -56,7 +80,7 @@ impl TryFrom<Row> for DocumentVersion {
         let version_id: Uuid = value.try_get(1)?;
         let version_name: String = value.try_get(2)?;
         let created_at: DateTime<Utc> = value.try_get(3)?;
-        let content: String = value.try_get(4)?;
+        let content_length: i64 = value.try_get(4)?;
         let version_state: i16 = value.try_get(5)?;
         let version_state = DocumentVersionState::try_from(version_state).unwrap();
         let updated_at: DateTime<Utc> = value.try_get(6)?;
@@ -68,7 +92,8 @@ impl TryFrom<Row> for DocumentVersion {
             version_id,
             version_name,
             created_at,
-            content,
+            content: None,
+            content_length,
             version_state,
             updated_at,
             children,
@@ -76,3 +101,33 @@ impl TryFrom<Row> for DocumentVersion {
         })
     }
 }
+
+/// Version in the version graph along with where to draw it
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionGraphNode {
+    #[serde(flatten)]
+    pub version: DocumentVersion,
+    /// Longest path from a version without parents, a version always comes after its parents
+    pub generation: i32,
+}
+
+impl TryFrom<Row> for VersionGraphNode {
+    type Error = tokio_postgres::Error;
+
+    fn try_from(value: Row) -> Result<Self, Self::Error> {
+        let generation: i32 = value.try_get(9)?;
+        let version = DocumentVersion::try_from(value)?;
+        Ok(Self {
+            version,
+            generation,
+        })
+    }
+}
+
+/// Versions whose lowest common ancestors are looked for
+#[derive(Debug, Deserialize)]
+pub struct CommonAncestorsQuery {
+    pub first: Uuid,
+    pub second: Uuid,
+}
@@ -1,5 +1,3 @@
-use std::convert::Infallible;
-
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -13,9 +11,8 @@ pub enum DocumentVersionState {
     Published = 3,
 }
 
-// TODO: handle a very unlikely case of invalid value properly
 impl TryFrom<i16> for DocumentVersionState {
-    type Error = Infallible;
+    type Error = i16;
 
     fn try_from(value: i16) -> Result<Self, Self::Error> {
         match value {
@@ -23,7 +20,7 @@ impl TryFrom<i16> for DocumentVersionState {
             1 => Ok(Self::ReadyForReview),
             2 => Ok(Self::Reviewed),
             3 => Ok(Self::Published),
-            _ => unreachable!(),
+            _ => Err(value),
         }
     }
 }
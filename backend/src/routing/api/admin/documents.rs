@@ -0,0 +1,119 @@
+use axum::{
+    extract::{FromRef, Path},
+    http::StatusCode,
+    routing::put,
+    Router,
+};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    models::{event::EventType, role::DocumentVersionRole},
+    services::{
+        auth::{admin::AdminClaims, auth_keys::AuthKeys},
+        database::{
+            repositories::{
+                events::EventsRepository,
+                ownership::{OwnerRemoval, OwnershipRepository},
+                permission::PermissionRepository,
+            },
+            DbPool,
+        },
+        util::Res2,
+    },
+};
+
+/// Makes the user an owner of the version, e.g. when its owners have left
+async fn add_owner(
+    AdminClaims(claims): AdminClaims,
+    permission_repository: PermissionRepository,
+    event_repository: EventsRepository,
+    Path((document_id, version_id, user_id)): Path<(Uuid, Uuid, Uuid)>,
+) -> StatusCode {
+    let owner = DocumentVersionRole::Owner;
+    match permission_repository
+        .grant_document_version_role(user_id, document_id, version_id, owner, None, None)
+        .await
+    {
+        Ok(true) => {
+            info!(
+                {
+                    user_id = user_id.to_string(),
+                    document_id = document_id.to_string(),
+                    version_id = version_id.to_string(),
+                    admin_id = claims.user_id.to_string()
+                },
+                "Granted ownership"
+            );
+            event_repository
+                .create_event(
+                    document_id,
+                    version_id,
+                    user_id,
+                    EventType::RoleAdded(owner),
+                )
+                .await
+                .ok();
+            StatusCode::OK
+        }
+        Ok(false) => StatusCode::NOT_MODIFIED,
+        Err(error) => {
+            error!({ error = error.to_string() }, "Failed to grant ownership");
+            StatusCode::BAD_REQUEST
+        }
+    }
+}
+
+async fn remove_owner(
+    AdminClaims(claims): AdminClaims,
+    mut ownership_repository: OwnershipRepository,
+    event_repository: EventsRepository,
+    Path((document_id, version_id, user_id)): Path<(Uuid, Uuid, Uuid)>,
+) -> Res2 {
+    match ownership_repository
+        .remove_owner(user_id, document_id, version_id)
+        .await
+    {
+        Ok(OwnerRemoval::Removed) => {
+            info!(
+                {
+                    user_id = user_id.to_string(),
+                    document_id = document_id.to_string(),
+                    version_id = version_id.to_string(),
+                    admin_id = claims.user_id.to_string()
+                },
+                "Revoked ownership"
+            );
+            event_repository
+                .create_event(
+                    document_id,
+                    version_id,
+                    user_id,
+                    EventType::RoleRemoved(DocumentVersionRole::Owner),
+                )
+                .await
+                .ok();
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(OwnerRemoval::NotOwner) => Res2::NoMsg(StatusCode::NOT_FOUND),
+        Ok(OwnerRemoval::LastOwner) => {
+            Res2::Msg((StatusCode::CONFLICT, "Cannot revoke the last owner"))
+        }
+        Err(error) => {
+            error!({ error = error.to_string() }, "Failed to revoke ownership");
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub fn documents_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    DbPool: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new().route(
+        "/:document_id/:version_id/owners/:user_id",
+        put(add_owner).delete(remove_owner),
+    )
+}
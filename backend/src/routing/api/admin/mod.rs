@@ -0,0 +1,28 @@
+mod documents;
+mod security_events;
+mod users;
+
+use axum::{extract::FromRef, Router};
+
+use crate::services::{
+    auth::{auth_keys::AuthKeys, password::PasswordHashing, throttling::ThrottlingConfig},
+    database::DbPool,
+};
+
+use self::{
+    documents::documents_router, security_events::security_events_router, users::users_router,
+};
+
+pub fn admin_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    PasswordHashing: FromRef<T>,
+    ThrottlingConfig: FromRef<T>,
+    DbPool: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new()
+        .nest("/users", users_router())
+        .nest("/security-events", security_events_router())
+        .nest("/documents", documents_router())
+}
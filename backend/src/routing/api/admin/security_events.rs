@@ -0,0 +1,37 @@
+use axum::{extract::FromRef, http::StatusCode, routing::get, Json, Router};
+use tracing::error;
+
+use crate::{
+    models::security_event::SecurityEvent,
+    services::{
+        auth::{
+            admin::AdminClaims, auth_keys::AuthKeys, password::PasswordHashing,
+            throttling::ThrottlingConfig,
+        },
+        database::{repositories::login_throttling::LoginThrottlingRepository, DbPool},
+    },
+};
+
+async fn get_security_events(
+    _: AdminClaims,
+    throttling_repository: LoginThrottlingRepository,
+) -> Result<Json<Vec<SecurityEvent>>, StatusCode> {
+    match throttling_repository.get_security_events().await {
+        Ok(events) => Ok(Json(events)),
+        Err(error) => {
+            error!({ error = error }, "Failed to fetch security events");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub fn security_events_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    PasswordHashing: FromRef<T>,
+    ThrottlingConfig: FromRef<T>,
+    DbPool: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new().route("/", get(get_security_events))
+}
@@ -0,0 +1,257 @@
+use axum::{
+    extract::{FromRef, Path},
+    http::StatusCode,
+    routing::{get, post, put},
+    Json, Router,
+};
+use chrono::{Duration, Utc};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        role::Role,
+        sensitivity_label::UpdateClearance,
+        user::{LockUser, ManagedUser, PasswordReset},
+    },
+    services::{
+        auth::{
+            admin::AdminClaims, auth_keys::AuthKeys, password::PasswordHashing,
+            throttling::ThrottlingConfig,
+        },
+        database::{
+            repositories::{
+                login_throttling::LoginThrottlingRepository, permission::PermissionRepository,
+                users::UsersRepository,
+            },
+            DbPool,
+        },
+        util::{Res2, ValidatedJson},
+    },
+};
+
+async fn get_users(
+    _: AdminClaims,
+    users_repository: UsersRepository,
+) -> Result<Json<Vec<ManagedUser>>, StatusCode> {
+    match users_repository.get_managed_users().await {
+        Ok(users) => Ok(Json(users)),
+        Err(error) => {
+            error!({ error = error }, "Failed to fetch users");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_user(
+    _: AdminClaims,
+    users_repository: UsersRepository,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<ManagedUser>, StatusCode> {
+    match users_repository.get_managed_user(user_id).await {
+        Ok(Some(user)) => Ok(Json(user)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!({ error = error }, "Failed to fetch user");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn promote(
+    AdminClaims(claims): AdminClaims,
+    permission_repository: PermissionRepository,
+    Path(user_id): Path<Uuid>,
+) -> StatusCode {
+    match permission_repository.grant_role(user_id, Role::Admin).await {
+        Ok(true) => {
+            info!(
+                { user_id = user_id.to_string(), admin_id = claims.user_id.to_string() },
+                "Promoted to admin"
+            );
+            StatusCode::OK
+        }
+        Ok(false) => StatusCode::NOT_MODIFIED,
+        Err(error) => {
+            error!({ error = error }, "Failed to promote user");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn demote(
+    AdminClaims(claims): AdminClaims,
+    permission_repository: PermissionRepository,
+    Path(user_id): Path<Uuid>,
+) -> Res2 {
+    if user_id == claims.user_id {
+        return Res2::Msg((StatusCode::CONFLICT, "Admins can't demote themselves"));
+    }
+    match permission_repository
+        .revoke_role(user_id, Role::Admin)
+        .await
+    {
+        Ok(true) => {
+            info!(
+                { user_id = user_id.to_string(), admin_id = claims.user_id.to_string() },
+                "Demoted from admin"
+            );
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(false) => Res2::NoMsg(StatusCode::NOT_MODIFIED),
+        Err(error) => {
+            error!({ error = error }, "Failed to demote user");
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn set_disabled(
+    claims: &AdminClaims,
+    users_repository: &UsersRepository,
+    user_id: Uuid,
+    disabled: bool,
+) -> Res2 {
+    if user_id == claims.0.user_id {
+        return Res2::Msg((StatusCode::CONFLICT, "Admins can't disable themselves"));
+    }
+    match users_repository.set_disabled(user_id, disabled).await {
+        Ok(true) => {
+            info!(
+                { user_id = user_id.to_string(), admin_id = claims.0.user_id.to_string(), disabled = disabled },
+                "Changed account status"
+            );
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(false) => Res2::NoMsg(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!({ error = error }, "Failed to change account status");
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn disable(
+    claims: AdminClaims,
+    users_repository: UsersRepository,
+    Path(user_id): Path<Uuid>,
+) -> Res2 {
+    set_disabled(&claims, &users_repository, user_id, true).await
+}
+
+async fn enable(
+    claims: AdminClaims,
+    users_repository: UsersRepository,
+    Path(user_id): Path<Uuid>,
+) -> Res2 {
+    set_disabled(&claims, &users_repository, user_id, false).await
+}
+
+async fn lock(
+    _: AdminClaims,
+    users_repository: UsersRepository,
+    Path(user_id): Path<Uuid>,
+    ValidatedJson(data): ValidatedJson<LockUser>,
+) -> StatusCode {
+    let locked_until = Utc::now() + Duration::minutes(data.duration_minutes);
+    match users_repository
+        .set_locked_until(user_id, Some(locked_until))
+        .await
+    {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(error) => {
+            error!({ error = error }, "Failed to lock user");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn unlock(
+    _: AdminClaims,
+    users_repository: UsersRepository,
+    throttling_repository: LoginThrottlingRepository,
+    Path(user_id): Path<Uuid>,
+) -> StatusCode {
+    if let Err(error) = throttling_repository.clear_user(user_id).await {
+        error!({ error = error }, "Failed to clear login throttling");
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    match users_repository.set_locked_until(user_id, None).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(error) => {
+            error!({ error = error }, "Failed to unlock user");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Documents labeled above the clearance stop counting the user's roles
+async fn set_clearance(
+    AdminClaims(claims): AdminClaims,
+    users_repository: UsersRepository,
+    Path(user_id): Path<Uuid>,
+    ValidatedJson(data): ValidatedJson<UpdateClearance>,
+) -> StatusCode {
+    match users_repository
+        .set_clearance(user_id, data.clearance)
+        .await
+    {
+        Ok(true) => {
+            info!(
+                { user_id = user_id.to_string(), admin_id = claims.user_id.to_string(), clearance = ?data.clearance },
+                "Changed clearance"
+            );
+            StatusCode::OK
+        }
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(error) => {
+            error!({ error = error }, "Failed to change clearance");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Invalidates the user's password and returns a one-time token to hand over to them
+async fn reset_password(
+    AdminClaims(claims): AdminClaims,
+    mut users_repository: UsersRepository,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<PasswordReset>, StatusCode> {
+    match users_repository.create_password_reset(user_id).await {
+        Ok(Some(reset)) => {
+            info!(
+                { user_id = user_id.to_string(), admin_id = claims.user_id.to_string() },
+                "Forced password reset"
+            );
+            Ok(Json(reset))
+        }
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!({ error = error }, "Failed to reset password");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub fn users_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    PasswordHashing: FromRef<T>,
+    ThrottlingConfig: FromRef<T>,
+    DbPool: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new()
+        .route("/", get(get_users))
+        .route("/:user_id", get(get_user))
+        .route("/:user_id/promote", post(promote))
+        .route("/:user_id/demote", post(demote))
+        .route("/:user_id/disable", post(disable))
+        .route("/:user_id/enable", post(enable))
+        .route("/:user_id/lock", post(lock))
+        .route("/:user_id/unlock", post(unlock))
+        .route("/:user_id/reset-password", post(reset_password))
+        .route("/:user_id/clearance", put(set_clearance))
+}
@@ -1,26 +1,136 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::net::SocketAddr;
 
-use axum::{extract::State, Json};
+use axum::{
+    extract::{ConnectInfo, State},
+    Json,
+};
+use chrono::{Duration, Utc};
 use serde::{Deserialize, Serialize};
 use tracing::{error, info};
 
-use crate::services::{
-    auth::{auth_keys::AuthKeys, claims::Claims, error::AuthError},
-    database::repositories::users::UsersRepository,
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        session::Session,
+        totp::{ChallengeAnswer, LoginChallenge},
+        user::User,
+    },
+    services::{
+        auth::{
+            auth_keys::AuthKeys, claims::Claims, error::AuthError, session::SessionConfig,
+            throttling::ThrottleScope,
+        },
+        database::repositories::{
+            login_throttling::LoginThrottlingRepository, sessions::SessionsRepository,
+            totp::TotpRepository, users::UsersRepository,
+        },
+        util::ValidatedJson,
+    },
 };
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthorizeResponse {
     token: String,
+    refresh_token: String,
 }
 
 impl AuthorizeResponse {
-    fn new(token: String) -> Self {
-        Self { token }
+    fn new(token: String, refresh_token: String) -> Self {
+        Self {
+            token,
+            refresh_token,
+        }
+    }
+
+    pub fn try_from_session(
+        session: Session,
+        keys: &AuthKeys,
+        config: &SessionConfig,
+    ) -> Result<Self, AuthError> {
+        let claims = Claims::new(
+            session.user_id,
+            session.username,
+            session.session_id,
+            config.access_token_ttl,
+        );
+        let token = claims.try_into_token(keys)?;
+        Ok(Self::new(token, session.refresh_token))
     }
 }
 
+/// Either a finished login or a second step for users with 2FA enabled
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum LoginResponse {
+    Authorized(AuthorizeResponse),
+    Challenge(LoginChallenge),
+}
+
+pub async fn start_session(
+    user_id: Uuid,
+    username: String,
+    keys: &AuthKeys,
+    session_config: &SessionConfig,
+    sessions_repository: &mut SessionsRepository,
+) -> Result<AuthorizeResponse, AuthError> {
+    let lifetime = Duration::seconds(session_config.refresh_token_ttl as i64);
+    let session = sessions_repository
+        .create_session(user_id, username, lifetime)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            AuthError::TokenCreation
+        })?;
+    AuthorizeResponse::try_from_session(session, keys, session_config)
+}
+
+/// Starts a session for an authenticated user,
+/// unless the account is blocked or a second factor is still required
+pub async fn authorize_user(
+    user: User,
+    keys: &AuthKeys,
+    session_config: &SessionConfig,
+    totp_repository: &TotpRepository,
+    sessions_repository: &mut SessionsRepository,
+) -> Result<LoginResponse, AuthError> {
+    if user.disabled {
+        info!(
+            { user_id = user.user_id.to_string() },
+            "Disabled user tried to log in"
+        );
+        return Err(AuthError::AccountDisabled);
+    }
+    if user.is_locked() {
+        info!(
+            { user_id = user.user_id.to_string() },
+            "Locked user tried to log in"
+        );
+        return Err(AuthError::AccountLocked);
+    }
+    let User {
+        user_id, username, ..
+    } = user;
+    let totp_enabled = totp_repository.is_enabled(user_id).await.map_err(|e| {
+        error!("{}", e);
+        AuthError::Internal
+    })?;
+    if totp_enabled {
+        let challenge = totp_repository
+            .create_challenge(user_id)
+            .await
+            .map_err(|e| {
+                error!("{}", e);
+                AuthError::Internal
+            })?;
+        return Ok(LoginResponse::Challenge(LoginChallenge { challenge }));
+    }
+    let response =
+        start_session(user_id, username, keys, session_config, sessions_repository).await?;
+    Ok(LoginResponse::Authorized(response))
+}
+
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AuthorizeRequest {
@@ -28,37 +138,108 @@ pub struct AuthorizeRequest {
     password: String,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn login(
     State(keys): State<AuthKeys>,
+    State(session_config): State<SessionConfig>,
+    ConnectInfo(address): ConnectInfo<SocketAddr>,
     users_repository: UsersRepository,
+    totp_repository: TotpRepository,
+    mut sessions_repository: SessionsRepository,
+    mut throttling_repository: LoginThrottlingRepository,
     Json(data): Json<AuthorizeRequest>,
-) -> Result<Json<AuthorizeResponse>, AuthError> {
-    match users_repository
+) -> Result<Json<LoginResponse>, AuthError> {
+    let address = address.ip().to_string();
+    let blocked_until = throttling_repository
+        .blocked_until(ThrottleScope::Username, &data.username, &address)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            AuthError::Internal
+        })?;
+    if let Some(blocked_until) = blocked_until {
+        info!({ address = address }, "Throttled login attempt");
+        let retry_after = (blocked_until - Utc::now()).num_seconds();
+        return Err(AuthError::TooManyAttempts(retry_after));
+    }
+    let user = match users_repository
         .verify(&data.username, &data.password)
         .await
     {
         Err(e) => {
             error!("{}", e);
-            Err(AuthError::InvalidCredentials)
+            return Err(AuthError::InvalidCredentials);
         }
-        Ok(None) => {
-            info!("Invalid password");
-            Err(AuthError::InvalidCredentials)
-        }
-        Ok(Some(user)) => {
-            let now = SystemTime::now()
-                .duration_since(UNIX_EPOCH)
-                .expect("Time went backwards")
-                .as_secs();
-            let claims = Claims {
-                exp: now + 60 * 60 * 24,
-                nbf: now,
-                iat: now,
-                user_id: user.user_id,
-                username: user.username,
-            };
-            let token = claims.try_into_token(&keys.encoding)?;
-            Ok(Json(AuthorizeResponse::new(token)))
+        Ok(user) => user,
+    };
+    let Some(user) = user else {
+        info!("Invalid password");
+        if let Err(e) = throttling_repository
+            .record_failure(ThrottleScope::Username, &data.username, &address)
+            .await
+        {
+            error!("{}", e);
         }
+        return Err(AuthError::InvalidCredentials);
+    };
+    if let Err(e) = throttling_repository
+        .record_success(ThrottleScope::Username, &data.username)
+        .await
+    {
+        error!("{}", e);
+    }
+    let response = authorize_user(
+        user,
+        &keys,
+        &session_config,
+        &totp_repository,
+        &mut sessions_repository,
+    )
+    .await?;
+    Ok(Json(response))
+}
+
+/// Second login step, answers a challenge with a TOTP or recovery code
+pub async fn login_totp(
+    State(keys): State<AuthKeys>,
+    State(session_config): State<SessionConfig>,
+    totp_repository: TotpRepository,
+    mut sessions_repository: SessionsRepository,
+    ValidatedJson(data): ValidatedJson<ChallengeAnswer>,
+) -> Result<Json<AuthorizeResponse>, AuthError> {
+    let (user_id, username) = totp_repository
+        .attempt_challenge(&data.challenge)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            AuthError::Internal
+        })?
+        .ok_or(AuthError::InvalidCredentials)?;
+    let valid = totp_repository
+        .verify_code(user_id, &data.code)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            AuthError::Internal
+        })?;
+    if !valid {
+        info!("Invalid second factor code");
+        return Err(AuthError::InvalidCredentials);
     }
+    totp_repository
+        .finish_challenge(&data.challenge)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            AuthError::Internal
+        })?;
+    let response = start_session(
+        user_id,
+        username,
+        &keys,
+        &session_config,
+        &mut sessions_repository,
+    )
+    .await?;
+    Ok(Json(response))
 }
@@ -0,0 +1,17 @@
+use axum::http::StatusCode;
+use tracing::error;
+
+use crate::services::{auth::claims::Claims, database::repositories::sessions::SessionsRepository};
+
+pub async fn logout(claims: Claims, sessions_repository: SessionsRepository) -> StatusCode {
+    match sessions_repository
+        .revoke_session(claims.user_id, claims.jti)
+        .await
+    {
+        Ok(_) => StatusCode::OK,
+        Err(error) => {
+            error!({ error = error }, "Failed to revoke session");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
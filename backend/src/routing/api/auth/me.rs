@@ -0,0 +1,109 @@
+use axum::{http::StatusCode, Json};
+use tracing::{error, info};
+
+use crate::{
+    models::user::{AccountDetails, ChangePassword, DeleteAccount, UpdateProfile},
+    services::{
+        auth::claims::Claims,
+        database::repositories::{
+            documents::UniqueError, sessions::SessionsRepository, users::UsersRepository,
+        },
+        util::ValidatedJson,
+    },
+};
+
+pub async fn get_me(
+    claims: Claims,
+    users_repository: UsersRepository,
+) -> Result<Json<AccountDetails>, StatusCode> {
+    match users_repository.get_account(claims.user_id).await {
+        Ok(account) => Ok(Json(account)),
+        Err(error) => {
+            error!({ error = error }, "Failed to fetch account");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn update_me(
+    claims: Claims,
+    users_repository: UsersRepository,
+    ValidatedJson(data): ValidatedJson<UpdateProfile>,
+) -> Result<Json<AccountDetails>, StatusCode> {
+    match users_repository.update_profile(claims.user_id, data).await {
+        Ok(account) => Ok(Json(account)),
+        Err(UniqueError::UniqueValueViolation) => Err(StatusCode::CONFLICT),
+        Err(error) => {
+            error!({ error = error.to_string() }, "Failed to update profile");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Changes the password and signs out every other session
+pub async fn change_password(
+    claims: Claims,
+    users_repository: UsersRepository,
+    sessions_repository: SessionsRepository,
+    ValidatedJson(data): ValidatedJson<ChangePassword>,
+) -> StatusCode {
+    match users_repository
+        .verify_by_id(claims.user_id, &data.old_password)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            info!("Invalid password");
+            return StatusCode::FORBIDDEN;
+        }
+        Err(error) => {
+            error!({ error = error }, "Failed to verify password");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    if let Err(error) = users_repository
+        .set_password(claims.user_id, &data.new_password)
+        .await
+    {
+        error!({ error = error }, "Failed to change password");
+        return StatusCode::INTERNAL_SERVER_ERROR;
+    }
+    match sessions_repository
+        .revoke_other_sessions(claims.user_id, claims.jti)
+        .await
+    {
+        Ok(_) => StatusCode::OK,
+        Err(error) => {
+            error!({ error = error }, "Failed to revoke sessions");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+pub async fn delete_me(
+    claims: Claims,
+    mut users_repository: UsersRepository,
+    Json(data): Json<DeleteAccount>,
+) -> StatusCode {
+    match users_repository
+        .verify_by_id(claims.user_id, &data.password)
+        .await
+    {
+        Ok(Some(_)) => {}
+        Ok(None) => {
+            info!("Invalid password");
+            return StatusCode::FORBIDDEN;
+        }
+        Err(error) => {
+            error!({ error = error }, "Failed to verify password");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    match users_repository.delete_user(claims.user_id).await {
+        Ok(()) => StatusCode::OK,
+        Err(error) => {
+            error!({ error = error }, "Failed to delete user");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
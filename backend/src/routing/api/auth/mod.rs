@@ -1,31 +1,77 @@
 use axum::{
     extract::FromRef,
-    routing::{get, post},
+    routing::{delete, get, patch, post},
     Router,
 };
 
-use crate::services::{auth::auth_keys::AuthKeys, database::DbPool};
+use crate::services::{
+    auth::{
+        auth_keys::AuthKeys, oidc::OidcClient, password::PasswordHashing, session::SessionConfig,
+        throttling::ThrottlingConfig,
+    },
+    database::DbPool,
+};
 
 use self::{
-    am_admin::am_admin, login::login, register::register, users::users, who_am_i::who_am_i,
+    am_admin::am_admin,
+    login::{login, login_totp},
+    logout::logout,
+    me::{change_password, delete_me, get_me, update_me},
+    oidc::{oidc_callback, oidc_login},
+    refresh::refresh,
+    register::register,
+    reset_password::reset_password,
+    tokens::{create_token, get_tokens, revoke_token},
+    totp::{confirm_totp, disable_totp, enrol_totp, regenerate_recovery_codes, reset_totp},
+    users::users,
+    who_am_i::who_am_i,
 };
 
 mod am_admin;
 mod login;
+mod logout;
+mod me;
+mod oidc;
+mod refresh;
 mod register;
+mod reset_password;
+mod tokens;
+mod totp;
 mod users;
 mod who_am_i;
 
 pub fn auth_router<T>() -> Router<T>
 where
     AuthKeys: FromRef<T>,
+    PasswordHashing: FromRef<T>,
+    SessionConfig: FromRef<T>,
+    ThrottlingConfig: FromRef<T>,
+    Option<OidcClient>: FromRef<T>,
     DbPool: FromRef<T>,
     T: 'static + Send + Sync + Clone,
 {
     Router::new()
         .route("/register", post(register))
         .route("/login", post(login))
+        .route("/login/totp", post(login_totp))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
+        .route("/reset-password", post(reset_password))
+        .route("/oidc/login", get(oidc_login))
+        .route("/oidc/callback", get(oidc_callback))
         .route("/who-am-i", get(who_am_i))
+        .route("/me", get(get_me))
+        .route("/me", patch(update_me))
+        .route("/me", delete(delete_me))
+        .route("/me/password", post(change_password))
         .route("/am-admin", get(am_admin))
         .route("/users", get(users))
+        .route("/tokens", get(get_tokens))
+        .route("/tokens", post(create_token))
+        .route("/tokens/:token_id", delete(revoke_token))
+        .route("/totp/enrol", post(enrol_totp))
+        .route("/totp/confirm", post(confirm_totp))
+        .route("/totp/recovery-codes", post(regenerate_recovery_codes))
+        .route("/totp/disable", post(disable_totp))
+        .route("/users/:user_id/totp", delete(reset_totp))
 }
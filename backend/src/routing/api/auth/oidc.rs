@@ -0,0 +1,99 @@
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Redirect,
+    Json,
+};
+use serde::Deserialize;
+use tracing::{error, info};
+
+use crate::services::{
+    auth::{auth_keys::AuthKeys, error::AuthError, oidc::OidcClient, session::SessionConfig},
+    database::repositories::{
+        oidc_logins::OidcLoginsRepository, sessions::SessionsRepository, totp::TotpRepository,
+        users::UsersRepository,
+    },
+};
+
+use super::login::{authorize_user, LoginResponse};
+
+#[derive(Debug, Deserialize)]
+pub struct CallbackQuery {
+    code: String,
+    state: String,
+}
+
+pub async fn oidc_login(
+    State(oidc): State<Option<OidcClient>>,
+    oidc_logins_repository: OidcLoginsRepository,
+) -> Result<Redirect, StatusCode> {
+    let Some(oidc) = oidc else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+    let request = oidc.authorization_request().map_err(|e| {
+        error!("{}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    oidc_logins_repository
+        .start_login(&request.state, &request.code_verifier, &request.nonce)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Redirect::to(&request.url))
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn oidc_callback(
+    State(keys): State<AuthKeys>,
+    State(session_config): State<SessionConfig>,
+    State(oidc): State<Option<OidcClient>>,
+    oidc_logins_repository: OidcLoginsRepository,
+    mut users_repository: UsersRepository,
+    totp_repository: TotpRepository,
+    mut sessions_repository: SessionsRepository,
+    Query(query): Query<CallbackQuery>,
+) -> Result<Json<LoginResponse>, AuthError> {
+    let Some(oidc) = oidc else {
+        return Err(AuthError::InvalidCredentials);
+    };
+    let (code_verifier, nonce) = match oidc_logins_repository.finish_login(&query.state).await {
+        Ok(Some(login)) => login,
+        Ok(None) => {
+            info!("Unknown or expired login state");
+            return Err(AuthError::InvalidCredentials);
+        }
+        Err(e) => {
+            error!("{}", e);
+            return Err(AuthError::Internal);
+        }
+    };
+    let claims = oidc
+        .exchange_code(&query.code, &code_verifier, &nonce)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            AuthError::InvalidCredentials
+        })?;
+    let username = claims
+        .preferred_username
+        .or(claims.email)
+        .unwrap_or_else(|| claims.sub.clone());
+    let user = users_repository
+        .get_or_create_external_user(oidc.issuer(), &claims.sub, &username)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            AuthError::Internal
+        })?;
+    let response = authorize_user(
+        user,
+        &keys,
+        &session_config,
+        &totp_repository,
+        &mut sessions_repository,
+    )
+    .await?;
+    Ok(Json(response))
+}
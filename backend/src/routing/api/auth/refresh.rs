@@ -0,0 +1,40 @@
+use axum::{extract::State, Json};
+use chrono::Duration;
+use serde::Deserialize;
+use tracing::error;
+
+use crate::services::{
+    auth::{auth_keys::AuthKeys, error::AuthError, session::SessionConfig},
+    database::repositories::sessions::SessionsRepository,
+};
+
+use super::login::AuthorizeResponse;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshRequest {
+    refresh_token: String,
+}
+
+pub async fn refresh(
+    State(keys): State<AuthKeys>,
+    State(session_config): State<SessionConfig>,
+    mut sessions_repository: SessionsRepository,
+    Json(data): Json<RefreshRequest>,
+) -> Result<Json<AuthorizeResponse>, AuthError> {
+    let lifetime = Duration::seconds(session_config.refresh_token_ttl as i64);
+    match sessions_repository
+        .refresh_session(&data.refresh_token, lifetime)
+        .await
+    {
+        Ok(Some(session)) => {
+            let response = AuthorizeResponse::try_from_session(session, &keys, &session_config)?;
+            Ok(Json(response))
+        }
+        Ok(None) => Err(AuthError::InvalidToken),
+        Err(e) => {
+            error!("{}", e);
+            Err(AuthError::Internal)
+        }
+    }
+}
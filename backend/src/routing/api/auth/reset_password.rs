@@ -0,0 +1,25 @@
+use axum::http::StatusCode;
+use tracing::error;
+
+use crate::{
+    models::user::RedeemPasswordReset,
+    services::{database::repositories::users::UsersRepository, util::ValidatedJson},
+};
+
+/// Sets a new password with a token issued by an admin
+pub async fn reset_password(
+    mut users_repository: UsersRepository,
+    ValidatedJson(data): ValidatedJson<RedeemPasswordReset>,
+) -> StatusCode {
+    match users_repository
+        .redeem_password_reset(&data.reset_token, &data.new_password)
+        .await
+    {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::UNAUTHORIZED,
+        Err(error) => {
+            error!({ error = error }, "Failed to reset password");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
@@ -0,0 +1,69 @@
+use axum::{extract::Path, http::StatusCode, Json};
+use chrono::Duration;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::personal_token::{CreatePersonalToken, CreatedPersonalToken, PersonalToken},
+    services::{
+        auth::claims::Claims,
+        database::repositories::{
+            documents::UniqueError, personal_tokens::PersonalTokensRepository,
+        },
+        util::ValidatedJson,
+    },
+};
+
+pub async fn create_token(
+    claims: Claims,
+    personal_tokens_repository: PersonalTokensRepository,
+    ValidatedJson(data): ValidatedJson<CreatePersonalToken>,
+) -> Result<Json<CreatedPersonalToken>, StatusCode> {
+    match personal_tokens_repository
+        .create_token(
+            claims.user_id,
+            data.token_name,
+            data.scopes,
+            Duration::days(data.expires_in_days),
+        )
+        .await
+    {
+        Ok(token) => Ok(Json(token)),
+        Err(UniqueError::UniqueValueViolation) => Err(StatusCode::CONFLICT),
+        Err(error) => {
+            error!({ error = error.to_string() }, "Failed to create token");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn get_tokens(
+    claims: Claims,
+    personal_tokens_repository: PersonalTokensRepository,
+) -> Result<Json<Vec<PersonalToken>>, StatusCode> {
+    match personal_tokens_repository.get_tokens(claims.user_id).await {
+        Ok(tokens) => Ok(Json(tokens)),
+        Err(error) => {
+            error!({ error = error }, "Failed to fetch tokens");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn revoke_token(
+    claims: Claims,
+    personal_tokens_repository: PersonalTokensRepository,
+    Path(token_id): Path<Uuid>,
+) -> StatusCode {
+    match personal_tokens_repository
+        .revoke_token(claims.user_id, token_id)
+        .await
+    {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(error) => {
+            error!({ error = error }, "Failed to revoke token");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
@@ -0,0 +1,143 @@
+use axum::{extract::Path, http::StatusCode, Json};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::totp::{RecoveryCodes, TotpCode, TotpEnrolment},
+    services::{
+        auth::{
+            claims::Claims,
+            totp::{check_code, generate_totp_secret, otpauth_uri},
+        },
+        database::repositories::{permission::PermissionRepository, totp::TotpRepository},
+        util::ValidatedJson,
+    },
+};
+
+pub async fn enrol_totp(
+    claims: Claims,
+    totp_repository: TotpRepository,
+) -> Result<Json<TotpEnrolment>, StatusCode> {
+    let secret = generate_totp_secret();
+    match totp_repository
+        .start_enrolment(claims.user_id, &secret)
+        .await
+    {
+        Ok(true) => Ok(Json(TotpEnrolment {
+            otpauth_uri: otpauth_uri(&secret, &claims.username),
+            secret,
+        })),
+        Ok(false) => Err(StatusCode::CONFLICT),
+        Err(error) => {
+            error!({ error = error }, "Failed to start TOTP enrolment");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn confirm_totp(
+    claims: Claims,
+    mut totp_repository: TotpRepository,
+    ValidatedJson(data): ValidatedJson<TotpCode>,
+) -> Result<Json<RecoveryCodes>, StatusCode> {
+    let totp = match totp_repository.get_totp(claims.user_id).await {
+        Ok(Some(totp)) if !totp.enabled => totp,
+        Ok(Some(_)) => return Err(StatusCode::CONFLICT),
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!({ error = error }, "Failed to fetch TOTP");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    let Some(step) = check_code(&totp.secret, &data.code, totp.last_used_step) else {
+        return Err(StatusCode::UNAUTHORIZED);
+    };
+    match totp_repository
+        .confirm_enrolment(claims.user_id, step)
+        .await
+    {
+        Ok(recovery_codes) => Ok(Json(RecoveryCodes { recovery_codes })),
+        Err(error) => {
+            error!({ error = error }, "Failed to confirm TOTP enrolment");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn regenerate_recovery_codes(
+    claims: Claims,
+    mut totp_repository: TotpRepository,
+    ValidatedJson(data): ValidatedJson<TotpCode>,
+) -> Result<Json<RecoveryCodes>, StatusCode> {
+    match totp_repository
+        .verify_code(claims.user_id, &data.code)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return Err(StatusCode::UNAUTHORIZED),
+        Err(error) => {
+            error!({ error = error }, "Failed to verify code");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    match totp_repository
+        .regenerate_recovery_codes(claims.user_id)
+        .await
+    {
+        Ok(recovery_codes) => Ok(Json(RecoveryCodes { recovery_codes })),
+        Err(error) => {
+            error!({ error = error }, "Failed to regenerate recovery codes");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub async fn disable_totp(
+    claims: Claims,
+    mut totp_repository: TotpRepository,
+    ValidatedJson(data): ValidatedJson<TotpCode>,
+) -> StatusCode {
+    match totp_repository
+        .verify_code(claims.user_id, &data.code)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return StatusCode::UNAUTHORIZED,
+        Err(error) => {
+            error!({ error = error }, "Failed to verify code");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    match totp_repository.disable(claims.user_id).await {
+        Ok(_) => StatusCode::OK,
+        Err(error) => {
+            error!({ error = error }, "Failed to disable TOTP");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Lets an admin remove 2FA from a user who lost their authenticator and recovery codes
+pub async fn reset_totp(
+    claims: Claims,
+    permission_repository: PermissionRepository,
+    mut totp_repository: TotpRepository,
+    Path(user_id): Path<Uuid>,
+) -> StatusCode {
+    match permission_repository.is_admin(claims.user_id).await {
+        Ok(true) => {}
+        Ok(false) => return StatusCode::FORBIDDEN,
+        Err(error) => {
+            error!({ error = error }, "Failed to check permissions");
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+    }
+    match totp_repository.disable(user_id).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(error) => {
+            error!({ error = error }, "Failed to reset TOTP");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
@@ -1,7 +1,7 @@
 use axum::{http::StatusCode, Json};
 
 use crate::{
-    models::user::PublicUser,
+    models::user::UserProfile,
     services::{auth::claims::Claims, database::repositories::permission::PermissionRepository},
 };
 
@@ -10,7 +10,7 @@ use tracing::error;
 pub async fn users(
     _: Claims,
     permission_repository: PermissionRepository,
-) -> Result<Json<Vec<PublicUser>>, StatusCode> {
+) -> Result<Json<Vec<UserProfile>>, StatusCode> {
     match permission_repository.get_all_users().await {
         Ok(users) => Ok(Json(users)),
         Err(error) => {
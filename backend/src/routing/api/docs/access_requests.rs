@@ -0,0 +1,233 @@
+use axum::{
+    extract::{FromRef, Path, Query},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use s3::Bucket;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        access_request::{AccessRequest, AccessRequestStatus, CreateAccessRequest},
+        event::EventType,
+        role::{DocumentVersionRole, GrantValidity},
+    },
+    services::{
+        auth::{auth_keys::AuthKeys, policy::DocumentAccess},
+        database::{
+            repositories::{
+                access_requests::AccessRequestsRepository, documents::UniqueError,
+                events::EventsRepository,
+            },
+            DbPool,
+        },
+        util::{Res2, Res3, ValidatedJson},
+    },
+};
+
+async fn request_access(
+    access: DocumentAccess,
+    requests_repository: AccessRequestsRepository,
+    event_repository: EventsRepository,
+    Path((document_id, version_id)): Path<(Uuid, Uuid)>,
+    ValidatedJson(data): ValidatedJson<CreateAccessRequest>,
+) -> Res3<AccessRequest> {
+    if data.role == DocumentVersionRole::Owner {
+        return Res3::Msg((StatusCode::BAD_REQUEST, "Cannot request this role"));
+    }
+
+    let request = match requests_repository
+        .create_request(
+            access.claims.user_id,
+            document_id,
+            version_id,
+            data.role,
+            data.message,
+        )
+        .await
+    {
+        Ok(request) => request,
+        Err(UniqueError::UniqueValueViolation) => {
+            return Res3::Msg((StatusCode::CONFLICT, "Access already requested"))
+        }
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when requesting access"
+            );
+            return Res3::NoMsg(StatusCode::BAD_REQUEST);
+        }
+    };
+    let owners = match requests_repository
+        .get_version_owners(document_id, version_id)
+        .await
+    {
+        Ok(owners) => owners,
+        Err(error) => {
+            error!({ error = error.to_string() }, "Error when getting owners");
+            vec![]
+        }
+    };
+    for owner_id in owners {
+        event_repository
+            .create_event(
+                document_id,
+                version_id,
+                owner_id,
+                EventType::AccessRequested(request.role),
+            )
+            .await
+            .ok();
+    }
+    Res3::Json((request, StatusCode::OK))
+}
+
+async fn get_version_requests(
+    _: DocumentAccess,
+    requests_repository: AccessRequestsRepository,
+    Path((document_id, version_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Vec<AccessRequest>>, StatusCode> {
+    match requests_repository
+        .get_version_requests(document_id, version_id)
+        .await
+    {
+        Ok(requests) => Ok(Json(requests)),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when getting access requests"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_my_requests(
+    access: DocumentAccess,
+    requests_repository: AccessRequestsRepository,
+) -> Result<Json<Vec<AccessRequest>>, StatusCode> {
+    match requests_repository
+        .get_user_requests(access.claims.user_id)
+        .await
+    {
+        Ok(requests) => Ok(Json(requests)),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when getting access requests"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Grants the requested role, optionally for a limited time like a regular grant
+async fn approve_request(
+    access: DocumentAccess,
+    mut requests_repository: AccessRequestsRepository,
+    event_repository: EventsRepository,
+    Path((document_id, version_id, request_id)): Path<(Uuid, Uuid, Uuid)>,
+    Query(validity): Query<GrantValidity>,
+) -> Res2 {
+    if !validity.is_valid() {
+        return Res2::Msg((StatusCode::BAD_REQUEST, "Invalid validity window"));
+    }
+
+    match requests_repository
+        .approve_request(
+            document_id,
+            version_id,
+            request_id,
+            access.claims.user_id,
+            &validity,
+        )
+        .await
+    {
+        Ok(Some((request, granted))) => {
+            if granted {
+                event_repository
+                    .create_event(
+                        document_id,
+                        version_id,
+                        request.user.user.user_id,
+                        EventType::RoleAdded(request.role),
+                    )
+                    .await
+                    .ok();
+            }
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(None) => Res2::Msg((StatusCode::NOT_FOUND, "Access request not found")),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when approving access request"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn deny_request(
+    access: DocumentAccess,
+    requests_repository: AccessRequestsRepository,
+    event_repository: EventsRepository,
+    Path((document_id, version_id, request_id)): Path<(Uuid, Uuid, Uuid)>,
+) -> Res2 {
+    match requests_repository
+        .decide_request(
+            document_id,
+            version_id,
+            request_id,
+            access.claims.user_id,
+            AccessRequestStatus::Denied,
+        )
+        .await
+    {
+        Ok(Some(request)) => {
+            event_repository
+                .create_event(
+                    document_id,
+                    version_id,
+                    request.user.user.user_id,
+                    EventType::AccessDenied(request.role),
+                )
+                .await
+                .ok();
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(None) => Res2::Msg((StatusCode::NOT_FOUND, "Access request not found")),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when denying access request"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub fn access_requests_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    DbPool: FromRef<T>,
+    Bucket: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new()
+        .route("/access-requests", get(get_my_requests))
+        .route(
+            "/:document_id/:version_id/access-requests",
+            post(request_access).get(get_version_requests),
+        )
+        .route(
+            "/:document_id/:version_id/access-requests/:request_id/approve",
+            post(approve_request),
+        )
+        .route(
+            "/:document_id/:version_id/access-requests/:request_id/deny",
+            post(deny_request),
+        )
+}
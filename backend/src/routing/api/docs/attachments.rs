@@ -6,13 +6,14 @@ use axum::{
 };
 use mime::Mime;
 use s3::Bucket;
-use tracing::{error, info};
+use tracing::error;
 use uuid::Uuid;
 
 use crate::{
     models::attachment::File,
     services::{
-        auth::{auth_keys::AuthKeys, claims::Claims},
+        auth::{auth_keys::AuthKeys, policy::DocumentAccess},
+        content_storage::ContentStorage,
         database::{
             repositories::{documents::DocumentsRepository, files::FilesRepository, RepoError},
             DbPool,
@@ -21,9 +22,9 @@ use crate::{
 };
 
 async fn patch_file_attachment(
+    _: DocumentAccess,
     documents_repository: DocumentsRepository,
     mut files_repository: FilesRepository,
-    _: Claims,
     Path((document_id, version_id)): Path<(Uuid, Uuid)>,
     mut multipart: Multipart,
 ) -> Result<Json<File>, StatusCode> {
@@ -82,12 +83,12 @@ async fn patch_file_attachment(
 }
 
 async fn get_file_attachments(
+    access: DocumentAccess,
     documents_repository: DocumentsRepository,
-    claims: Claims,
     Path((document_id, version_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<Vec<File>>, StatusCode> {
     match documents_repository
-        .get_file_attachments(claims.user_id, document_id, version_id)
+        .get_file_attachments(access.claims.user_id, document_id, version_id)
         .await
     {
         Ok(file_attachments) => Ok(Json(file_attachments)),
@@ -99,12 +100,12 @@ async fn get_file_attachments(
 }
 
 async fn get_file_attachment(
+    access: DocumentAccess,
     documents_repository: DocumentsRepository,
-    claims: Claims,
     Path((document_id, version_id, file_id)): Path<(Uuid, Uuid, Uuid)>,
 ) -> Result<Json<File>, StatusCode> {
     match documents_repository
-        .get_file_attachment(claims.user_id, document_id, version_id, file_id)
+        .get_file_attachment(access.claims.user_id, document_id, version_id, file_id)
         .await
     {
         Ok(file_attachment) => Ok(Json(file_attachment)),
@@ -117,11 +118,22 @@ async fn get_file_attachment(
 }
 
 async fn get_file_attachment_content(
+    access: DocumentAccess,
+    documents_repository: DocumentsRepository,
     files_repository: FilesRepository,
-    _: Claims,
-    Path((_document_id, _version_id, file_id)): Path<(Uuid, Uuid, Uuid)>,
+    Path((document_id, version_id, file_id)): Path<(Uuid, Uuid, Uuid)>,
 ) -> Result<Vec<u8>, StatusCode> {
-    info!("{}", file_id);
+    match documents_repository
+        .get_file_attachment(access.claims.user_id, document_id, version_id, file_id)
+        .await
+    {
+        Ok(_) => {}
+        Err(RepoError::Forbidden) => return Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!("{}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
     let content = files_repository.get_file(file_id).await.map_err(|e| {
         error!("{}", e);
         StatusCode::BAD_REQUEST
@@ -130,9 +142,9 @@ async fn get_file_attachment_content(
 }
 
 async fn delete_file_attachment(
+    _: DocumentAccess,
     documents_repository: DocumentsRepository,
     mut files_repository: FilesRepository,
-    _: Claims,
     Path((document_id, version_id, file_id)): Path<(Uuid, Uuid, Uuid)>,
 ) -> Result<StatusCode, StatusCode> {
     match documents_repository
@@ -162,6 +174,7 @@ where
     AuthKeys: FromRef<T>,
     DbPool: FromRef<T>,
     Bucket: FromRef<T>,
+    ContentStorage: FromRef<T>,
     T: 'static + Send + Sync + Clone,
 {
     Router::new()
@@ -1,5 +1,5 @@
 use axum::{
-    extract::{FromRef, Path},
+    extract::{DefaultBodyLimit, FromRef, Path},
     http::StatusCode,
     routing::{get, post},
     Json, Router,
@@ -11,7 +11,8 @@ use uuid::Uuid;
 use crate::{
     models::document::{CreateDocument, Document, DocumentWithInitialVersion},
     services::{
-        auth::{auth_keys::AuthKeys, claims::Claims},
+        auth::{auth_keys::AuthKeys, policy::DocumentAccess},
+        content_storage::ContentStorage,
         database::{
             repositories::{
                 documents::{DocumentsRepository, UniqueError},
@@ -23,15 +24,17 @@ use crate::{
     },
 };
 
+use super::CONTENT_BODY_LIMIT;
+
 async fn create_document(
+    access: DocumentAccess,
     mut documents_repository: DocumentsRepository,
-    claims: Claims,
     ValidatedJson(data): ValidatedJson<CreateDocument>,
 ) -> Result<Json<DocumentWithInitialVersion>, StatusCode> {
     let result = documents_repository
         .create_document(
             data.document_name,
-            claims.user_id,
+            access.claims.user_id,
             data.initial_version.version_name,
             data.initial_version.content,
         )
@@ -47,12 +50,12 @@ async fn create_document(
 }
 
 async fn get_document(
+    access: DocumentAccess,
     documents_repository: DocumentsRepository,
-    claims: Claims,
     Path(document_id): Path<Uuid>,
 ) -> Result<Json<Document>, StatusCode> {
     match documents_repository
-        .get_document(claims.user_id, document_id)
+        .get_document(access.claims.user_id, document_id)
         .await
     {
         Ok(document) => Ok(Json(document)),
@@ -65,11 +68,11 @@ async fn get_document(
 }
 
 async fn get_documents(
+    access: DocumentAccess,
     documents_repository: DocumentsRepository,
-    claims: Claims,
 ) -> Result<Json<Vec<Document>>, StatusCode> {
     let documents = documents_repository
-        .get_documents(claims.user_id)
+        .get_documents(access.claims.user_id)
         .await
         .map_err(|e| {
             error!("{}", e);
@@ -83,10 +86,12 @@ where
     AuthKeys: FromRef<T>,
     DbPool: FromRef<T>,
     Bucket: FromRef<T>,
+    ContentStorage: FromRef<T>,
     T: 'static + Send + Sync + Clone,
 {
     Router::new()
         .route("/", post(create_document))
         .route("/documents", get(get_documents))
         .route("/:document_id", get(get_document))
+        .layer(DefaultBodyLimit::max(CONTENT_BODY_LIMIT))
 }
@@ -0,0 +1,122 @@
+use axum::{
+    extract::{FromRef, Path, Query},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::version::{CommonAncestorsQuery, DocumentVersion, VersionGraphNode},
+    services::{
+        auth::{auth_keys::AuthKeys, policy::DocumentAccess},
+        content_storage::ContentStorage,
+        database::{
+            repositories::{documents::DocumentsRepository, RepoError},
+            DbPool,
+        },
+    },
+};
+
+async fn get_ancestors(
+    access: DocumentAccess,
+    documents_repository: DocumentsRepository,
+    Path((document_id, version_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Vec<DocumentVersion>>, StatusCode> {
+    let ancestors = documents_repository
+        .get_ancestors(access.claims.user_id, document_id, version_id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+    Ok(Json(ancestors))
+}
+
+async fn get_descendants(
+    access: DocumentAccess,
+    documents_repository: DocumentsRepository,
+    Path((document_id, version_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Vec<DocumentVersion>>, StatusCode> {
+    let descendants = documents_repository
+        .get_descendants(access.claims.user_id, document_id, version_id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+    Ok(Json(descendants))
+}
+
+async fn get_heads(
+    access: DocumentAccess,
+    documents_repository: DocumentsRepository,
+    Path(document_id): Path<Uuid>,
+) -> Result<Json<Vec<DocumentVersion>>, StatusCode> {
+    let heads = documents_repository
+        .get_heads(access.claims.user_id, document_id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+    Ok(Json(heads))
+}
+
+async fn get_common_ancestors(
+    access: DocumentAccess,
+    documents_repository: DocumentsRepository,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<CommonAncestorsQuery>,
+) -> Result<Json<Vec<DocumentVersion>>, StatusCode> {
+    match documents_repository
+        .get_common_ancestors(
+            access.claims.user_id,
+            document_id,
+            query.first,
+            query.second,
+        )
+        .await
+    {
+        Ok(ancestors) => Ok(Json(ancestors)),
+        Err(RepoError::Forbidden) => Err(StatusCode::FORBIDDEN),
+        Err(error) => {
+            error!("{}", error);
+            Err(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+async fn get_version_graph(
+    access: DocumentAccess,
+    documents_repository: DocumentsRepository,
+    Path(document_id): Path<Uuid>,
+) -> Result<Json<Vec<VersionGraphNode>>, StatusCode> {
+    let graph = documents_repository
+        .get_version_graph(access.claims.user_id, document_id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+    Ok(Json(graph))
+}
+
+pub fn graph_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    DbPool: FromRef<T>,
+    ContentStorage: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new()
+        .route("/:document_id/heads", get(get_heads))
+        .route("/:document_id/common-ancestors", get(get_common_ancestors))
+        .route("/:document_id/graph", get(get_version_graph))
+        .route("/:document_id/:version_id/ancestors", get(get_ancestors))
+        .route(
+            "/:document_id/:version_id/descendants",
+            get(get_descendants),
+        )
+}
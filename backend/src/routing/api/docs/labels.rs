@@ -0,0 +1,99 @@
+use axum::{
+    extract::{FromRef, Path},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use s3::Bucket;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    models::sensitivity_label::{DocumentLabelChange, UpdateDocumentLabel},
+    services::{
+        auth::{auth_keys::AuthKeys, password::PasswordHashing, policy::DocumentAccess},
+        database::{
+            repositories::{labels::LabelsRepository, users::UsersRepository},
+            DbPool,
+        },
+        util::{Res2, ValidatedJson},
+    },
+};
+
+/// Owners can't label the document above their own clearance, they would lose access to it
+async fn set_label(
+    access: DocumentAccess,
+    mut labels_repository: LabelsRepository,
+    users_repository: UsersRepository,
+    Path(document_id): Path<Uuid>,
+    ValidatedJson(data): ValidatedJson<UpdateDocumentLabel>,
+) -> Res2 {
+    let clearance = match users_repository.get_clearance(access.claims.user_id).await {
+        Ok(Some(clearance)) => clearance,
+        Ok(None) => return Res2::NoMsg(StatusCode::FORBIDDEN),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when getting clearance"
+            );
+            return Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    if data.label > clearance {
+        return Res2::Msg((StatusCode::FORBIDDEN, "Label exceeds your clearance"));
+    }
+
+    match labels_repository
+        .set_document_label(document_id, data.label, access.claims.user_id)
+        .await
+    {
+        Ok(Some(old_label)) if old_label == data.label => Res2::NoMsg(StatusCode::NOT_MODIFIED),
+        Ok(Some(old_label)) => {
+            info!(
+                {
+                    document_id = document_id.to_string(),
+                    user_id = access.claims.user_id.to_string(),
+                    old_label = ?old_label,
+                    new_label = ?data.label
+                },
+                "Changed document label"
+            );
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(None) => Res2::NoMsg(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!({ error = error.to_string() }, "Error when changing label");
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_label_history(
+    _: DocumentAccess,
+    labels_repository: LabelsRepository,
+    Path(document_id): Path<Uuid>,
+) -> Result<Json<Vec<DocumentLabelChange>>, StatusCode> {
+    match labels_repository.get_label_changes(document_id).await {
+        Ok(changes) => Ok(Json(changes)),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when getting label history"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub fn labels_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    PasswordHashing: FromRef<T>,
+    DbPool: FromRef<T>,
+    Bucket: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new()
+        .route("/:document_id/label", put(set_label))
+        .route("/:document_id/label-history", get(get_label_history))
+}
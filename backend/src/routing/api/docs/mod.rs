@@ -1,25 +1,693 @@
+mod access_requests;
 mod attachments;
 mod documents;
+mod graph;
+mod labels;
+mod ownership;
 mod permission;
+mod share_links;
 mod states;
 mod versions;
 
 use axum::{extract::FromRef, Router};
 use s3::Bucket;
 
-use crate::services::{auth::auth_keys::AuthKeys, database::DbPool};
+use crate::{
+    models::version::MAX_CONTENT_LENGTH,
+    services::{
+        auth::{auth_keys::AuthKeys, password::PasswordHashing},
+        content_storage::ContentStorage,
+        database::DbPool,
+    },
+};
+
+/// Content takes up to four bytes per character, the rest of the body is small
+const CONTENT_BODY_LIMIT: usize = 4 * MAX_CONTENT_LENGTH as usize + 64 * 1024;
 
 pub fn documents_router<T>() -> Router<T>
 where
     AuthKeys: FromRef<T>,
+    PasswordHashing: FromRef<T>,
     DbPool: FromRef<T>,
     Bucket: FromRef<T>,
+    ContentStorage: FromRef<T>,
     T: 'static + Send + Sync + Clone,
 {
     Router::new()
+        .merge(access_requests::access_requests_router())
         .merge(attachments::attachments_router())
         .merge(documents::documents_router())
+        .merge(graph::graph_router())
+        .merge(labels::labels_router())
+        .merge(ownership::ownership_router())
         .merge(permission::permission_router())
+        .merge(share_links::share_links_router())
         .merge(states::states_router())
         .merge(versions::versions_router())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use axum::{
+        body::Body,
+        extract::{FromRef, MatchedPath},
+        http::{Method, Request, StatusCode},
+        middleware::{self, Next},
+        response::Response,
+        routing::get,
+        Router,
+    };
+    use bb8::Pool;
+    use bb8_postgres::PostgresConnectionManager;
+    use s3::{creds::Credentials, Bucket, Region};
+    use tokio_postgres::NoTls;
+    use tower::ServiceExt;
+    use uuid::Uuid;
+
+    use crate::{
+        models::role::DocumentVersionRole::{self, Editor, Owner, Reviewer, Viewer},
+        services::{
+            auth::{
+                auth_keys::{AuthKeyConfig, AuthKeys, AuthKeysConfig},
+                claims::Claims,
+                password::{PasswordHashing, PasswordHashingConfig},
+                policy::{allowed_roles, has_any_role, route_permission, DocumentAccess, Resource},
+            },
+            content_storage::{ContentStorage, ContentStorageConfig},
+            database::{testing::TestDatabase, DbPool},
+        },
+    };
+
+    use super::documents_router;
+
+    const D: &str = "65a45040-f418-11ed-a05b-0242ac120003";
+    const V: &str = "88c2e78e-f419-11ed-a05b-0242ac120003";
+    const F: &str = "81721217-8f19-4c3b-8b25-a2af68875018";
+
+    const ALL: &[DocumentVersionRole] = &[Owner, Viewer, Editor, Reviewer];
+    const WRITERS: &[DocumentVersionRole] = &[Owner, Editor];
+
+    /// Every document route with the roles allowed to call it
+    fn routes() -> Vec<(Method, String, &'static str, &'static [DocumentVersionRole])> {
+        let d = "/api/documents/:document_id";
+        let v = "/api/documents/:document_id/:version_id";
+        vec![
+            (Method::POST, "".into(), "/api/documents", ALL),
+            (
+                Method::GET,
+                "/documents".into(),
+                "/api/documents/documents",
+                ALL,
+            ),
+            (Method::GET, format!("/{D}"), d, ALL),
+            (Method::POST, format!("/{D}"), d, WRITERS),
+            (
+                Method::GET,
+                format!("/{D}/members"),
+                "/api/documents/:document_id/members",
+                ALL,
+            ),
+            (
+                Method::POST,
+                format!("/{D}/grant/{F}/viewer"),
+                "/api/documents/:document_id/grant/:user_id/:role",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                format!("/{D}/revoke/{F}/viewer"),
+                "/api/documents/:document_id/revoke/:user_id/:role",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                format!("/{D}/bulk-grant"),
+                "/api/documents/:document_id/bulk-grant",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                format!("/{D}/bulk-revoke"),
+                "/api/documents/:document_id/bulk-revoke",
+                &[Owner],
+            ),
+            (
+                Method::PUT,
+                format!("/{D}/label"),
+                "/api/documents/:document_id/label",
+                &[Owner],
+            ),
+            (
+                Method::GET,
+                format!("/{D}/label-history"),
+                "/api/documents/:document_id/label-history",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/default-members"),
+                "/api/documents/:document_id/default-members",
+                ALL,
+            ),
+            (
+                Method::PUT,
+                format!("/{D}/default-members/{F}/viewer"),
+                "/api/documents/:document_id/default-members/:user_id/:role",
+                &[Owner],
+            ),
+            (
+                Method::DELETE,
+                format!("/{D}/default-members/{F}/viewer"),
+                "/api/documents/:document_id/default-members/:user_id/:role",
+                &[Owner],
+            ),
+            (
+                Method::GET,
+                format!("/{D}/versions"),
+                "/api/documents/:document_id/versions",
+                ALL,
+            ),
+            (
+                Method::POST,
+                format!("/{D}/merge-preview"),
+                "/api/documents/:document_id/merge-preview",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/diff"),
+                "/api/documents/:document_id/diff",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/heads"),
+                "/api/documents/:document_id/heads",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/common-ancestors"),
+                "/api/documents/:document_id/common-ancestors",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/graph"),
+                "/api/documents/:document_id/graph",
+                ALL,
+            ),
+            (Method::GET, format!("/{D}/{V}"), v, ALL),
+            (
+                Method::GET,
+                format!("/{D}/{V}/ancestors"),
+                "/api/documents/:document_id/:version_id/ancestors",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/{V}/descendants"),
+                "/api/documents/:document_id/:version_id/descendants",
+                ALL,
+            ),
+            (Method::PATCH, format!("/{D}/{V}"), v, WRITERS),
+            (
+                Method::GET,
+                format!("/{D}/{V}/comments"),
+                "/api/documents/:document_id/:version_id/comments",
+                ALL,
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/comment"),
+                "/api/documents/:document_id/:version_id/comment",
+                ALL,
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/change-state"),
+                "/api/documents/:document_id/:version_id/change-state",
+                &[Owner, Editor, Reviewer],
+            ),
+            (
+                Method::GET,
+                format!("/{D}/{V}/files"),
+                "/api/documents/:document_id/:version_id/files",
+                ALL,
+            ),
+            (
+                Method::PATCH,
+                format!("/{D}/{V}/files"),
+                "/api/documents/:document_id/:version_id/files",
+                WRITERS,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/{V}/files/{F}"),
+                "/api/documents/:document_id/:version_id/files/:file_id",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/{V}/files/{F}/content"),
+                "/api/documents/:document_id/:version_id/files/:file_id/content",
+                ALL,
+            ),
+            (
+                Method::DELETE,
+                format!("/{D}/{V}/files/{F}"),
+                "/api/documents/:document_id/:version_id/files/:file_id",
+                WRITERS,
+            ),
+            (
+                Method::GET,
+                "/access-requests".into(),
+                "/api/documents/access-requests",
+                ALL,
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/access-requests"),
+                "/api/documents/:document_id/:version_id/access-requests",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/{V}/access-requests"),
+                "/api/documents/:document_id/:version_id/access-requests",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/access-requests/{F}/approve"),
+                "/api/documents/:document_id/:version_id/access-requests/:request_id/approve",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/access-requests/{F}/deny"),
+                "/api/documents/:document_id/:version_id/access-requests/:request_id/deny",
+                &[Owner],
+            ),
+            (
+                Method::GET,
+                "/transfers".into(),
+                "/api/documents/transfers",
+                ALL,
+            ),
+            (
+                Method::POST,
+                format!("/transfers/{F}/accept"),
+                "/api/documents/transfers/:transfer_id/accept",
+                ALL,
+            ),
+            (
+                Method::POST,
+                format!("/transfers/{F}/decline"),
+                "/api/documents/transfers/:transfer_id/decline",
+                ALL,
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/transfer/{F}"),
+                "/api/documents/:document_id/:version_id/transfer/:user_id",
+                &[Owner],
+            ),
+            (
+                Method::GET,
+                format!("/{D}/{V}/members"),
+                "/api/documents/:document_id/:version_id/members",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/{V}/member"),
+                "/api/documents/:document_id/:version_id/member",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/{V}/am-owner"),
+                "/api/documents/:document_id/:version_id/am-owner",
+                ALL,
+            ),
+            (
+                Method::GET,
+                format!("/{D}/{V}/groups"),
+                "/api/documents/:document_id/:version_id/groups",
+                ALL,
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/grant/{F}/viewer"),
+                "/api/documents/:document_id/:version_id/grant/:user_id/:role",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/revoke/{F}/viewer"),
+                "/api/documents/:document_id/:version_id/revoke/:user_id/:role",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/grant-group/{F}/viewer"),
+                "/api/documents/:document_id/:version_id/grant-group/:group_id/:role",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/revoke-group/{F}/viewer"),
+                "/api/documents/:document_id/:version_id/revoke-group/:group_id/:role",
+                &[Owner],
+            ),
+            (
+                Method::GET,
+                format!("/{D}/{V}/share-links"),
+                "/api/documents/:document_id/:version_id/share-links",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                format!("/{D}/{V}/share-links"),
+                "/api/documents/:document_id/:version_id/share-links",
+                &[Owner],
+            ),
+            (
+                Method::DELETE,
+                format!("/{D}/{V}/share-links/{F}"),
+                "/api/documents/:document_id/:version_id/share-links/:link_id",
+                &[Owner],
+            ),
+        ]
+    }
+
+    #[derive(Clone, FromRef)]
+    struct TestState {
+        auth_keys: AuthKeys,
+        password_hashing: PasswordHashing,
+        database: DbPool,
+        s3storage: Bucket,
+        content_storage: ContentStorage,
+    }
+
+    fn test_state(database: DbPool) -> TestState {
+        let auth_keys = AuthKeys::try_from(&AuthKeysConfig {
+            signing_key: "test".to_owned(),
+            keys: vec![AuthKeyConfig {
+                kid: "test".to_owned(),
+                encoding: Some(PathBuf::from("devkeys/priv.pem")),
+                decoding: PathBuf::from("devkeys/pub.pem"),
+                retired: false,
+            }],
+        })
+        .unwrap();
+        let credentials = Credentials::new(Some("test"), Some("test"), None, None, None).unwrap();
+        let region = Region::Custom {
+            region: "test".to_owned(),
+            endpoint: "http://localhost".to_owned(),
+        };
+        let s3storage = Bucket::new("test", region, credentials).unwrap();
+        let content_storage = ContentStorage::new(
+            &ContentStorageConfig {
+                compression_threshold: None,
+                external_threshold: 1024,
+                snapshot_interval: 16,
+                repack_interval: 300,
+            },
+            s3storage.clone(),
+        );
+        let password_hashing = PasswordHashing::try_from(&PasswordHashingConfig {
+            memory_cost: 1024,
+            time_cost: 1,
+            parallelism: 1,
+        })
+        .unwrap();
+        TestState {
+            auth_keys,
+            password_hashing,
+            database,
+            s3storage,
+            content_storage,
+        }
+    }
+
+    /// Exposes the route a request matched so it can be compared with the policy
+    async fn matched_path<B>(path: MatchedPath, request: Request<B>, next: Next<B>) -> Response {
+        let mut response = next.run(request).await;
+        response
+            .headers_mut()
+            .insert("x-matched-path", path.as_str().parse().unwrap());
+        response
+    }
+
+    fn router(state: TestState) -> Router {
+        Router::new()
+            .nest("/api/documents", documents_router())
+            .route_layer(middleware::from_fn(matched_path))
+            .with_state(state)
+    }
+
+    /// Pool which never connects, for requests rejected before reaching the database
+    fn unconnected_pool() -> DbPool {
+        let manager = PostgresConnectionManager::new(tokio_postgres::Config::new(), NoTls);
+        Pool::builder().build_unchecked(manager)
+    }
+
+    fn request(method: &Method, uri: &str, token: Option<&str>) -> Request<Body> {
+        let mut request = Request::builder()
+            .method(method.clone())
+            .uri(format!("/api/documents{}", uri));
+        if let Some(token) = token {
+            request = request.header("Authorization", format!("Bearer {token}"));
+        }
+        request.body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn every_route_requires_authentication() {
+        let router = router(test_state(unconnected_pool()));
+        for (method, uri, route, _) in routes() {
+            let response = router
+                .clone()
+                .oneshot(request(&method, &uri, None))
+                .await
+                .unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::UNAUTHORIZED,
+                "{method} {uri}"
+            );
+            assert_eq!(
+                response.headers()["x-matched-path"],
+                route,
+                "{method} {uri}"
+            );
+        }
+    }
+
+    /// Asks the built router for every method on every listed path, so a handler added
+    /// to one of them has to be listed and covered by the policy.
+    /// Paths missing from the list have no policy either and are refused by `DocumentAccess`,
+    /// see `routes_without_a_policy_are_forbidden`
+    #[tokio::test]
+    async fn router_serves_only_listed_methods() {
+        let router = router(test_state(unconnected_pool()));
+        let listed = routes();
+        for (_, uri, route, _) in &listed {
+            for method in [
+                Method::GET,
+                Method::POST,
+                Method::PUT,
+                Method::PATCH,
+                Method::DELETE,
+            ] {
+                if listed.iter().any(|(m, _, r, _)| *m == method && r == route) {
+                    continue;
+                }
+                let response = router
+                    .clone()
+                    .oneshot(request(&method, uri, None))
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    response.status(),
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    "{method} {route} is missing from routes()"
+                );
+                assert_eq!(route_permission(&method, route), None, "{method} {route}");
+            }
+        }
+    }
+
+    #[test]
+    fn every_route_forbids_other_roles() {
+        for (method, _, route, allowed) in routes() {
+            let (resource, action) = route_permission(&method, route).unwrap();
+            for role in ALL {
+                assert_eq!(
+                    has_any_role(&[*role], allowed_roles(resource, action)),
+                    allowed.contains(role),
+                    "{method} {route} as {role:?}"
+                );
+            }
+        }
+        assert_eq!(
+            route_permission(&Method::PUT, "/api/documents/:document_id"),
+            None
+        );
+        assert_eq!(route_permission(&Method::GET, "/api/events"), None);
+    }
+
+    #[test]
+    fn owning_a_child_version_does_not_own_the_document() {
+        let parent = Uuid::new_v4();
+        let child = Uuid::new_v4();
+        let versions = [parent, child];
+        for (method, route) in [
+            (
+                Method::POST,
+                "/api/documents/:document_id/grant/:user_id/:role",
+            ),
+            (
+                Method::POST,
+                "/api/documents/:document_id/revoke/:user_id/:role",
+            ),
+            (Method::PUT, "/api/documents/:document_id/label"),
+        ] {
+            let (resource, action) = route_permission(&method, route).unwrap();
+            assert!(resource.needs_every_version(action), "{route}");
+            let allowed = allowed_roles(resource, action);
+            let editor_owning_child = DocumentVersionRole::held_on_every_version(
+                &versions,
+                &[(parent, Editor), (child, Owner)],
+            );
+            assert!(!has_any_role(&editor_owning_child, allowed), "{route}");
+            let owner = DocumentVersionRole::held_on_every_version(
+                &versions,
+                &[(parent, Owner), (child, Owner)],
+            );
+            assert!(has_any_role(&owner, allowed), "{route}");
+        }
+    }
+
+    /// Signs a token for a new user holding the role on every version of the document
+    async fn member_token(
+        db: &TestDatabase,
+        keys: &AuthKeys,
+        role: Option<DocumentVersionRole>,
+    ) -> String {
+        let user_id = Uuid::new_v4();
+        let session_id = Uuid::new_v4();
+        let username = user_id.simple().to_string();
+        let connection = db.connection().await;
+        connection
+            .execute(
+                "INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, '')",
+                &[&user_id, &username],
+            )
+            .await
+            .unwrap();
+        if let Some(role) = role {
+            connection
+                .execute(
+                    "
+                    INSERT INTO user_document_version_roles (user_id, document_id, version_id, role_id)
+                    SELECT $1, document_id, version_id, $2
+                    FROM document_versions
+                    WHERE document_id = $3
+                    ",
+                    &[&user_id, &i16::from(role), &Uuid::parse_str(D).unwrap()],
+                )
+                .await
+                .unwrap();
+        }
+        connection
+            .execute(
+                "
+                INSERT INTO sessions (session_id, user_id, expires_at)
+                VALUES ($1, $2, now() + interval '1 hour')
+                ",
+                &[&session_id, &user_id],
+            )
+            .await
+            .unwrap();
+        Claims::new(user_id, username, session_id, 900)
+            .try_into_token(keys)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn every_route_forbids_authenticated_users_without_an_allowed_role() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let state = test_state(db.pool.clone());
+        let keys = state.auth_keys.clone();
+        let router = router(state);
+        let outsider = member_token(&db, &keys, None).await;
+        for role in ALL {
+            let token = member_token(&db, &keys, Some(*role)).await;
+            for (method, uri, route, allowed) in routes() {
+                if allowed.contains(role) {
+                    continue;
+                }
+                let response = router
+                    .clone()
+                    .oneshot(request(&method, &uri, Some(&token)))
+                    .await
+                    .unwrap();
+                assert_eq!(
+                    response.status(),
+                    StatusCode::FORBIDDEN,
+                    "{method} {route} as {role:?}"
+                );
+            }
+        }
+        for (method, uri, route, _) in routes() {
+            let (resource, _) = route_permission(&method, route).unwrap();
+            if resource == Resource::Documents {
+                continue;
+            }
+            let response = router
+                .clone()
+                .oneshot(request(&method, &uri, Some(&outsider)))
+                .await
+                .unwrap();
+            assert_eq!(
+                response.status(),
+                StatusCode::FORBIDDEN,
+                "{method} {route} without a role"
+            );
+        }
+        db.finish().await;
+    }
+
+    #[tokio::test]
+    async fn routes_without_a_policy_are_forbidden() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let state = test_state(db.pool.clone());
+        let keys = state.auth_keys.clone();
+        let router = Router::new()
+            .route(
+                "/api/documents/:document_id/unlisted",
+                get(|_: DocumentAccess| async {}),
+            )
+            .with_state(state);
+        let owner = member_token(&db, &keys, Some(Owner)).await;
+        let response = router
+            .oneshot(request(
+                &Method::GET,
+                &format!("/{D}/unlisted"),
+                Some(&owner),
+            ))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        db.finish().await;
+    }
+}
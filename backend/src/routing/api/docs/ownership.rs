@@ -0,0 +1,158 @@
+use axum::{
+    extract::{FromRef, Path},
+    http::StatusCode,
+    routing::{get, post},
+    Json, Router,
+};
+use s3::Bucket;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::{event::EventType, ownership::OwnershipTransfer, role::DocumentVersionRole},
+    services::{
+        auth::{auth_keys::AuthKeys, policy::DocumentAccess},
+        database::{
+            repositories::{
+                documents::UniqueError, events::EventsRepository, ownership::OwnershipRepository,
+            },
+            DbPool,
+        },
+        util::{Res2, Res3},
+    },
+};
+
+async fn offer_transfer(
+    access: DocumentAccess,
+    ownership_repository: OwnershipRepository,
+    event_repository: EventsRepository,
+    Path((document_id, version_id, user_id)): Path<(Uuid, Uuid, Uuid)>,
+) -> Res3<OwnershipTransfer> {
+    if user_id == access.claims.user_id {
+        return Res3::Msg((StatusCode::BAD_REQUEST, "Cannot transfer to yourself"));
+    }
+
+    match ownership_repository
+        .offer_transfer(access.claims.user_id, user_id, document_id, version_id)
+        .await
+    {
+        Ok(transfer) => {
+            event_repository
+                .create_event(
+                    document_id,
+                    version_id,
+                    user_id,
+                    EventType::OwnershipOffered,
+                )
+                .await
+                .ok();
+            Res3::Json((transfer, StatusCode::OK))
+        }
+        Err(UniqueError::UniqueValueViolation) => {
+            Res3::Msg((StatusCode::CONFLICT, "Transfer already offered"))
+        }
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when offering transfer"
+            );
+            Res3::NoMsg(StatusCode::BAD_REQUEST)
+        }
+    }
+}
+
+async fn get_transfers(
+    access: DocumentAccess,
+    ownership_repository: OwnershipRepository,
+) -> Result<Json<Vec<OwnershipTransfer>>, StatusCode> {
+    match ownership_repository
+        .get_transfers(access.claims.user_id)
+        .await
+    {
+        Ok(transfers) => Ok(Json(transfers)),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when getting transfers"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn accept_transfer(
+    access: DocumentAccess,
+    mut ownership_repository: OwnershipRepository,
+    event_repository: EventsRepository,
+    Path(transfer_id): Path<Uuid>,
+) -> Res2 {
+    match ownership_repository
+        .accept_transfer(access.claims.user_id, transfer_id)
+        .await
+    {
+        Ok(Some(transfer)) => {
+            let owner = DocumentVersionRole::Owner;
+            for (user_id, event_type) in [
+                (transfer.from_user_id, EventType::RoleRemoved(owner)),
+                (transfer.to_user_id, EventType::RoleAdded(owner)),
+            ] {
+                event_repository
+                    .create_event(
+                        transfer.document_id,
+                        transfer.version_id,
+                        user_id,
+                        event_type,
+                    )
+                    .await
+                    .ok();
+            }
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(None) => Res2::Msg((StatusCode::NOT_FOUND, "Transfer not found")),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when accepting transfer"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn cancel_transfer(
+    access: DocumentAccess,
+    ownership_repository: OwnershipRepository,
+    Path(transfer_id): Path<Uuid>,
+) -> StatusCode {
+    match ownership_repository
+        .cancel_transfer(access.claims.user_id, transfer_id)
+        .await
+    {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when cancelling transfer"
+            );
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+pub fn ownership_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    DbPool: FromRef<T>,
+    Bucket: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new()
+        .route("/transfers", get(get_transfers))
+        .route("/transfers/:transfer_id/accept", post(accept_transfer))
+        .route("/transfers/:transfer_id/decline", post(cancel_transfer))
+        .route(
+            "/:document_id/:version_id/transfer/:user_id",
+            post(offer_transfer),
+        )
+}
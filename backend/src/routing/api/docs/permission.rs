@@ -1,7 +1,7 @@
 use axum::{
-    extract::{FromRef, Path},
+    extract::{FromRef, Path, Query},
     http::StatusCode,
-    routing::{get, post},
+    routing::{get, post, put},
     Json, Router,
 };
 use s3::Bucket;
@@ -9,20 +9,30 @@ use tracing::error;
 use uuid::Uuid;
 
 use crate::{
-    models::{event::EventType, role::DocumentVersionRole, user::PublicUserWithRoles},
+    models::{
+        event::EventType,
+        group::GroupWithRoles,
+        role::{BulkGrant, BulkRevoke, BulkRoleChange, DocumentVersionRole, GrantValidity},
+        user::PublicUserWithRoles,
+    },
     services::{
-        auth::{auth_keys::AuthKeys, claims::Claims},
+        auth::{auth_keys::AuthKeys, policy::DocumentAccess},
         database::{
-            repositories::{events::EventsRepository, permission::PermissionRepository},
+            repositories::{
+                events::EventsRepository,
+                groups::GroupsRepository,
+                ownership::{OwnerRemoval, OwnershipRepository},
+                permission::{BulkOutcome, PermissionRepository},
+            },
             DbPool,
         },
-        util::Res2,
+        util::{Res2, Res3, ValidatedJson},
     },
 };
 
 async fn get_members(
+    _: DocumentAccess,
     permission_repository: PermissionRepository,
-    _: Claims,
     Path((document_id, version_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<Vec<PublicUserWithRoles>>, StatusCode> {
     match permission_repository
@@ -38,12 +48,12 @@ async fn get_members(
 }
 
 async fn get_member(
+    access: DocumentAccess,
     permission_repository: PermissionRepository,
-    claims: Claims,
     Path((document_id, version_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<PublicUserWithRoles>, StatusCode> {
     match permission_repository
-        .get_document_version_user(claims.user_id, document_id, version_id)
+        .get_document_version_user(access.claims.user_id, document_id, version_id)
         .await
     {
         Ok(user) => Ok(Json(user)),
@@ -54,39 +64,64 @@ async fn get_member(
     }
 }
 
-async fn am_owner(
-    permission_repository: PermissionRepository,
-    claims: Claims,
-    Path((document_id, version_id)): Path<(Uuid, Uuid)>,
-) -> StatusCode {
-    match permission_repository
-        .is_owner(claims.user_id, document_id, version_id)
-        .await
-    {
-        Ok(true) => StatusCode::OK,
-        Ok(false) => StatusCode::FORBIDDEN,
-        Err(error) => {
-            error!(
-                { error = error.to_string() },
-                "Error when checking if owner"
-            );
-            StatusCode::INTERNAL_SERVER_ERROR
-        }
+async fn am_owner(access: DocumentAccess) -> StatusCode {
+    if access.roles.contains(&DocumentVersionRole::Owner) {
+        StatusCode::OK
+    } else {
+        StatusCode::FORBIDDEN
     }
 }
 
 async fn grant_version_role(
+    _: DocumentAccess,
     permission_repository: PermissionRepository,
     event_repository: EventsRepository,
-    _: Claims,
     Path((document_id, version_id, user_id, role)): Path<(Uuid, Uuid, Uuid, DocumentVersionRole)>,
+    Query(validity): Query<GrantValidity>,
 ) -> Res2 {
-    if role == DocumentVersionRole::Owner {
-        return Res2::Msg((StatusCode::BAD_REQUEST, "Cannot grant this role"));
+    grant_role(
+        &permission_repository,
+        &event_repository,
+        document_id,
+        version_id,
+        user_id,
+        role,
+        validity,
+    )
+    .await
+}
+
+/// Grants a version role and notifies the user
+async fn grant_role(
+    permission_repository: &PermissionRepository,
+    event_repository: &EventsRepository,
+    document_id: Uuid,
+    version_id: Uuid,
+    user_id: Uuid,
+    role: DocumentVersionRole,
+    validity: GrantValidity,
+) -> Res2 {
+    if role == DocumentVersionRole::Owner
+        && (validity.valid_from.is_some() || validity.valid_until.is_some())
+    {
+        return Res2::Msg((
+            StatusCode::BAD_REQUEST,
+            "Ownership cannot be limited in time",
+        ));
+    }
+    if !validity.is_valid() {
+        return Res2::Msg((StatusCode::BAD_REQUEST, "Invalid validity window"));
     }
 
     match permission_repository
-        .grant_document_version_role(user_id, document_id, version_id, role)
+        .grant_document_version_role(
+            user_id,
+            document_id,
+            version_id,
+            role,
+            validity.valid_from,
+            validity.valid_until,
+        )
         .await
     {
         Ok(true) => {
@@ -96,7 +131,8 @@ async fn grant_version_role(
                 .ok();
             Res2::NoMsg(StatusCode::OK)
         }
-        Ok(false) => Res2::NoMsg(StatusCode::BAD_REQUEST),
+        // The role was already held, only its validity window changed
+        Ok(false) => Res2::NoMsg(StatusCode::OK),
         Err(error) => {
             error!(
                 { error = error.to_string() },
@@ -108,19 +144,30 @@ async fn grant_version_role(
 }
 
 async fn revoke_version_role(
+    _: DocumentAccess,
     permission_repository: PermissionRepository,
+    mut ownership_repository: OwnershipRepository,
     event_repository: EventsRepository,
-    _: Claims,
     Path((document_id, version_id, user_id, role)): Path<(Uuid, Uuid, Uuid, DocumentVersionRole)>,
 ) -> Res2 {
-    if role == DocumentVersionRole::Owner {
-        return Res2::Msg((StatusCode::BAD_REQUEST, "Cannot revoke this role"));
-    }
+    let revoked = if role == DocumentVersionRole::Owner {
+        match ownership_repository
+            .remove_owner(user_id, document_id, version_id)
+            .await
+        {
+            Ok(OwnerRemoval::LastOwner) => {
+                return Res2::Msg((StatusCode::CONFLICT, "Cannot revoke the last owner"))
+            }
+            Ok(removal) => Ok(removal == OwnerRemoval::Removed),
+            Err(error) => Err(error),
+        }
+    } else {
+        permission_repository
+            .revoke_document_version_role(user_id, document_id, version_id, role)
+            .await
+    };
 
-    match permission_repository
-        .revoke_document_version_role(user_id, document_id, version_id, role)
-        .await
-    {
+    match revoked {
         Ok(true) => {
             event_repository
                 .create_event(
@@ -144,6 +191,347 @@ async fn revoke_version_role(
     }
 }
 
+async fn get_member_groups(
+    _: DocumentAccess,
+    permission_repository: PermissionRepository,
+    Path((document_id, version_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Vec<GroupWithRoles>>, StatusCode> {
+    match permission_repository
+        .get_document_version_groups(document_id, version_id)
+        .await
+    {
+        Ok(groups) => Ok(Json(groups)),
+        Err(error) => {
+            error!({ error = error.to_string() }, "Error when getting groups");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Notifies every member of a group about a role change
+async fn create_group_events(
+    groups_repository: &GroupsRepository,
+    event_repository: &EventsRepository,
+    document_id: Uuid,
+    version_id: Uuid,
+    group_id: Uuid,
+    event_type: EventType,
+) {
+    let members = match groups_repository.get_members(group_id).await {
+        Ok(members) => members,
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when getting group members"
+            );
+            return;
+        }
+    };
+    for member in members {
+        event_repository
+            .create_event(
+                document_id,
+                version_id,
+                member.user.user.user_id,
+                event_type.clone(),
+            )
+            .await
+            .ok();
+    }
+}
+
+async fn grant_version_group_role(
+    _: DocumentAccess,
+    permission_repository: PermissionRepository,
+    groups_repository: GroupsRepository,
+    event_repository: EventsRepository,
+    Path((document_id, version_id, group_id, role)): Path<(Uuid, Uuid, Uuid, DocumentVersionRole)>,
+) -> Res2 {
+    if role == DocumentVersionRole::Owner {
+        return Res2::Msg((StatusCode::BAD_REQUEST, "Cannot grant this role"));
+    }
+    match groups_repository.get_group(group_id).await {
+        Ok(Some(_)) => {}
+        Ok(None) => return Res2::Msg((StatusCode::NOT_FOUND, "Group not found")),
+        Err(error) => {
+            error!({ error = error.to_string() }, "Error when getting group");
+            return Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    match permission_repository
+        .grant_document_version_group_role(group_id, document_id, version_id, role)
+        .await
+    {
+        Ok(true) => {
+            create_group_events(
+                &groups_repository,
+                &event_repository,
+                document_id,
+                version_id,
+                group_id,
+                EventType::RoleAdded(role),
+            )
+            .await;
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(false) => Res2::NoMsg(StatusCode::BAD_REQUEST),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when granting group permission"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn revoke_version_group_role(
+    _: DocumentAccess,
+    permission_repository: PermissionRepository,
+    groups_repository: GroupsRepository,
+    event_repository: EventsRepository,
+    Path((document_id, version_id, group_id, role)): Path<(Uuid, Uuid, Uuid, DocumentVersionRole)>,
+) -> Res2 {
+    match permission_repository
+        .revoke_document_version_group_role(group_id, document_id, version_id, role)
+        .await
+    {
+        Ok(true) => {
+            create_group_events(
+                &groups_repository,
+                &event_repository,
+                document_id,
+                version_id,
+                group_id,
+                EventType::RoleRemoved(role),
+            )
+            .await;
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(false) => Res2::NoMsg(StatusCode::BAD_REQUEST),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when revoking group permission"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_document_members(
+    _: DocumentAccess,
+    permission_repository: PermissionRepository,
+    Path(document_id): Path<Uuid>,
+) -> Result<Json<Vec<PublicUserWithRoles>>, StatusCode> {
+    match permission_repository.get_document_users(document_id).await {
+        Ok(users) => Ok(Json(users)),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when getting document members"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn grant_document_role(
+    _: DocumentAccess,
+    mut permission_repository: PermissionRepository,
+    event_repository: EventsRepository,
+    Path((document_id, user_id, role)): Path<(Uuid, Uuid, DocumentVersionRole)>,
+) -> Res2 {
+    if role == DocumentVersionRole::Owner {
+        return Res2::Msg((StatusCode::BAD_REQUEST, "Cannot grant this role"));
+    }
+
+    match permission_repository
+        .grant_document_role(user_id, document_id, role)
+        .await
+    {
+        Ok(Some(version_ids)) => {
+            for version_id in version_ids {
+                event_repository
+                    .create_event(document_id, version_id, user_id, EventType::RoleAdded(role))
+                    .await
+                    .ok();
+            }
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(None) => Res2::NoMsg(StatusCode::BAD_REQUEST),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when granting document permission"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn revoke_document_role(
+    _: DocumentAccess,
+    mut permission_repository: PermissionRepository,
+    event_repository: EventsRepository,
+    Path((document_id, user_id, role)): Path<(Uuid, Uuid, DocumentVersionRole)>,
+) -> Res2 {
+    match permission_repository
+        .revoke_document_role(user_id, document_id, role)
+        .await
+    {
+        Ok(Some(version_ids)) => {
+            for version_id in version_ids {
+                event_repository
+                    .create_event(
+                        document_id,
+                        version_id,
+                        user_id,
+                        EventType::RoleRemoved(role),
+                    )
+                    .await
+                    .ok();
+            }
+            Res2::NoMsg(StatusCode::OK)
+        }
+        Ok(None) => Res2::NoMsg(StatusCode::BAD_REQUEST),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when revoking document permission"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+fn bulk_response(outcome: BulkOutcome) -> Res3<Vec<BulkRoleChange>> {
+    match outcome {
+        BulkOutcome::Changed(changes) => Res3::Json((changes, StatusCode::OK)),
+        BulkOutcome::NoVersions => {
+            Res3::Msg((StatusCode::BAD_REQUEST, "No versions match the selector"))
+        }
+        BulkOutcome::NotOwner => Res3::Msg((
+            StatusCode::FORBIDDEN,
+            "Not an owner of every selected version",
+        )),
+    }
+}
+
+async fn bulk_grant(
+    access: DocumentAccess,
+    mut permission_repository: PermissionRepository,
+    Path(document_id): Path<Uuid>,
+    ValidatedJson(data): ValidatedJson<BulkGrant>,
+) -> Res3<Vec<BulkRoleChange>> {
+    if data.role == DocumentVersionRole::Owner {
+        return Res3::Msg((StatusCode::BAD_REQUEST, "Cannot grant this role in bulk"));
+    }
+    if !data.validity.is_valid() {
+        return Res3::Msg((StatusCode::BAD_REQUEST, "Invalid validity window"));
+    }
+
+    match permission_repository
+        .bulk_grant(access.claims.user_id, document_id, &data)
+        .await
+    {
+        Ok(outcome) => bulk_response(outcome),
+        Err(error) => {
+            error!({ error = error.to_string() }, "Error when granting in bulk");
+            Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn bulk_revoke(
+    access: DocumentAccess,
+    mut permission_repository: PermissionRepository,
+    Path(document_id): Path<Uuid>,
+    ValidatedJson(data): ValidatedJson<BulkRevoke>,
+) -> Res3<Vec<BulkRoleChange>> {
+    if data.role == Some(DocumentVersionRole::Owner) {
+        return Res3::Msg((StatusCode::BAD_REQUEST, "Cannot revoke this role in bulk"));
+    }
+
+    match permission_repository
+        .bulk_revoke(access.claims.user_id, document_id, &data)
+        .await
+    {
+        Ok(outcome) => bulk_response(outcome),
+        Err(error) => {
+            error!({ error = error.to_string() }, "Error when revoking in bulk");
+            Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_default_members(
+    _: DocumentAccess,
+    permission_repository: PermissionRepository,
+    Path(document_id): Path<Uuid>,
+) -> Result<Json<Vec<PublicUserWithRoles>>, StatusCode> {
+    match permission_repository
+        .get_document_default_members(document_id)
+        .await
+    {
+        Ok(users) => Ok(Json(users)),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when getting default members"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn add_default_member(
+    _: DocumentAccess,
+    permission_repository: PermissionRepository,
+    Path((document_id, user_id, role)): Path<(Uuid, Uuid, DocumentVersionRole)>,
+) -> Res2 {
+    if role == DocumentVersionRole::Owner {
+        return Res2::Msg((StatusCode::BAD_REQUEST, "Cannot grant this role"));
+    }
+
+    match permission_repository
+        .add_document_default_member(document_id, user_id, role)
+        .await
+    {
+        Ok(true) => Res2::NoMsg(StatusCode::OK),
+        Ok(false) => Res2::NoMsg(StatusCode::BAD_REQUEST),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when adding default member"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn remove_default_member(
+    _: DocumentAccess,
+    permission_repository: PermissionRepository,
+    Path((document_id, user_id, role)): Path<(Uuid, Uuid, DocumentVersionRole)>,
+) -> Res2 {
+    match permission_repository
+        .remove_document_default_member(document_id, user_id, role)
+        .await
+    {
+        Ok(true) => Res2::NoMsg(StatusCode::OK),
+        Ok(false) => Res2::NoMsg(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when removing default member"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
 pub fn permission_router<T>() -> Router<T>
 where
     AuthKeys: FromRef<T>,
@@ -152,9 +540,26 @@ where
     T: 'static + Send + Sync + Clone,
 {
     Router::new()
+        .route("/:document_id/members", get(get_document_members))
+        .route(
+            "/:document_id/grant/:user_id/:role",
+            post(grant_document_role),
+        )
+        .route(
+            "/:document_id/revoke/:user_id/:role",
+            post(revoke_document_role),
+        )
+        .route("/:document_id/bulk-grant", post(bulk_grant))
+        .route("/:document_id/bulk-revoke", post(bulk_revoke))
+        .route("/:document_id/default-members", get(get_default_members))
+        .route(
+            "/:document_id/default-members/:user_id/:role",
+            put(add_default_member).delete(remove_default_member),
+        )
         .route("/:document_id/:version_id/members", get(get_members))
         .route("/:document_id/:version_id/member", get(get_member))
         .route("/:document_id/:version_id/am-owner", get(am_owner))
+        .route("/:document_id/:version_id/groups", get(get_member_groups))
         .route(
             "/:document_id/:version_id/grant/:user_id/:role",
             post(grant_version_role),
@@ -163,4 +568,12 @@ where
             "/:document_id/:version_id/revoke/:user_id/:role",
             post(revoke_version_role),
         )
+        .route(
+            "/:document_id/:version_id/grant-group/:group_id/:role",
+            post(grant_version_group_role),
+        )
+        .route(
+            "/:document_id/:version_id/revoke-group/:group_id/:role",
+            post(revoke_version_group_role),
+        )
 }
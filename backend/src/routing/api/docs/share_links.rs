@@ -0,0 +1,116 @@
+use axum::{
+    extract::{FromRef, Path},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use chrono::Utc;
+use s3::Bucket;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::share_link::{CreateShareLink, CreatedShareLink, ShareLink},
+    services::{
+        auth::{auth_keys::AuthKeys, password::PasswordHashing, policy::DocumentAccess},
+        content_storage::ContentStorage,
+        database::{repositories::share_links::ShareLinksRepository, DbPool},
+        util::{Res2, Res3, ValidatedJson},
+    },
+};
+
+async fn create_share_link(
+    access: DocumentAccess,
+    share_links_repository: ShareLinksRepository,
+    Path((document_id, version_id)): Path<(Uuid, Uuid)>,
+    ValidatedJson(data): ValidatedJson<CreateShareLink>,
+) -> Res3<CreatedShareLink> {
+    if matches!(data.expires_at, Some(expires_at) if expires_at <= Utc::now()) {
+        return Res3::Msg((StatusCode::BAD_REQUEST, "Link would already be expired"));
+    }
+
+    match share_links_repository
+        .create_link(
+            document_id,
+            version_id,
+            access.claims.user_id,
+            data.expires_at,
+            data.password,
+        )
+        .await
+    {
+        Ok(Some(link)) => Res3::Json((link, StatusCode::OK)),
+        Ok(None) => Res3::Msg((
+            StatusCode::BAD_REQUEST,
+            "Only published versions of public documents can be shared",
+        )),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when creating share link"
+            );
+            Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_share_links(
+    _: DocumentAccess,
+    share_links_repository: ShareLinksRepository,
+    Path((document_id, version_id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<Vec<ShareLink>>, StatusCode> {
+    match share_links_repository
+        .get_links(document_id, version_id)
+        .await
+    {
+        Ok(links) => Ok(Json(links)),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when getting share links"
+            );
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn revoke_share_link(
+    _: DocumentAccess,
+    share_links_repository: ShareLinksRepository,
+    Path((document_id, version_id, link_id)): Path<(Uuid, Uuid, Uuid)>,
+) -> Res2 {
+    match share_links_repository
+        .revoke_link(document_id, version_id, link_id)
+        .await
+    {
+        Ok(true) => Res2::NoMsg(StatusCode::OK),
+        Ok(false) => Res2::Msg((StatusCode::NOT_FOUND, "Share link not found")),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when revoking share link"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub fn share_links_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    PasswordHashing: FromRef<T>,
+    DbPool: FromRef<T>,
+    Bucket: FromRef<T>,
+    ContentStorage: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new()
+        .route(
+            "/:document_id/:version_id/share-links",
+            get(get_share_links).post(create_share_link),
+        )
+        .route(
+            "/:document_id/:version_id/share-links/:link_id",
+            delete(revoke_share_link),
+        )
+}
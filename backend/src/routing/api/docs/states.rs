@@ -9,14 +9,13 @@ use tracing::error;
 use uuid::Uuid;
 
 use crate::{
-    models::{
-        event::EventType,
-        role::DocumentVersionRole,
-        version::DocumentVersion,
-        version_state::{DocumentVersionState, VersionChangeState},
-    },
+    models::{event::EventType, version::DocumentVersion, version_state::VersionChangeState},
     services::{
-        auth::{auth_keys::AuthKeys, claims::Claims},
+        auth::{
+            auth_keys::AuthKeys,
+            policy::{has_any_role, state_change_roles, DocumentAccess},
+        },
+        content_storage::ContentStorage,
         database::{
             repositories::{
                 documents::{ConcurrencyError, DocumentsRepository},
@@ -30,52 +29,18 @@ use crate::{
 };
 
 async fn change_state(
+    access: DocumentAccess,
     documents_repository: DocumentsRepository,
     permission_repository: PermissionRepository,
     event_repository: EventsRepository,
-    claims: Claims,
     Path((document_id, version_id)): Path<(Uuid, Uuid)>,
     Json(data): Json<VersionChangeState>,
 ) -> Res3<DocumentVersion> {
-    let required_roles = match data.new_state {
-        DocumentVersionState::InProgress => {
-            vec![
-                DocumentVersionRole::Owner,
-                DocumentVersionRole::Editor,
-                DocumentVersionRole::Reviewer,
-            ]
-        }
-        DocumentVersionState::ReadyForReview => {
-            vec![DocumentVersionRole::Owner, DocumentVersionRole::Editor]
-        }
-        DocumentVersionState::Reviewed => vec![DocumentVersionRole::Reviewer],
-        DocumentVersionState::Published => {
-            vec![DocumentVersionRole::Owner]
-        }
-    };
-    match permission_repository
-        .does_user_have_document_version_roles(
-            claims.user_id,
-            document_id,
-            version_id,
-            &required_roles,
-        )
-        .await
-    {
-        Ok(true) => {}
-        Ok(false) => {
-            return Res3::Msg((
-                StatusCode::BAD_REQUEST,
-                "User does not have permission to perform this state change",
-            ));
-        }
-        Err(error) => {
-            error!(
-                { error = error.to_string() },
-                "Error when checking permission for changing state"
-            );
-            return Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR);
-        }
+    if !has_any_role(&access.roles, state_change_roles(data.new_state)) {
+        return Res3::Msg((
+            StatusCode::FORBIDDEN,
+            "User does not have permission to perform this state change",
+        ));
     }
     match documents_repository
         .change_state(document_id, version_id, data.new_state, data.updated_at)
@@ -91,7 +56,7 @@ async fn change_state(
                     .create_event(
                         document_id,
                         version_id,
-                        user.user.user_id,
+                        user.user.user.user_id,
                         EventType::StatusChange(data.new_state),
                     )
                     .await
@@ -110,6 +75,10 @@ async fn change_state(
             error!({ error = error.to_string() }, "Error when changing state");
             Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
         }
+        Err(ConcurrencyError::Content(error)) => {
+            error!({ error = error.to_string() }, "Error when changing state");
+            Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
     }
 }
 
@@ -118,6 +87,7 @@ where
     AuthKeys: FromRef<T>,
     DbPool: FromRef<T>,
     Bucket: FromRef<T>,
+    ContentStorage: FromRef<T>,
     T: 'static + Send + Sync + Clone,
 {
     Router::new().route("/:document_id/:version_id/change-state", post(change_state))
@@ -1,5 +1,5 @@
 use axum::{
-    extract::{FromRef, Path},
+    extract::{DefaultBodyLimit, FromRef, Path, Query},
     http::StatusCode,
     routing::{get, patch, post},
     Json, Router,
@@ -11,56 +11,87 @@ use uuid::Uuid;
 use crate::{
     models::{
         comment::{CreateDocumentVersionComment, DocumentVersionComment},
-        role::DocumentVersionRole,
-        version::{CreateVersionWithParents, DocumentVersion, UpdateVersion},
+        diff::{DiffQuery, VersionDiff},
+        merge::{MergeParents, MergePreview},
+        version::{CreateVersionWithParents, DocumentVersion, UpdateVersion, MAX_CONTENT_LENGTH},
     },
     services::{
-        auth::{auth_keys::AuthKeys, claims::Claims},
+        auth::{auth_keys::AuthKeys, policy::DocumentAccess},
+        content_storage::ContentStorage,
         database::{
             repositories::{
                 comments::CommentsRepository,
                 documents::{ConcurrencyError, DocumentsRepository, UniqueError},
-                permission::PermissionRepository,
                 RepoError,
             },
             DbPool,
         },
+        text_diff::diff_versions,
+        text_merge::merge_versions,
         util::{Res3, ValidatedJson},
     },
 };
 
+use super::CONTENT_BODY_LIMIT;
+
 async fn create_version(
+    access: DocumentAccess,
     mut documents_repository: DocumentsRepository,
-    claims: Claims,
     Path(document_id): Path<Uuid>,
     ValidatedJson(data): ValidatedJson<CreateVersionWithParents>,
-) -> Result<Json<DocumentVersion>, StatusCode> {
+) -> Result<Json<DocumentVersion>, Res3<MergePreview>> {
+    let content = if data.merge {
+        let preview = merge_parents(
+            &documents_repository,
+            access.claims.user_id,
+            document_id,
+            &data.parents,
+        )
+        .await?;
+        if !preview.clean {
+            return Err(Res3::Json((preview, StatusCode::CONFLICT)));
+        }
+        if preview.content.chars().count() as u64 > MAX_CONTENT_LENGTH {
+            return Err(Res3::Msg((
+                StatusCode::BAD_REQUEST,
+                "Merged content is too long",
+            )));
+        }
+        preview.content
+    } else {
+        data.content
+    };
     let result = documents_repository
         .create_version(
-            claims.user_id,
+            access.claims.user_id,
             document_id,
             data.version_name,
-            data.content,
+            content,
             data.parents,
+            data.inherit_members,
         )
         .await;
     match result {
         Ok(version) => Ok(Json(version)),
-        Err(UniqueError::UniqueValueViolation) => Err(StatusCode::CONFLICT),
+        Err(UniqueError::UniqueValueViolation) => Err(Res3::NoMsg(StatusCode::CONFLICT)),
+        Err(UniqueError::InvalidParents) => Err(Res3::Msg((
+            StatusCode::BAD_REQUEST,
+            "Parents must be distinct versions of the document",
+        ))),
         Err(error) => {
             error!("{}", error);
-            Err(StatusCode::BAD_REQUEST)
+            Err(Res3::NoMsg(StatusCode::BAD_REQUEST))
         }
     }
 }
 
 async fn get_version(
+    access: DocumentAccess,
     documents_repository: DocumentsRepository,
-    claims: Claims,
     Path((document_id, version_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<DocumentVersion>, StatusCode> {
     match documents_repository
-        .get_version(claims.user_id, document_id, version_id)
+        .get_version(access.claims.user_id, document_id, version_id)
         .await
     {
         Ok(versions) => Ok(Json(versions)),
@@ -73,12 +104,12 @@ async fn get_version(
 }
 
 async fn get_versions(
+    access: DocumentAccess,
     documents_repository: DocumentsRepository,
-    claims: Claims,
     Path(document_id): Path<Uuid>,
 ) -> Result<Json<Vec<DocumentVersion>>, StatusCode> {
     let versions = documents_repository
-        .get_versions(claims.user_id, document_id)
+        .get_versions(access.claims.user_id, document_id)
         .await
         .map_err(|e| {
             error!("{}", e);
@@ -87,37 +118,125 @@ async fn get_versions(
     Ok(Json(versions))
 }
 
-async fn update_version(
+/// Reads a version to compare with the visibility checks of `get_version`
+async fn get_compared_version(
+    documents_repository: &DocumentsRepository,
+    user_id: Uuid,
+    document_id: Uuid,
+    version_id: Uuid,
+) -> Result<DocumentVersion, StatusCode> {
+    documents_repository
+        .get_version(user_id, document_id, version_id)
+        .await
+        .map_err(|error| match error {
+            RepoError::Forbidden => StatusCode::FORBIDDEN,
+            error => {
+                error!("{}", error);
+                StatusCode::BAD_REQUEST
+            }
+        })
+}
+
+async fn get_diff(
+    access: DocumentAccess,
     documents_repository: DocumentsRepository,
-    permission_repository: PermissionRepository,
-    claims: Claims,
+    Path(document_id): Path<Uuid>,
+    Query(query): Query<DiffQuery>,
+) -> Result<Json<VersionDiff>, StatusCode> {
+    let user_id = access.claims.user_id;
+    let to_id = query.to.or(query.from).ok_or(StatusCode::BAD_REQUEST)?;
+    let to = get_compared_version(&documents_repository, user_id, document_id, to_id).await?;
+    let from_id = match (query.from, query.to) {
+        (Some(from_id), Some(_)) => Some(from_id),
+        // The parent is ambiguous for merged versions
+        _ => match to.parents.as_slice() {
+            [] => None,
+            [parent_id] => Some(*parent_id),
+            _ => return Err(StatusCode::BAD_REQUEST),
+        },
+    };
+    let from = match from_id {
+        Some(from_id) => {
+            Some(get_compared_version(&documents_repository, user_id, document_id, from_id).await?)
+        }
+        None => None,
+    };
+    Ok(Json(diff_versions(from.as_ref(), &to)))
+}
+
+/// Three-way merge of the content of two parents, both need a role like when reading them.
+/// Merges take exactly two parents and their lowest common ancestor has to be unique,
+/// criss-cross histories have to be merged pairwise first so the base isn't an arbitrary pick
+async fn merge_parents(
+    documents_repository: &DocumentsRepository,
+    user_id: Uuid,
+    document_id: Uuid,
+    parents: &[Uuid],
+) -> Result<MergePreview, Res3<MergePreview>> {
+    let &[ours_id, theirs_id] = parents else {
+        return Err(Res3::Msg((
+            StatusCode::BAD_REQUEST,
+            "Merging takes exactly two parents",
+        )));
+    };
+    let ours = get_compared_version(documents_repository, user_id, document_id, ours_id)
+        .await
+        .map_err(Res3::NoMsg)?;
+    let theirs = get_compared_version(documents_repository, user_id, document_id, theirs_id)
+        .await
+        .map_err(Res3::NoMsg)?;
+    let bases = documents_repository
+        .get_lowest_common_ancestors(document_id, ours_id, theirs_id)
+        .await
+        .map_err(|error| {
+            error!({ error = error.to_string() }, "Error when merging versions");
+            Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        })?;
+    let base = match bases.as_slice() {
+        [] => None,
+        [base] => Some(*base),
+        _ => {
+            return Err(Res3::Msg((
+                StatusCode::BAD_REQUEST,
+                "Parents have several lowest common ancestors",
+            )))
+        }
+    };
+    let base_content = match base {
+        Some(base) => documents_repository
+            .get_content(document_id, base)
+            .await
+            .map_err(|error| {
+                error!({ error = error.to_string() }, "Error when merging versions");
+                Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+            })?,
+        None => String::new(),
+    };
+    Ok(merge_versions(base, &base_content, &ours, &theirs))
+}
+
+async fn merge_preview(
+    access: DocumentAccess,
+    documents_repository: DocumentsRepository,
+    Path(document_id): Path<Uuid>,
+    ValidatedJson(data): ValidatedJson<MergeParents>,
+) -> Result<Json<MergePreview>, Res3<MergePreview>> {
+    let preview = merge_parents(
+        &documents_repository,
+        access.claims.user_id,
+        document_id,
+        &data.parents,
+    )
+    .await?;
+    Ok(Json(preview))
+}
+
+async fn update_version(
+    _: DocumentAccess,
+    mut documents_repository: DocumentsRepository,
     Path((document_id, version_id)): Path<(Uuid, Uuid)>,
     ValidatedJson(data): ValidatedJson<UpdateVersion>,
 ) -> Res3<DocumentVersion> {
-    match permission_repository
-        .does_user_have_document_version_roles(
-            claims.user_id,
-            document_id,
-            version_id,
-            &[DocumentVersionRole::Owner, DocumentVersionRole::Editor],
-        )
-        .await
-    {
-        Ok(true) => {}
-        Ok(false) => {
-            return Res3::Msg((
-                StatusCode::FORBIDDEN,
-                "User does not have permission to perform update",
-            ));
-        }
-        Err(error) => {
-            error!(
-                { error = error },
-                "Error during version update permission check"
-            );
-            return Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR);
-        }
-    }
     match documents_repository
         .update_version(document_id, version_id, data.content, data.updated_at)
         .await
@@ -133,16 +252,20 @@ async fn update_version(
             error!({ error = error.to_string() }, "Error during version update");
             Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
         }
+        Err(ConcurrencyError::Content(error)) => {
+            error!({ error = error.to_string() }, "Error during version update");
+            Res3::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
     }
 }
 
 async fn get_comments(
+    access: DocumentAccess,
     comments_repository: CommentsRepository,
-    claims: Claims,
     Path((document_id, version_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Json<Vec<DocumentVersionComment>>, StatusCode> {
     let comments = comments_repository
-        .get_comments(claims.user_id, document_id, version_id)
+        .get_comments(access.claims.user_id, document_id, version_id)
         .await
         .map_err(|e| {
             error!("{}", e);
@@ -152,13 +275,13 @@ async fn get_comments(
 }
 
 async fn create_comment(
+    access: DocumentAccess,
     comments_repository: CommentsRepository,
-    claims: Claims,
     Path((document_id, version_id)): Path<(Uuid, Uuid)>,
     Json(data): Json<CreateDocumentVersionComment>,
 ) -> Result<Json<DocumentVersionComment>, StatusCode> {
     let comment = comments_repository
-        .create_comment(claims.user_id, document_id, version_id, data.content)
+        .create_comment(access.claims.user_id, document_id, version_id, data.content)
         .await
         .map_err(|e| {
             error!("{}", e);
@@ -172,13 +295,17 @@ where
     AuthKeys: FromRef<T>,
     DbPool: FromRef<T>,
     Bucket: FromRef<T>,
+    ContentStorage: FromRef<T>,
     T: 'static + Send + Sync + Clone,
 {
     Router::new()
         .route("/:document_id", post(create_version))
         .route("/:document_id/versions", get(get_versions))
+        .route("/:document_id/diff", get(get_diff))
+        .route("/:document_id/merge-preview", post(merge_preview))
         .route("/:document_id/:version_id", get(get_version))
         .route("/:document_id/:version_id", patch(update_version))
         .route("/:document_id/:version_id/comments", get(get_comments))
         .route("/:document_id/:version_id/comment", post(create_comment))
+        .layer(DefaultBodyLimit::max(CONTENT_BODY_LIMIT))
 }
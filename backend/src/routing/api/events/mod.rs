@@ -32,13 +32,19 @@ async fn get_events_for_user(
 async fn mark_read(
     event_repository: EventsRepository,
     Path(event_id): Path<Uuid>,
-    _: Claims,
+    claims: Claims,
 ) -> Result<(), StatusCode> {
-    event_repository.mark_read(event_id).await.map_err(|e| {
-        error!("{}", e);
-        StatusCode::BAD_REQUEST
-    })?;
-    Ok(())
+    let marked = event_repository
+        .mark_read(claims.user_id, event_id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            StatusCode::BAD_REQUEST
+        })?;
+    match marked {
+        true => Ok(()),
+        false => Err(StatusCode::NOT_FOUND),
+    }
 }
 
 pub fn events_router<T>() -> Router<T>
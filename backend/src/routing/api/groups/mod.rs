@@ -0,0 +1,279 @@
+use axum::{
+    extract::{FromRef, Path},
+    http::StatusCode,
+    routing::{get, put},
+    Json, Router,
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::group::{AddGroupMember, CreateGroup, Group, GroupWithMembers, UpdateGroup},
+    services::{
+        auth::{auth_keys::AuthKeys, claims::Claims},
+        database::{
+            repositories::{
+                documents::UniqueError, groups::GroupsRepository, permission::PermissionRepository,
+            },
+            DbPool,
+        },
+        util::{Res2, ValidatedJson},
+    },
+};
+
+/// Lets members (or only managers) and admins through, returning the group
+async fn check_access(
+    groups_repository: &GroupsRepository,
+    permission_repository: &PermissionRepository,
+    user_id: Uuid,
+    group_id: Uuid,
+    manage: bool,
+) -> Result<Group, StatusCode> {
+    let group = match groups_repository.get_group(group_id).await {
+        Ok(Some(group)) => group,
+        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!({ error = error }, "Failed to fetch group");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+    match groups_repository.get_membership(group_id, user_id).await {
+        Ok(Some(is_manager)) if is_manager || !manage => return Ok(group),
+        Ok(_) => {}
+        Err(error) => {
+            error!({ error = error }, "Failed to fetch membership");
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    match permission_repository.is_admin(user_id).await {
+        Ok(true) => Ok(group),
+        Ok(false) => Err(StatusCode::FORBIDDEN),
+        Err(error) => {
+            error!({ error = error }, "Failed to check permissions");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Whether the change would leave the group without managers
+async fn is_last_manager(
+    groups_repository: &GroupsRepository,
+    group_id: Uuid,
+    user_id: Uuid,
+) -> Result<bool, StatusCode> {
+    let members = groups_repository.get_members(group_id).await.map_err(|e| {
+        error!("{}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let mut managers = members.iter().filter(|m| m.is_manager);
+    let only_manager = managers.next().map(|m| m.user.user.user_id);
+    Ok(only_manager == Some(user_id) && managers.next().is_none())
+}
+
+async fn get_groups(
+    groups_repository: GroupsRepository,
+    claims: Claims,
+) -> Result<Json<Vec<Group>>, StatusCode> {
+    match groups_repository.get_groups(claims.user_id).await {
+        Ok(groups) => Ok(Json(groups)),
+        Err(error) => {
+            error!({ error = error }, "Failed to fetch groups");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn create_group(
+    mut groups_repository: GroupsRepository,
+    claims: Claims,
+    ValidatedJson(data): ValidatedJson<CreateGroup>,
+) -> Result<Json<Group>, Res2> {
+    match groups_repository
+        .create_group(claims.user_id, data.group_name)
+        .await
+    {
+        Ok(group) => Ok(Json(group)),
+        Err(UniqueError::UniqueValueViolation) => Err(Res2::Msg((
+            StatusCode::CONFLICT,
+            "Group name already in use",
+        ))),
+        Err(error) => {
+            error!({ error = error.to_string() }, "Failed to create group");
+            Err(Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR))
+        }
+    }
+}
+
+async fn get_group(
+    groups_repository: GroupsRepository,
+    permission_repository: PermissionRepository,
+    claims: Claims,
+    Path(group_id): Path<Uuid>,
+) -> Result<Json<GroupWithMembers>, StatusCode> {
+    let group = check_access(
+        &groups_repository,
+        &permission_repository,
+        claims.user_id,
+        group_id,
+        false,
+    )
+    .await?;
+    match groups_repository.get_members(group_id).await {
+        Ok(members) => Ok(Json(GroupWithMembers { group, members })),
+        Err(error) => {
+            error!({ error = error }, "Failed to fetch group members");
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn update_group(
+    groups_repository: GroupsRepository,
+    permission_repository: PermissionRepository,
+    claims: Claims,
+    Path(group_id): Path<Uuid>,
+    ValidatedJson(data): ValidatedJson<UpdateGroup>,
+) -> Res2 {
+    if let Err(status) = check_access(
+        &groups_repository,
+        &permission_repository,
+        claims.user_id,
+        group_id,
+        true,
+    )
+    .await
+    {
+        return Res2::NoMsg(status);
+    }
+    match groups_repository
+        .rename_group(group_id, data.group_name)
+        .await
+    {
+        Ok(true) => Res2::NoMsg(StatusCode::OK),
+        Ok(false) => Res2::NoMsg(StatusCode::NOT_FOUND),
+        Err(UniqueError::UniqueValueViolation) => {
+            Res2::Msg((StatusCode::CONFLICT, "Group name already in use"))
+        }
+        Err(error) => {
+            error!({ error = error.to_string() }, "Failed to rename group");
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn delete_group(
+    groups_repository: GroupsRepository,
+    permission_repository: PermissionRepository,
+    claims: Claims,
+    Path(group_id): Path<Uuid>,
+) -> StatusCode {
+    if let Err(status) = check_access(
+        &groups_repository,
+        &permission_repository,
+        claims.user_id,
+        group_id,
+        true,
+    )
+    .await
+    {
+        return status;
+    }
+    match groups_repository.delete_group(group_id).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(error) => {
+            error!({ error = error }, "Failed to delete group");
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Adds a member or changes whether they manage the group
+async fn set_member(
+    groups_repository: GroupsRepository,
+    permission_repository: PermissionRepository,
+    claims: Claims,
+    Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+    Json(data): Json<AddGroupMember>,
+) -> Res2 {
+    if let Err(status) = check_access(
+        &groups_repository,
+        &permission_repository,
+        claims.user_id,
+        group_id,
+        true,
+    )
+    .await
+    {
+        return Res2::NoMsg(status);
+    }
+    if !data.is_manager {
+        match is_last_manager(&groups_repository, group_id, user_id).await {
+            Ok(false) => {}
+            Ok(true) => return Res2::Msg((StatusCode::CONFLICT, "Group needs a manager")),
+            Err(status) => return Res2::NoMsg(status),
+        }
+    }
+    match groups_repository
+        .set_member(group_id, user_id, data.is_manager)
+        .await
+    {
+        Ok(true) => Res2::NoMsg(StatusCode::OK),
+        Ok(false) => Res2::Msg((StatusCode::NOT_FOUND, "User not found")),
+        Err(error) => {
+            error!({ error = error }, "Failed to set group member");
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Removes a member, members can also leave on their own
+async fn remove_member(
+    groups_repository: GroupsRepository,
+    permission_repository: PermissionRepository,
+    claims: Claims,
+    Path((group_id, user_id)): Path<(Uuid, Uuid)>,
+) -> Res2 {
+    if let Err(status) = check_access(
+        &groups_repository,
+        &permission_repository,
+        claims.user_id,
+        group_id,
+        user_id != claims.user_id,
+    )
+    .await
+    {
+        return Res2::NoMsg(status);
+    }
+    match is_last_manager(&groups_repository, group_id, user_id).await {
+        Ok(false) => {}
+        Ok(true) => return Res2::Msg((StatusCode::CONFLICT, "Group needs a manager")),
+        Err(status) => return Res2::NoMsg(status),
+    }
+    match groups_repository.remove_member(group_id, user_id).await {
+        Ok(true) => Res2::NoMsg(StatusCode::OK),
+        Ok(false) => Res2::NoMsg(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!({ error = error }, "Failed to remove group member");
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+pub fn groups_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    DbPool: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new()
+        .route("/", get(get_groups).post(create_group))
+        .route(
+            "/:group_id",
+            get(get_group).patch(update_group).delete(delete_group),
+        )
+        .route(
+            "/:group_id/members/:user_id",
+            put(set_member).delete(remove_member),
+        )
+}
@@ -1,31 +1,50 @@
 //! Api server
 
+pub mod admin;
 pub mod auth;
 pub mod docs;
 pub mod events;
+pub mod groups;
 pub mod sets;
+pub mod shared;
 
 use axum::{extract::FromRef, http::StatusCode, response::IntoResponse, Router};
 use s3::Bucket;
 
-use crate::services::{auth::auth_keys::AuthKeys, database::DbPool};
+use crate::services::{
+    auth::{
+        auth_keys::AuthKeys, oidc::OidcClient, password::PasswordHashing, session::SessionConfig,
+        throttling::ThrottlingConfig,
+    },
+    content_storage::ContentStorage,
+    database::DbPool,
+};
 
 use self::{
-    auth::auth_router, docs::documents_router, events::events_router, sets::document_sets_router,
+    admin::admin_router, auth::auth_router, docs::documents_router, events::events_router,
+    groups::groups_router, sets::document_sets_router, shared::shared_router,
 };
 
 pub fn api_router<T>() -> Router<T>
 where
     AuthKeys: FromRef<T>,
+    PasswordHashing: FromRef<T>,
+    SessionConfig: FromRef<T>,
+    ThrottlingConfig: FromRef<T>,
+    Option<OidcClient>: FromRef<T>,
     DbPool: FromRef<T>,
     Bucket: FromRef<T>,
+    ContentStorage: FromRef<T>,
     T: 'static + Send + Sync + Clone,
 {
     Router::new()
         .nest("/auth", auth_router())
+        .nest("/admin", admin_router())
         .nest("/documents", documents_router())
         .nest("/document-sets", document_sets_router())
         .nest("/events", events_router())
+        .nest("/groups", groups_router())
+        .nest("/shared", shared_router())
         .fallback(handler_404)
 }
 
@@ -9,24 +9,39 @@ use uuid::Uuid;
 
 use crate::{
     models::{
-        document_set::{CreateDocumentSet, DocumentSet, DocumentSetWithInitialVersion},
+        document_set::{
+            CreateDocumentSet, DocumentSet, DocumentSetMember, DocumentSetWithInitialVersion,
+        },
+        role::DocumentSetRole,
         set_version::{CreateSetVersionWithParents, SetVersion, UpdateSetVersion},
     },
     services::{
-        auth::{auth_keys::AuthKeys, claims::Claims},
-        database::{repositories::document_sets::DocumentSetsRepository, DbPool},
-        util::ValidatedJson,
+        auth::{auth_keys::AuthKeys, policy::DocumentSetAccess},
+        database::{
+            repositories::{
+                document_sets::DocumentSetsRepository, permission::PermissionRepository,
+            },
+            DbPool,
+        },
+        util::{Res2, ValidatedJson},
     },
 };
 
 async fn create_document_set(
+    access: DocumentSetAccess,
     mut set_repository: DocumentSetsRepository,
-    claims: Claims,
+    permission_repository: PermissionRepository,
     ValidatedJson(data): ValidatedJson<CreateDocumentSet>,
 ) -> Result<Json<DocumentSetWithInitialVersion>, StatusCode> {
+    check_visible(
+        &permission_repository,
+        access.claims.user_id,
+        &data.initial_version.document_version_ids,
+    )
+    .await?;
     let document_set = set_repository
         .create_document_set(
-            claims.user_id,
+            access.claims.user_id,
             data.document_set_name,
             data.initial_version.set_version_name,
             data.initial_version.document_version_ids,
@@ -40,11 +55,11 @@ async fn create_document_set(
 }
 
 async fn get_document_sets(
+    access: DocumentSetAccess,
     set_repository: DocumentSetsRepository,
-    claims: Claims,
 ) -> Result<Json<Vec<DocumentSet>>, StatusCode> {
     let document_sets = set_repository
-        .get_document_sets(claims.user_id)
+        .get_document_sets(access.claims.user_id)
         .await
         .map_err(|e| {
             error!("{}", e);
@@ -54,12 +69,12 @@ async fn get_document_sets(
 }
 
 async fn get_document_set(
+    access: DocumentSetAccess,
     set_repository: DocumentSetsRepository,
-    claims: Claims,
     Path(document_set_id): Path<Uuid>,
 ) -> Result<Json<DocumentSet>, StatusCode> {
     let set = set_repository
-        .get_document_set(claims.user_id, document_set_id)
+        .get_document_set(access.claims.user_id, document_set_id)
         .await
         .map_err(|e| {
             error!("{}", e);
@@ -69,14 +84,20 @@ async fn get_document_set(
 }
 
 async fn create_document_set_version(
+    access: DocumentSetAccess,
     mut set_repository: DocumentSetsRepository,
-    claims: Claims,
+    permission_repository: PermissionRepository,
     Path(document_set_id): Path<Uuid>,
     ValidatedJson(data): ValidatedJson<CreateSetVersionWithParents>,
 ) -> Result<Json<SetVersion>, StatusCode> {
+    check_visible(
+        &permission_repository,
+        access.claims.user_id,
+        &data.document_version_ids,
+    )
+    .await?;
     let set_version = set_repository
         .create_document_set_version(
-            claims.user_id,
             document_set_id,
             data.set_version_name,
             data.document_version_ids,
@@ -87,16 +108,19 @@ async fn create_document_set_version(
             error!("{}", e);
             StatusCode::BAD_REQUEST
         })?;
+    let Some(set_version) = set_version else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
     Ok(Json(set_version))
 }
 
 async fn get_document_set_versions(
+    access: DocumentSetAccess,
     set_repository: DocumentSetsRepository,
-    claims: Claims,
     Path(document_set_id): Path<Uuid>,
 ) -> Result<Json<Vec<SetVersion>>, StatusCode> {
     let set_versions = set_repository
-        .get_document_set_versions(claims.user_id, document_set_id)
+        .get_document_set_versions(access.claims.user_id, document_set_id)
         .await
         .map_err(|e| {
             error!("{}", e);
@@ -106,14 +130,20 @@ async fn get_document_set_versions(
 }
 
 async fn add_to_document_set_version(
+    access: DocumentSetAccess,
     set_repository: DocumentSetsRepository,
-    claims: Claims,
+    permission_repository: PermissionRepository,
     Path((document_set_id, set_version_id)): Path<(Uuid, Uuid)>,
     Json(data): Json<UpdateSetVersion>,
 ) -> Result<StatusCode, StatusCode> {
+    check_visible(
+        &permission_repository,
+        access.claims.user_id,
+        &[(data.document_id, data.version_id)],
+    )
+    .await?;
     let added = set_repository
         .add_to_document_set_version(
-            claims.user_id,
             document_set_id,
             set_version_id,
             data.document_id,
@@ -131,17 +161,12 @@ async fn add_to_document_set_version(
 }
 
 async fn remove_from_document_set_version(
+    _: DocumentSetAccess,
     set_repository: DocumentSetsRepository,
-    claims: Claims,
     Path((document_set_id, set_version_id, document_id)): Path<(Uuid, Uuid, Uuid)>,
 ) -> Result<StatusCode, StatusCode> {
     let removed = set_repository
-        .remove_from_document_set_version(
-            claims.user_id,
-            document_set_id,
-            set_version_id,
-            document_id,
-        )
+        .remove_from_document_set_version(document_set_id, set_version_id, document_id)
         .await
         .map_err(|e| {
             error!("{}", e);
@@ -153,6 +178,90 @@ async fn remove_from_document_set_version(
     })
 }
 
+async fn get_members(
+    _: DocumentSetAccess,
+    set_repository: DocumentSetsRepository,
+    Path(document_set_id): Path<Uuid>,
+) -> Result<Json<Vec<DocumentSetMember>>, StatusCode> {
+    let members = set_repository
+        .get_document_set_members(document_set_id)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    Ok(Json(members))
+}
+
+async fn grant_set_role(
+    _: DocumentSetAccess,
+    set_repository: DocumentSetsRepository,
+    Path((document_set_id, user_id, role)): Path<(Uuid, Uuid, DocumentSetRole)>,
+) -> Res2 {
+    if role == DocumentSetRole::Owner {
+        return Res2::Msg((StatusCode::BAD_REQUEST, "Cannot grant this role"));
+    }
+
+    match set_repository
+        .grant_document_set_role(user_id, document_set_id, role)
+        .await
+    {
+        Ok(true) => Res2::NoMsg(StatusCode::OK),
+        Ok(false) => Res2::NoMsg(StatusCode::BAD_REQUEST),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when granting set role"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn revoke_set_role(
+    _: DocumentSetAccess,
+    set_repository: DocumentSetsRepository,
+    Path((document_set_id, user_id, role)): Path<(Uuid, Uuid, DocumentSetRole)>,
+) -> Res2 {
+    if role == DocumentSetRole::Owner {
+        return Res2::Msg((StatusCode::BAD_REQUEST, "Cannot revoke this role"));
+    }
+
+    match set_repository
+        .revoke_document_set_role(user_id, document_set_id, role)
+        .await
+    {
+        Ok(true) => Res2::NoMsg(StatusCode::OK),
+        Ok(false) => Res2::NoMsg(StatusCode::BAD_REQUEST),
+        Err(error) => {
+            error!(
+                { error = error.to_string() },
+                "Error when revoking set role"
+            );
+            Res2::NoMsg(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+/// Set versions may only reference document versions the caller can see
+async fn check_visible(
+    permission_repository: &PermissionRepository,
+    user_id: Uuid,
+    document_version_ids: &[(Uuid, Uuid)],
+) -> Result<(), StatusCode> {
+    let visible = permission_repository
+        .can_see_document_versions(user_id, document_version_ids)
+        .await
+        .map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    match visible {
+        true => Ok(()),
+        false => Err(StatusCode::FORBIDDEN),
+    }
+}
+
 pub fn document_sets_router<T>() -> Router<T>
 where
     AuthKeys: FromRef<T>,
@@ -165,6 +274,15 @@ where
         .route("/:document_set_id", post(create_document_set_version))
         .route("/:document_set_id", get(get_document_set_versions))
         .route("/:document_set_id/self", get(get_document_set))
+        .route("/:document_set_id/members", get(get_members))
+        .route(
+            "/:document_set_id/grant/:user_id/:role",
+            post(grant_set_role),
+        )
+        .route(
+            "/:document_set_id/revoke/:user_id/:role",
+            post(revoke_set_role),
+        )
         .route(
             "/:document_set_id/:set_version_id",
             post(add_to_document_set_version),
@@ -174,3 +292,69 @@ where
             delete(remove_from_document_set_version),
         )
 }
+
+#[cfg(test)]
+mod tests {
+    use axum::http::Method;
+
+    use crate::{
+        models::role::DocumentSetRole::{self, Editor, Owner, Viewer},
+        services::auth::policy::{allowed_set_roles, has_any_role, set_route_permission},
+    };
+
+    const ALL: &[DocumentSetRole] = &[Owner, Editor, Viewer];
+
+    #[test]
+    fn every_route_forbids_other_roles() {
+        let routes: &[(Method, &str, &[DocumentSetRole])] = &[
+            (Method::POST, "/api/document-sets", ALL),
+            (Method::GET, "/api/document-sets/sets", ALL),
+            (
+                Method::POST,
+                "/api/document-sets/:document_set_id",
+                &[Owner, Editor],
+            ),
+            (Method::GET, "/api/document-sets/:document_set_id", ALL),
+            (Method::GET, "/api/document-sets/:document_set_id/self", ALL),
+            (
+                Method::GET,
+                "/api/document-sets/:document_set_id/members",
+                ALL,
+            ),
+            (
+                Method::POST,
+                "/api/document-sets/:document_set_id/grant/:user_id/:role",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                "/api/document-sets/:document_set_id/revoke/:user_id/:role",
+                &[Owner],
+            ),
+            (
+                Method::POST,
+                "/api/document-sets/:document_set_id/:set_version_id",
+                &[Owner, Editor],
+            ),
+            (
+                Method::DELETE,
+                "/api/document-sets/:document_set_id/:set_version_id/:document_id",
+                &[Owner, Editor],
+            ),
+        ];
+        for (method, route, allowed) in routes {
+            let (resource, action) = set_route_permission(method, route).unwrap();
+            for role in ALL {
+                assert_eq!(
+                    has_any_role(&[*role], allowed_set_roles(resource, action)),
+                    allowed.contains(role),
+                    "{method} {route} as {role:?}"
+                );
+            }
+        }
+        assert_eq!(
+            set_route_permission(&Method::DELETE, "/api/document-sets/:document_set_id"),
+            None
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Read-only access to published versions through share links, without an account
+
+use axum::{
+    extract::{FromRef, Path},
+    http::StatusCode,
+    routing::get,
+    Json, Router,
+};
+use s3::Bucket;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::{attachment::File, share_link::SharedVersion},
+    services::{
+        auth::{
+            password::PasswordHashing, share_link::ShareLinkAccess, throttling::ThrottlingConfig,
+        },
+        content_storage::ContentStorage,
+        database::{
+            repositories::{files::FilesRepository, share_links::ShareLinksRepository},
+            DbPool,
+        },
+    },
+};
+
+async fn get_shared_version(
+    access: ShareLinkAccess,
+    share_links_repository: ShareLinksRepository,
+) -> Result<Json<SharedVersion>, StatusCode> {
+    match share_links_repository
+        .get_shared_version(access.document_id, access.version_id)
+        .await
+    {
+        Ok(Some(version)) => Ok(Json(version)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!("{}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_shared_files(
+    access: ShareLinkAccess,
+    share_links_repository: ShareLinksRepository,
+) -> Result<Json<Vec<File>>, StatusCode> {
+    match share_links_repository
+        .get_shared_files(access.document_id, access.version_id)
+        .await
+    {
+        Ok(files) => Ok(Json(files)),
+        Err(error) => {
+            error!("{}", error);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn get_shared_file_content(
+    access: ShareLinkAccess,
+    share_links_repository: ShareLinksRepository,
+    files_repository: FilesRepository,
+    Path((_, file_id)): Path<(String, Uuid)>,
+) -> Result<Vec<u8>, StatusCode> {
+    match share_links_repository
+        .is_file_shared(access.document_id, access.version_id, file_id)
+        .await
+    {
+        Ok(true) => {}
+        Ok(false) => return Err(StatusCode::NOT_FOUND),
+        Err(error) => {
+            error!("{}", error);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+    let content = files_repository.get_file(file_id).await.map_err(|e| {
+        error!("{}", e);
+        StatusCode::BAD_REQUEST
+    })?;
+    Ok(content)
+}
+
+pub fn shared_router<T>() -> Router<T>
+where
+    PasswordHashing: FromRef<T>,
+    DbPool: FromRef<T>,
+    Bucket: FromRef<T>,
+    ContentStorage: FromRef<T>,
+    ThrottlingConfig: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new()
+        .route("/:token", get(get_shared_version))
+        .route("/:token/files", get(get_shared_files))
+        .route(
+            "/:token/files/:file_id/content",
+            get(get_shared_file_content),
+        )
+}
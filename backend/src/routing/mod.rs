@@ -1,24 +1,42 @@
 pub mod api;
 mod healthcheck;
 mod static_files;
+mod well_known;
 
 use axum::{extract::FromRef, Router};
 use s3::Bucket;
 use tower_http::{cors::CorsLayer, trace::TraceLayer};
 
-use crate::services::{auth::auth_keys::AuthKeys, config::Config, database::DbPool};
+use crate::services::{
+    auth::{
+        auth_keys::AuthKeys, oidc::OidcClient, password::PasswordHashing, session::SessionConfig,
+        throttling::ThrottlingConfig,
+    },
+    config::Config,
+    content_storage::ContentStorage,
+    database::DbPool,
+};
 
-use self::{api::api_router, healthcheck::healthcheck_router, static_files::static_files_service};
+use self::{
+    api::api_router, healthcheck::healthcheck_router, static_files::static_files_service,
+    well_known::well_known_router,
+};
 
 pub fn main_route<T>(config: &Config) -> Router<T>
 where
     AuthKeys: FromRef<T>,
+    PasswordHashing: FromRef<T>,
+    SessionConfig: FromRef<T>,
+    ThrottlingConfig: FromRef<T>,
+    Option<OidcClient>: FromRef<T>,
     DbPool: FromRef<T>,
     Bucket: FromRef<T>,
+    ContentStorage: FromRef<T>,
     T: 'static + Send + Sync + Clone,
 {
     let mut router = Router::new()
         .merge(healthcheck_router())
+        .merge(well_known_router())
         .nest("/api", api_router())
         .fallback_service(static_files_service())
         .layer(TraceLayer::new_for_http());
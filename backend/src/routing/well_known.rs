@@ -0,0 +1,21 @@
+use axum::{
+    extract::{FromRef, State},
+    routing::get,
+    Json, Router,
+};
+use jsonwebtoken::jwk::JwkSet;
+
+use crate::services::auth::auth_keys::AuthKeys;
+
+pub fn well_known_router<T>() -> Router<T>
+where
+    AuthKeys: FromRef<T>,
+    T: 'static + Send + Sync + Clone,
+{
+    Router::new().route("/.well-known/jwks.json", get(jwks))
+}
+
+/// Public keys which verify access tokens
+async fn jwks(State(keys): State<AuthKeys>) -> Json<JwkSet> {
+    Json(keys.jwks().clone())
+}
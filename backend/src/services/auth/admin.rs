@@ -0,0 +1,38 @@
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use tracing::error;
+
+use crate::services::database::{repositories::permission::PermissionRepository, DbPool};
+
+use super::{auth_keys::AuthKeys, claims::Claims, error::AuthError};
+
+/// Claims of a user holding the admin role
+pub struct AdminClaims(pub Claims);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AdminClaims
+where
+    AuthKeys: FromRef<S>,
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state).await?;
+        let permission_repository = PermissionRepository::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::Internal)?;
+        match permission_repository.is_admin(claims.user_id).await {
+            Ok(true) => Ok(Self(claims)),
+            Ok(false) => Err(AuthError::Forbidden),
+            Err(e) => {
+                error!("{}", e);
+                Err(AuthError::Internal)
+            }
+        }
+    }
+}
@@ -1,26 +1,188 @@
-use std::{error::Error, fs::read, path::PathBuf};
+use std::{collections::HashMap, error::Error, fs::read, path::PathBuf, sync::Arc};
 
-use jsonwebtoken::{DecodingKey, EncodingKey};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{
+    jwk::{
+        AlgorithmParameters, CommonParameters, Jwk, JwkSet, PublicKeyUse, RSAKeyParameters,
+        RSAKeyType,
+    },
+    Algorithm, DecodingKey, EncodingKey,
+};
 use serde::Deserialize;
+use simple_asn1::{from_der, ASN1Block};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct AuthKeysConfig {
-    pub encoding: PathBuf,
+    /// Id of the key which signs new tokens
+    pub signing_key: String,
+    pub keys: Vec<AuthKeyConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthKeyConfig {
+    pub kid: String,
+    /// Private key, only required for the signing key
+    pub encoding: Option<PathBuf>,
     pub decoding: PathBuf,
+    /// Retired keys are neither accepted nor published
+    #[serde(default)]
+    pub retired: bool,
 }
 
 #[derive(Clone)]
 pub struct AuthKeys {
-    pub encoding: EncodingKey,
-    pub decoding: DecodingKey,
+    signing_kid: String,
+    encoding: EncodingKey,
+    decoding: Arc<HashMap<String, DecodingKey>>,
+    jwks: Arc<JwkSet>,
 }
 
 impl TryFrom<&AuthKeysConfig> for AuthKeys {
     type Error = Box<dyn Error>;
 
     fn try_from(value: &AuthKeysConfig) -> Result<Self, Self::Error> {
-        let encoding = EncodingKey::from_rsa_pem(&read(&value.encoding)?)?;
-        let decoding = DecodingKey::from_rsa_pem(&read(&value.decoding)?)?;
-        Ok(Self { encoding, decoding })
+        let mut encoding = None;
+        let mut decoding = HashMap::new();
+        let mut jwks = JwkSet { keys: vec![] };
+        for key in &value.keys {
+            if key.kid == value.signing_key {
+                if key.retired {
+                    Err(format!("Signing key {} is retired", key.kid))?
+                }
+                let path = key
+                    .encoding
+                    .as_ref()
+                    .ok_or("Signing key has no private key")?;
+                encoding = Some(EncodingKey::from_rsa_pem(&read(path)?)?);
+            }
+            if key.retired {
+                continue;
+            }
+            let pem = read(&key.decoding)?;
+            jwks.keys.push(rsa_jwk(&key.kid, &pem)?);
+            if decoding
+                .insert(key.kid.clone(), DecodingKey::from_rsa_pem(&pem)?)
+                .is_some()
+            {
+                Err(format!("Duplicate key id {}", key.kid))?
+            }
+        }
+        Ok(Self {
+            signing_kid: value.signing_key.clone(),
+            encoding: encoding.ok_or("Signing key not found")?,
+            decoding: Arc::new(decoding),
+            jwks: Arc::new(jwks),
+        })
+    }
+}
+
+impl AuthKeys {
+    /// Id and key which sign new tokens
+    pub fn signing_key(&self) -> (&str, &EncodingKey) {
+        (&self.signing_kid, &self.encoding)
+    }
+
+    /// Public key of a non-retired key
+    pub fn decoding_key(&self, kid: &str) -> Option<&DecodingKey> {
+        self.decoding.get(kid)
+    }
+
+    /// Public keys of all non-retired keys
+    pub fn decoding_keys(&self) -> impl Iterator<Item = &DecodingKey> {
+        self.decoding.values()
+    }
+
+    pub fn jwks(&self) -> &JwkSet {
+        &self.jwks
+    }
+}
+
+/// Builds a JWK from a PEM encoded RSA public key, either SPKI or PKCS#1
+fn rsa_jwk(kid: &str, pem: &[u8]) -> Result<Jwk, Box<dyn Error>> {
+    let pem = pem::parse(pem)?;
+    let mut blocks = from_der(&pem.contents)?;
+    if pem.tag == "PUBLIC KEY" {
+        let Some(ASN1Block::Sequence(_, spki)) = blocks.first() else {
+            Err("Invalid public key")?
+        };
+        let Some(ASN1Block::BitString(_, _, key)) = spki.get(1) else {
+            Err("Invalid public key")?
+        };
+        blocks = from_der(key)?;
+    }
+    let Some(ASN1Block::Sequence(_, numbers)) = blocks.first() else {
+        Err("Invalid RSA public key")?
+    };
+    let [ASN1Block::Integer(_, n), ASN1Block::Integer(_, e)] = numbers.as_slice() else {
+        Err("Invalid RSA public key")?
+    };
+    Ok(Jwk {
+        common: CommonParameters {
+            public_key_use: Some(PublicKeyUse::Signature),
+            algorithm: Some(Algorithm::RS256),
+            key_id: Some(kid.to_owned()),
+            ..Default::default()
+        },
+        algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+            key_type: RSAKeyType::RSA,
+            n: URL_SAFE_NO_PAD.encode(n.to_bytes_be().1),
+            e: URL_SAFE_NO_PAD.encode(e.to_bytes_be().1),
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use jsonwebtoken::{decode, encode, DecodingKey, Header, Validation};
+    use serde_json::{json, Value};
+
+    use super::{AuthKeyConfig, AuthKeys, AuthKeysConfig};
+
+    fn key(kid: &str, retired: bool) -> AuthKeyConfig {
+        AuthKeyConfig {
+            kid: kid.to_owned(),
+            encoding: Some(PathBuf::from("devkeys/priv.pem")),
+            decoding: PathBuf::from("devkeys/pub.pem"),
+            retired,
+        }
+    }
+
+    #[test]
+    fn jwks_verifies_signed_tokens() {
+        let config = AuthKeysConfig {
+            signing_key: "new".to_owned(),
+            keys: vec![key("old", true), key("new", false)],
+        };
+        let keys = AuthKeys::try_from(&config).unwrap();
+        assert!(keys.decoding_key("old").is_none());
+        assert_eq!(keys.jwks().keys.len(), 1);
+
+        let (kid, encoding) = keys.signing_key();
+        let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
+        header.kid = Some(kid.to_owned());
+        let token = encode(&header, &json!({ "exp": u64::MAX }), encoding).unwrap();
+        let jwk = keys.jwks().find("new").unwrap();
+        let decoding = DecodingKey::from_jwk(jwk).unwrap();
+        let validation = Validation::new(jsonwebtoken::Algorithm::RS256);
+        assert!(decode::<Value>(&token, &decoding, &validation).is_ok());
+    }
+
+    #[test]
+    fn signing_key_must_be_usable() {
+        let retired = AuthKeysConfig {
+            signing_key: "old".to_owned(),
+            keys: vec![key("old", true)],
+        };
+        assert!(AuthKeys::try_from(&retired).is_err());
+        let public_only = AuthKeysConfig {
+            signing_key: "next".to_owned(),
+            keys: vec![AuthKeyConfig {
+                encoding: None,
+                ..key("next", false)
+            }],
+        };
+        assert!(AuthKeys::try_from(&public_only).is_err());
     }
 }
@@ -1,15 +1,25 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
 use axum::{
     async_trait,
-    extract::{FromRef, FromRequestParts, TypedHeader},
+    extract::{FromRef, FromRequestParts, MatchedPath, TypedHeader},
     headers::{authorization::Bearer, Authorization},
     http::request::Parts,
     RequestPartsExt,
 };
-use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
 use tracing::error;
 use uuid::Uuid;
 
+use crate::{
+    models::personal_token::{AuthenticatedPersonalToken, TokenScope, PERSONAL_TOKEN_PREFIX},
+    services::database::{
+        repositories::{personal_tokens::PersonalTokensRepository, sessions::SessionsRepository},
+        DbPool,
+    },
+};
+
 use super::{auth_keys::AuthKeys, error::AuthError};
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -18,26 +28,105 @@ pub struct Claims {
     pub exp: u64,
     pub nbf: u64,
     pub iat: u64,
+    /// Session or personal token which issued the claims
+    pub jti: Uuid,
     pub user_id: Uuid,
     pub username: String,
 }
 
 impl Claims {
-    pub fn try_into_token(self, key: &EncodingKey) -> Result<String, AuthError> {
-        encode(&Header::new(Algorithm::RS256), &self, key).map_err(|e| {
+    pub fn new(user_id: Uuid, username: String, session_id: Uuid, lifetime: u64) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("Time went backwards")
+            .as_secs();
+        Self {
+            exp: now + lifetime,
+            nbf: now,
+            iat: now,
+            jti: session_id,
+            user_id,
+            username,
+        }
+    }
+
+    pub fn try_into_token(self, keys: &AuthKeys) -> Result<String, AuthError> {
+        let (kid, key) = keys.signing_key();
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_owned());
+        encode(&header, &self, key).map_err(|e| {
             error!("{}", e);
             AuthError::TokenCreation
         })
     }
 
-    pub fn try_from_token(token: &str, key: &DecodingKey) -> Result<Self, AuthError> {
+    /// Validates a token against the key named in its header.
+    /// Tokens issued before keys had ids are checked against every accepted key.
+    pub fn try_from_token(token: &str, keys: &AuthKeys) -> Result<Self, AuthError> {
         let mut validation = Validation::new(Algorithm::RS256);
         validation.validate_nbf = true;
-        let token_data = decode::<Claims>(token, key, &validation).map_err(|e| {
+        let header = decode_header(token).map_err(|e| {
             error!("{}", e);
             AuthError::InvalidToken
         })?;
-        Ok(token_data.claims)
+        let candidates: Vec<&DecodingKey> = match &header.kid {
+            Some(kid) => keys.decoding_key(kid).into_iter().collect(),
+            None => keys.decoding_keys().collect(),
+        };
+        if candidates.is_empty() {
+            error!("Token signed with an unknown or retired key");
+        }
+        for key in candidates {
+            match decode::<Claims>(token, key, &validation) {
+                Ok(token_data) => return Ok(token_data.claims),
+                Err(e) => error!("{}", e),
+            }
+        }
+        Err(AuthError::InvalidToken)
+    }
+
+    async fn try_from_personal_token<S>(
+        token: &str,
+        parts: &mut Parts,
+        state: &S,
+    ) -> Result<Self, AuthError>
+    where
+        DbPool: FromRef<S>,
+        S: Send + Sync,
+    {
+        let route = parts
+            .extensions
+            .get::<MatchedPath>()
+            .map(|path| path.as_str().to_owned())
+            .unwrap_or_default();
+        let Some(required_scope) = TokenScope::required_for(&parts.method, &route) else {
+            return Err(AuthError::InsufficientScope);
+        };
+
+        let personal_tokens_repository = PersonalTokensRepository::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::Internal)?;
+        let personal_token: AuthenticatedPersonalToken =
+            match personal_tokens_repository.authenticate(token).await {
+                Ok(Some(personal_token)) => personal_token,
+                Ok(None) => return Err(AuthError::InvalidToken),
+                Err(e) => {
+                    error!("{}", e);
+                    return Err(AuthError::Internal);
+                }
+            };
+        if !personal_token.scopes.contains(&required_scope) {
+            return Err(AuthError::InsufficientScope);
+        }
+
+        Ok(Self {
+            exp: personal_token.expires_at.timestamp() as u64,
+            nbf: personal_token.created_at.timestamp() as u64,
+            iat: personal_token.created_at.timestamp() as u64,
+            jti: personal_token.token_id,
+            user_id: personal_token.user_id,
+            username: personal_token.username,
+        })
     }
 }
 
@@ -45,6 +134,7 @@ impl Claims {
 impl<S> FromRequestParts<S> for Claims
 where
     AuthKeys: FromRef<S>,
+    DbPool: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = AuthError;
@@ -55,8 +145,24 @@ where
             .await
             .map_err(|_| AuthError::InvalidToken)?;
 
+        if bearer.token().starts_with(PERSONAL_TOKEN_PREFIX) {
+            return Claims::try_from_personal_token(bearer.token(), parts, state).await;
+        }
+
         let keys = AuthKeys::from_ref(state);
 
-        Claims::try_from_token(bearer.token(), &keys.decoding)
+        let claims = Claims::try_from_token(bearer.token(), &keys)?;
+
+        let sessions_repository = SessionsRepository::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::Internal)?;
+        match sessions_repository.is_session_active(claims.jti).await {
+            Ok(true) => Ok(claims),
+            Ok(false) => Err(AuthError::RevokedToken),
+            Err(e) => {
+                error!("{}", e);
+                Err(AuthError::Internal)
+            }
+        }
     }
 }
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -9,7 +9,15 @@ use serde_json::json;
 pub enum AuthError {
     TokenCreation,
     InvalidToken,
+    RevokedToken,
+    InsufficientScope,
     InvalidCredentials,
+    AccountDisabled,
+    AccountLocked,
+    /// Seconds until the next attempt is accepted
+    TooManyAttempts(i64),
+    Forbidden,
+    Internal,
 }
 
 impl IntoResponse for AuthError {
@@ -17,11 +25,26 @@ impl IntoResponse for AuthError {
         let (status, error_message) = match self {
             AuthError::TokenCreation => (StatusCode::INTERNAL_SERVER_ERROR, "Token creation error"),
             AuthError::InvalidToken => (StatusCode::UNAUTHORIZED, "Invalid token"),
+            AuthError::RevokedToken => (StatusCode::UNAUTHORIZED, "Revoked token"),
+            AuthError::InsufficientScope => (StatusCode::FORBIDDEN, "Insufficient token scope"),
             AuthError::InvalidCredentials => (StatusCode::UNAUTHORIZED, "Invalid credentials"),
+            AuthError::AccountDisabled => (StatusCode::FORBIDDEN, "Account disabled"),
+            AuthError::AccountLocked => (StatusCode::FORBIDDEN, "Account locked"),
+            AuthError::TooManyAttempts(_) => {
+                (StatusCode::TOO_MANY_REQUESTS, "Too many login attempts")
+            }
+            AuthError::Forbidden => (StatusCode::FORBIDDEN, "Forbidden"),
+            AuthError::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "Internal error"),
         };
         let body = Json(json!({
             "error": error_message,
         }));
-        (status, body).into_response()
+        let mut response = (status, body).into_response();
+        if let AuthError::TooManyAttempts(retry_after) = self {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(retry_after.max(1)));
+        }
+        response
     }
 }
@@ -1,3 +1,12 @@
+pub mod admin;
 pub mod auth_keys;
 pub mod claims;
 pub mod error;
+pub mod oidc;
+pub mod password;
+pub mod policy;
+pub mod secrets;
+pub mod session;
+pub mod share_link;
+pub mod throttling;
+pub mod totp;
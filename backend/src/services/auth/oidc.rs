@@ -0,0 +1,305 @@
+use std::error::Error;
+
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use url::Url;
+
+use crate::services::config::Config;
+
+use super::secrets::generate_secret;
+
+type OidcError = Box<dyn Error + Send + Sync>;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcConfig {
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub redirect_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Claims of a validated ID token
+#[derive(Debug, Deserialize)]
+pub struct IdTokenClaims {
+    pub iss: String,
+    pub sub: String,
+    pub nonce: Option<String>,
+    pub preferred_username: Option<String>,
+    pub email: Option<String>,
+}
+
+/// Everything needed to start an authorization-code + PKCE login
+pub struct AuthorizationRequest {
+    pub url: String,
+    pub state: String,
+    pub nonce: String,
+    pub code_verifier: String,
+}
+
+#[derive(Clone)]
+pub struct OidcClient {
+    config: OidcConfig,
+    metadata: ProviderMetadata,
+    http: reqwest::Client,
+}
+
+fn code_challenge(code_verifier: &str) -> String {
+    URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()))
+}
+
+impl OidcClient {
+    /// Fetches provider metadata from the issuer's discovery document
+    pub async fn discover(config: &OidcConfig) -> Result<Self, OidcError> {
+        let http = reqwest::Client::new();
+        let issuer = config.issuer.trim_end_matches('/');
+        let metadata: ProviderMetadata = http
+            .get(format!("{}/.well-known/openid-configuration", issuer))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        if metadata.issuer.trim_end_matches('/') != issuer {
+            Err("Discovered issuer does not match configured issuer")?
+        }
+        Ok(Self {
+            config: config.clone(),
+            metadata,
+            http,
+        })
+    }
+
+    pub fn issuer(&self) -> &str {
+        &self.metadata.issuer
+    }
+
+    pub fn authorization_request(&self) -> Result<AuthorizationRequest, OidcError> {
+        let state = generate_secret();
+        let nonce = generate_secret();
+        let code_verifier = generate_secret();
+        let url = Url::parse_with_params(
+            &self.metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", &self.config.client_id),
+                ("redirect_uri", &self.config.redirect_url),
+                ("scope", "openid profile email"),
+                ("state", &state),
+                ("nonce", &nonce),
+                ("code_challenge", &code_challenge(&code_verifier)),
+                ("code_challenge_method", "S256"),
+            ],
+        )?;
+        Ok(AuthorizationRequest {
+            url: url.into(),
+            state,
+            nonce,
+            code_verifier,
+        })
+    }
+
+    /// Redeems an authorization code and validates the returned ID token
+    pub async fn exchange_code(
+        &self,
+        code: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let tokens: TokenResponse = self
+            .http
+            .post(&self.metadata.token_endpoint)
+            .basic_auth(&self.config.client_id, Some(&self.config.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", &self.config.redirect_url),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let header = decode_header(&tokens.id_token)?;
+        let jwks: JwkSet = self
+            .http
+            .get(&self.metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let jwk = match &header.kid {
+            Some(kid) => jwks.find(kid),
+            None => jwks.keys.first(),
+        }
+        .ok_or("Signing key not found")?;
+        let key = DecodingKey::from_jwk(jwk)?;
+
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_issuer(&[&self.metadata.issuer]);
+        validation.set_audience(&[&self.config.client_id]);
+        let claims = decode::<IdTokenClaims>(&tokens.id_token, &key, &validation)?.claims;
+        if claims.nonce.as_deref() != Some(nonce) {
+            Err("Nonce mismatch")?
+        }
+        Ok(claims)
+    }
+}
+
+pub async fn setup_oidc(config: &Config) -> Option<OidcClient> {
+    let config = config.oidc.as_ref()?;
+    let client = OidcClient::discover(config)
+        .await
+        .expect("Could not reach OIDC provider");
+    Some(client)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::TcpListener,
+        sync::{Arc, Mutex},
+        time::{SystemTime, UNIX_EPOCH},
+    };
+
+    use axum::{
+        extract::State,
+        routing::{get, post},
+        Form, Json, Router,
+    };
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use openssl::{bn::BigNumRef, rsa::Rsa};
+    use serde_json::{json, Value};
+
+    use super::{code_challenge, OidcClient, OidcConfig};
+
+    #[derive(Clone, Default)]
+    struct MockProvider {
+        issuer: String,
+        nonce: Arc<Mutex<String>>,
+        code_challenge: Arc<Mutex<String>>,
+    }
+
+    async fn discovery(State(provider): State<MockProvider>) -> Json<Value> {
+        Json(json!({
+            "issuer": provider.issuer,
+            "authorization_endpoint": format!("{}/authorize", provider.issuer),
+            "token_endpoint": format!("{}/token", provider.issuer),
+            "jwks_uri": format!("{}/jwks", provider.issuer),
+        }))
+    }
+
+    async fn jwks() -> Json<Value> {
+        let pem = std::fs::read("devkeys/pub.pem").unwrap();
+        let key = Rsa::public_key_from_pem(&pem).unwrap();
+        let encode = |n: &BigNumRef| URL_SAFE_NO_PAD.encode(n.to_vec());
+        Json(json!({
+            "keys": [{
+                "kty": "RSA",
+                "kid": "mock",
+                "alg": "RS256",
+                "use": "sig",
+                "n": encode(key.n()),
+                "e": encode(key.e()),
+            }]
+        }))
+    }
+
+    async fn token(
+        State(provider): State<MockProvider>,
+        Form(form): Form<Vec<(String, String)>>,
+    ) -> Json<Value> {
+        let verifier = form.iter().find(|(k, _)| k == "code_verifier").unwrap();
+        assert_eq!(
+            code_challenge(&verifier.1),
+            *provider.code_challenge.lock().unwrap()
+        );
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs();
+        let claims = json!({
+            "iss": provider.issuer,
+            "sub": "mock-subject",
+            "aud": "apsi",
+            "exp": now + 60,
+            "iat": now,
+            "nonce": *provider.nonce.lock().unwrap(),
+            "preferred_username": "mock",
+        });
+        let mut header = Header::new(jsonwebtoken::Algorithm::RS256);
+        header.kid = Some("mock".to_owned());
+        let key = EncodingKey::from_rsa_pem(&std::fs::read("devkeys/priv.pem").unwrap()).unwrap();
+        let id_token = encode(&header, &claims, &key).unwrap();
+        Json(json!({ "access_token": "mock", "token_type": "Bearer", "id_token": id_token }))
+    }
+
+    fn spawn_provider() -> MockProvider {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let provider = MockProvider {
+            issuer: format!("http://{}", listener.local_addr().unwrap()),
+            ..Default::default()
+        };
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(discovery))
+            .route("/jwks", get(jwks))
+            .route("/token", post(token))
+            .with_state(provider.clone());
+        tokio::spawn(
+            axum::Server::from_tcp(listener)
+                .unwrap()
+                .serve(router.into_make_service()),
+        );
+        provider
+    }
+
+    #[tokio::test]
+    async fn authorization_code_flow() {
+        let provider = spawn_provider();
+        let config = OidcConfig {
+            issuer: provider.issuer.clone(),
+            client_id: "apsi".to_owned(),
+            client_secret: "secret".to_owned(),
+            redirect_url: "http://localhost:3000/api/auth/oidc/callback".to_owned(),
+        };
+        let client = OidcClient::discover(&config).await.unwrap();
+
+        let request = client.authorization_request().unwrap();
+        assert!(request
+            .url
+            .starts_with(&format!("{}/authorize?", provider.issuer)));
+        assert!(request.url.contains("code_challenge_method=S256"));
+        *provider.nonce.lock().unwrap() = request.nonce.clone();
+        *provider.code_challenge.lock().unwrap() = code_challenge(&request.code_verifier);
+
+        let claims = client
+            .exchange_code("code", &request.code_verifier, &request.nonce)
+            .await
+            .unwrap();
+        assert_eq!(claims.sub, "mock-subject");
+        assert_eq!(claims.preferred_username.as_deref(), Some("mock"));
+
+        let replayed = client
+            .exchange_code("code", &request.code_verifier, "other nonce")
+            .await;
+        assert!(replayed.is_err());
+    }
+}
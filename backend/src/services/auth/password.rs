@@ -0,0 +1,217 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use uuid::Uuid;
+
+const DUMMY_SALT: &[u8] = b"apsi-dummy-salt!";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PasswordHashingConfig {
+    /// Memory cost in KiB
+    pub memory_cost: u32,
+    /// Number of iterations
+    pub time_cost: u32,
+    /// Degree of parallelism
+    pub parallelism: u32,
+}
+
+/// Outcome of checking a password against a stored hash
+#[derive(Debug, PartialEq, Eq)]
+pub enum PasswordCheck {
+    Mismatch,
+    Match,
+    /// Password is correct, but the stored hash should be replaced
+    /// because it uses the legacy scheme or outdated parameters.
+    MatchOutdated,
+}
+
+#[derive(Clone)]
+pub struct PasswordHashing {
+    argon2: Argon2<'static>,
+}
+
+impl TryFrom<&PasswordHashingConfig> for PasswordHashing {
+    type Error = argon2::Error;
+
+    fn try_from(value: &PasswordHashingConfig) -> Result<Self, Self::Error> {
+        let params = Params::new(value.memory_cost, value.time_cost, value.parallelism, None)?;
+        let argon2 = Argon2::new(Algorithm::Argon2id, Version::V0x13, params);
+        Ok(Self { argon2 })
+    }
+}
+
+impl PasswordHashing {
+    /// Runs the hashing on the blocking pool, it takes too long for the async executor
+    async fn blocking<T, F>(&self, f: F) -> T
+    where
+        F: FnOnce(&Self) -> T + Send + 'static,
+        T: Send + 'static,
+    {
+        let hashing = self.clone();
+        match tokio::task::spawn_blocking(move || f(&hashing)).await {
+            Ok(value) => value,
+            // Blocking tasks are never cancelled, the hashing itself panicked
+            Err(error) => std::panic::resume_unwind(error.into_panic()),
+        }
+    }
+
+    /// Hashes a password into a PHC string
+    pub async fn hash(&self, password: &str) -> Result<String, argon2::password_hash::Error> {
+        let password = password.to_owned();
+        self.blocking(move |hashing| hashing.hash_now(&password))
+            .await
+    }
+
+    /// Checks a password against either a PHC string or a legacy SHA-256 hash
+    pub async fn check(
+        &self,
+        user_id: &Uuid,
+        salt: Option<&Uuid>,
+        password: &str,
+        password_hash: &str,
+    ) -> Result<PasswordCheck, argon2::password_hash::Error> {
+        let user_id = *user_id;
+        let salt = salt.copied();
+        let password = password.to_owned();
+        let password_hash = password_hash.to_owned();
+        self.blocking(move |hashing| {
+            hashing.check_now(&user_id, salt.as_ref(), &password, &password_hash)
+        })
+        .await
+    }
+
+    /// Spends as much time as checking a real hash, so unknown users and
+    /// accounts without a password can't be told apart by response time
+    pub async fn check_nothing(&self, password: &str) {
+        let password = password.to_owned();
+        self.blocking(move |hashing| hashing.check_nothing_now(&password))
+            .await
+    }
+
+    fn hash_now(&self, password: &str) -> Result<String, argon2::password_hash::Error> {
+        let salt = SaltString::generate(&mut OsRng);
+        let hash = self.argon2.hash_password(password.as_bytes(), &salt)?;
+        Ok(hash.to_string())
+    }
+
+    fn check_now(
+        &self,
+        user_id: &Uuid,
+        salt: Option<&Uuid>,
+        password: &str,
+        password_hash: &str,
+    ) -> Result<PasswordCheck, argon2::password_hash::Error> {
+        let Ok(parsed) = PasswordHash::new(password_hash) else {
+            let Some(salt) = salt else {
+                // Accounts without a password, e.g. provisioned by OIDC
+                self.check_nothing_now(password);
+                return Ok(PasswordCheck::Mismatch);
+            };
+            let legacy_hash = legacy_hash_password(user_id, password, salt);
+            return Ok(
+                if bool::from(legacy_hash.as_bytes().ct_eq(password_hash.as_bytes())) {
+                    PasswordCheck::MatchOutdated
+                } else {
+                    PasswordCheck::Mismatch
+                },
+            );
+        };
+        match self.argon2.verify_password(password.as_bytes(), &parsed) {
+            Ok(()) if self.is_outdated(&parsed) => Ok(PasswordCheck::MatchOutdated),
+            Ok(()) => Ok(PasswordCheck::Match),
+            Err(argon2::password_hash::Error::Password) => Ok(PasswordCheck::Mismatch),
+            Err(error) => Err(error),
+        }
+    }
+
+    fn check_nothing_now(&self, password: &str) {
+        let mut output = [0u8; 32];
+        let _ = self
+            .argon2
+            .hash_password_into(password.as_bytes(), DUMMY_SALT, &mut output);
+    }
+
+    fn is_outdated(&self, hash: &PasswordHash) -> bool {
+        if hash.algorithm != Algorithm::Argon2id.ident() {
+            return true;
+        }
+        match Params::try_from(hash) {
+            Ok(params) => {
+                let current = self.argon2.params();
+                params.m_cost() != current.m_cost()
+                    || params.t_cost() != current.t_cost()
+                    || params.p_cost() != current.p_cost()
+            }
+            Err(_) => true,
+        }
+    }
+}
+
+/// Single round SHA-256 scheme used before Argon2id, kept to upgrade old hashes
+fn legacy_hash_password(user_id: &Uuid, password: &str, salt: &Uuid) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(user_id.as_bytes());
+    hasher.update("|");
+    hasher.update(password.as_bytes());
+    hasher.update("|");
+    hasher.update(salt.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::{legacy_hash_password, PasswordCheck, PasswordHashing, PasswordHashingConfig};
+
+    fn password_hashing(memory_cost: u32) -> PasswordHashing {
+        let config = PasswordHashingConfig {
+            memory_cost,
+            time_cost: 1,
+            parallelism: 1,
+        };
+        PasswordHashing::try_from(&config).unwrap()
+    }
+
+    #[test]
+    fn legacy_hashing() {
+        let user_id = Uuid::parse_str("81721217-8f19-4c3b-8b25-a2af68875018").unwrap();
+        let password = "admin";
+        let salt = Uuid::parse_str("8ce53b43-a248-4abf-a76e-d79d21a820cf").unwrap();
+        let hashed = legacy_hash_password(&user_id, password, &salt);
+        assert_eq!(
+            hashed,
+            "34a5da8d86e760f40fdad550e0f3713952b96a63f78b618b56beb5df3049f4e7"
+        );
+    }
+
+    #[tokio::test]
+    async fn legacy_hash_is_upgraded() {
+        let hashing = password_hashing(1024);
+        let user_id = Uuid::new_v4();
+        let salt = Uuid::new_v4();
+        let hashed = legacy_hash_password(&user_id, "admin", &salt);
+        let check = |password| hashing.check(&user_id, Some(&salt), password, &hashed);
+        assert_eq!(check("admin").await.unwrap(), PasswordCheck::MatchOutdated);
+        assert_eq!(check("nimda").await.unwrap(), PasswordCheck::Mismatch);
+    }
+
+    #[tokio::test]
+    async fn argon2_hashing() {
+        let hashing = password_hashing(1024);
+        let user_id = Uuid::new_v4();
+        let hashed = hashing.hash("admin").await.unwrap();
+        assert!(hashed.starts_with("$argon2id$"));
+        let check = |password| hashing.check(&user_id, None, password, &hashed);
+        assert_eq!(check("admin").await.unwrap(), PasswordCheck::Match);
+        assert_eq!(check("nimda").await.unwrap(), PasswordCheck::Mismatch);
+        let check = password_hashing(2048)
+            .check(&user_id, None, "admin", &hashed)
+            .await;
+        assert_eq!(check.unwrap(), PasswordCheck::MatchOutdated);
+    }
+}
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts, MatchedPath, Path},
+    http::{request::Parts, Method},
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        role::{DocumentSetRole, DocumentVersionRole},
+        version_state::DocumentVersionState,
+    },
+    services::database::{
+        repositories::{document_sets::DocumentSetsRepository, permission::PermissionRepository},
+        DbPool,
+    },
+};
+
+use super::{auth_keys::AuthKeys, claims::Claims, error::AuthError};
+
+use DocumentVersionRole::{Editor, Owner, Reviewer, Viewer};
+
+const MEMBERS: &[DocumentVersionRole] = &[Owner, Viewer, Editor, Reviewer];
+const WRITERS: &[DocumentVersionRole] = &[Owner, Editor];
+const OWNERS: &[DocumentVersionRole] = &[Owner];
+
+/// What a document route acts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    /// Open to any user, e.g. all documents or ownership transfers offered to them
+    Documents,
+    /// A document, roles are combined from all of its versions
+    Document,
+    Version,
+    VersionState,
+    Comments,
+    Attachments,
+    Members,
+    /// Members holding roles on every version of a document
+    DocumentMembers,
+    /// Members added to every new version of a document
+    DefaultMembers,
+    /// Requests for roles on a version, made by users without them
+    AccessRequests,
+    /// Anonymous links to a published version
+    ShareLinks,
+    /// Sensitivity label of a document and its history
+    Label,
+    /// Members of several versions at once, owning each of them is checked when applied
+    BulkMembers,
+}
+
+impl Resource {
+    /// Whether roles are combined from all versions of the document
+    fn is_document_scoped(self) -> bool {
+        matches!(
+            self,
+            Self::Document
+                | Self::DocumentMembers
+                | Self::DefaultMembers
+                | Self::Label
+                | Self::BulkMembers
+        )
+    }
+
+    /// Whether changing it takes a role on every version of the document,
+    /// owning a single version, e.g. a freshly created child, isn't enough
+    pub fn needs_every_version(self, action: Action) -> bool {
+        matches!(
+            self,
+            Self::DocumentMembers | Self::DefaultMembers | Self::Label
+        ) && action != Action::Read
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Read,
+    Create,
+    Update,
+    Delete,
+}
+
+/// Roles allowed to perform an action on a resource, anything not listed is denied
+pub fn allowed_roles(resource: Resource, action: Action) -> &'static [DocumentVersionRole] {
+    use Action::*;
+    use Resource::*;
+    match (resource, action) {
+        (Documents, _) => MEMBERS,
+        (Document, Read) => MEMBERS,
+        (Document, Create) => WRITERS,
+        (Version, Read) => MEMBERS,
+        (Version, Update) => WRITERS,
+        (VersionState, Update) => &[Owner, Editor, Reviewer],
+        (Comments, Read | Create) => MEMBERS,
+        (Attachments, Read) => MEMBERS,
+        (Attachments, Create | Delete) => WRITERS,
+        (Members, Read) => MEMBERS,
+        (Members, Create | Delete) => OWNERS,
+        (DocumentMembers | DefaultMembers, Read) => MEMBERS,
+        (DocumentMembers | DefaultMembers, Create | Delete) => OWNERS,
+        (AccessRequests, Read | Update) => OWNERS,
+        (ShareLinks, Read | Create | Delete) => OWNERS,
+        (Label, Read) => MEMBERS,
+        (Label, Update) => OWNERS,
+        (BulkMembers, Create | Delete) => OWNERS,
+        _ => &[],
+    }
+}
+
+/// Roles allowed to move a version into a state
+pub fn state_change_roles(new_state: DocumentVersionState) -> &'static [DocumentVersionRole] {
+    match new_state {
+        DocumentVersionState::InProgress => &[Owner, Editor, Reviewer],
+        DocumentVersionState::ReadyForReview => WRITERS,
+        DocumentVersionState::Reviewed => &[Reviewer],
+        DocumentVersionState::Published => OWNERS,
+    }
+}
+
+pub fn has_any_role<R: PartialEq>(roles: &[R], allowed: &[R]) -> bool {
+    roles.iter().any(|role| allowed.contains(role))
+}
+
+/// Resource and action of a document route, `None` for routes without a policy
+pub fn route_permission(method: &Method, route: &str) -> Option<(Resource, Action)> {
+    use Action::*;
+    use Resource::*;
+    let route = route.strip_prefix("/api/documents")?;
+    let segments: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+    let permission = match (method.as_str(), segments.as_slice()) {
+        ("POST", []) => (Documents, Create),
+        ("GET", ["documents" | "transfers" | "access-requests"]) => (Documents, Read),
+        ("POST", [":document_id", ":version_id", "access-requests"]) => (Documents, Create),
+        ("GET", [":document_id", ":version_id", "access-requests"]) => (AccessRequests, Read),
+        ("POST", [":document_id", ":version_id", "access-requests", _, "approve" | "deny"]) => {
+            (AccessRequests, Update)
+        }
+        ("POST", ["transfers", ":transfer_id", "accept" | "decline"]) => (Documents, Update),
+        (
+            "GET",
+            [":document_id"]
+            | [":document_id", "versions" | "diff" | "heads" | "common-ancestors" | "graph"],
+        ) => (Document, Read),
+        ("POST", [":document_id"]) => (Document, Create),
+        ("POST", [":document_id", "merge-preview"]) => (Document, Read),
+        ("GET", [":document_id", "members"]) => (DocumentMembers, Read),
+        ("POST", [":document_id", "grant", ":user_id", ":role"]) => (DocumentMembers, Create),
+        ("POST", [":document_id", "revoke", ":user_id", ":role"]) => (DocumentMembers, Delete),
+        ("PUT", [":document_id", "label"]) => (Label, Update),
+        ("POST", [":document_id", "bulk-grant"]) => (BulkMembers, Create),
+        ("POST", [":document_id", "bulk-revoke"]) => (BulkMembers, Delete),
+        ("GET", [":document_id", "label-history"]) => (Label, Read),
+        ("GET", [":document_id", "default-members"]) => (DefaultMembers, Read),
+        ("PUT", [":document_id", "default-members", ":user_id", ":role"]) => {
+            (DefaultMembers, Create)
+        }
+        ("DELETE", [":document_id", "default-members", ":user_id", ":role"]) => {
+            (DefaultMembers, Delete)
+        }
+        ("GET", [":document_id", ":version_id"]) => (Version, Read),
+        ("PATCH", [":document_id", ":version_id"]) => (Version, Update),
+        (
+            "GET",
+            [":document_id", ":version_id", "member" | "am-owner" | "ancestors" | "descendants"],
+        ) => (Version, Read),
+        ("POST", [":document_id", ":version_id", "change-state"]) => (VersionState, Update),
+        ("GET", [":document_id", ":version_id", "comments"]) => (Comments, Read),
+        ("POST", [":document_id", ":version_id", "comment"]) => (Comments, Create),
+        ("GET", [":document_id", ":version_id", "files", ..]) => (Attachments, Read),
+        ("PATCH", [":document_id", ":version_id", "files"]) => (Attachments, Create),
+        ("DELETE", [":document_id", ":version_id", "files", ":file_id"]) => (Attachments, Delete),
+        ("GET", [":document_id", ":version_id", "members" | "groups"]) => (Members, Read),
+        ("POST", [":document_id", ":version_id", "grant" | "grant-group", _, ":role"]) => {
+            (Members, Create)
+        }
+        ("POST", [":document_id", ":version_id", "revoke" | "revoke-group", _, ":role"]) => {
+            (Members, Delete)
+        }
+        ("POST", [":document_id", ":version_id", "transfer", ":user_id"]) => (Members, Create),
+        ("GET", [":document_id", ":version_id", "share-links"]) => (ShareLinks, Read),
+        ("POST", [":document_id", ":version_id", "share-links"]) => (ShareLinks, Create),
+        ("DELETE", [":document_id", ":version_id", "share-links", ":link_id"]) => {
+            (ShareLinks, Delete)
+        }
+        _ => return None,
+    };
+    Some(permission)
+}
+
+/// Claims of a user allowed to call the matched document route,
+/// along with their roles on the document or version it targets
+pub struct DocumentAccess {
+    pub claims: Claims,
+    pub roles: Vec<DocumentVersionRole>,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for DocumentAccess
+where
+    AuthKeys: FromRef<S>,
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state).await?;
+        let route = matched_route(parts);
+        let Some((resource, action)) = route_permission(&parts.method, &route) else {
+            error!({ route = route }, "Route has no policy");
+            return Err(AuthError::Forbidden);
+        };
+        if resource == Resource::Documents {
+            return Ok(Self {
+                claims,
+                roles: vec![],
+            });
+        }
+
+        let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::Forbidden)?;
+        let id = |name: &str| {
+            params
+                .get(name)
+                .and_then(|value| Uuid::parse_str(value).ok())
+                .ok_or(AuthError::Forbidden)
+        };
+        let document_id = id("document_id")?;
+        let permission_repository = PermissionRepository::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::Internal)?;
+        let roles = if resource.needs_every_version(action) {
+            permission_repository
+                .get_document_wide_roles(claims.user_id, document_id)
+                .await
+        } else if resource.is_document_scoped() {
+            permission_repository
+                .get_document_roles(claims.user_id, document_id)
+                .await
+        } else {
+            permission_repository
+                .get_document_version_roles(claims.user_id, document_id, id("version_id")?)
+                .await
+        };
+        let roles = roles.map_err(|e| {
+            error!("{}", e);
+            AuthError::Internal
+        })?;
+        if !has_any_role(&roles, allowed_roles(resource, action)) {
+            return Err(AuthError::Forbidden);
+        }
+        Ok(Self { claims, roles })
+    }
+}
+
+fn matched_route(parts: &Parts) -> String {
+    parts
+        .extensions
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_owned())
+        .unwrap_or_default()
+}
+
+/// What a document set route acts on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetResource {
+    /// All document sets, open to any user
+    Sets,
+    Set,
+    SetVersion,
+    Members,
+}
+
+/// Roles allowed to perform an action on a document set, anything not listed is denied
+pub fn allowed_set_roles(resource: SetResource, action: Action) -> &'static [DocumentSetRole] {
+    use Action::*;
+    use DocumentSetRole::*;
+    use SetResource::*;
+    match (resource, action) {
+        (Sets, Read | Create) => &[Owner, Editor, Viewer],
+        (Set | SetVersion | Members, Read) => &[Owner, Editor, Viewer],
+        (SetVersion, Create | Update) => &[Owner, Editor],
+        (Members, Create | Delete) => &[Owner],
+        _ => &[],
+    }
+}
+
+/// Resource and action of a document set route, `None` for routes without a policy
+pub fn set_route_permission(method: &Method, route: &str) -> Option<(SetResource, Action)> {
+    use Action::*;
+    use SetResource::*;
+    let route = route.strip_prefix("/api/document-sets")?;
+    let segments: Vec<&str> = route.split('/').filter(|s| !s.is_empty()).collect();
+    let permission = match (method.as_str(), segments.as_slice()) {
+        ("POST", []) => (Sets, Create),
+        ("GET", ["sets"]) => (Sets, Read),
+        ("GET", [":document_set_id", "self"]) => (Set, Read),
+        ("GET", [":document_set_id"]) => (SetVersion, Read),
+        ("POST", [":document_set_id"]) => (SetVersion, Create),
+        ("POST", [":document_set_id", ":set_version_id"]) => (SetVersion, Update),
+        ("DELETE", [":document_set_id", ":set_version_id", ":document_id"]) => (SetVersion, Update),
+        ("GET", [":document_set_id", "members"]) => (Members, Read),
+        ("POST", [":document_set_id", "grant", ":user_id", ":role"]) => (Members, Create),
+        ("POST", [":document_set_id", "revoke", ":user_id", ":role"]) => (Members, Delete),
+        _ => return None,
+    };
+    Some(permission)
+}
+
+/// Claims of a user allowed to call the matched document set route
+pub struct DocumentSetAccess {
+    pub claims: Claims,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for DocumentSetAccess
+where
+    AuthKeys: FromRef<S>,
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let claims = Claims::from_request_parts(parts, state).await?;
+        let route = matched_route(parts);
+        let Some((resource, action)) = set_route_permission(&parts.method, &route) else {
+            error!({ route = route }, "Route has no policy");
+            return Err(AuthError::Forbidden);
+        };
+        if resource == SetResource::Sets {
+            return Ok(Self { claims });
+        }
+
+        let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::Forbidden)?;
+        let document_set_id = params
+            .get("document_set_id")
+            .and_then(|value| Uuid::parse_str(value).ok())
+            .ok_or(AuthError::Forbidden)?;
+        let set_repository = DocumentSetsRepository::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::Internal)?;
+        let roles = set_repository
+            .get_document_set_roles(claims.user_id, document_set_id)
+            .await
+            .map_err(|e| {
+                error!("{}", e);
+                AuthError::Internal
+            })?;
+        if !has_any_role(&roles, allowed_set_roles(resource, action)) {
+            return Err(AuthError::Forbidden);
+        }
+        Ok(Self { claims })
+    }
+}
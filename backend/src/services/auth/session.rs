@@ -0,0 +1,9 @@
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SessionConfig {
+    /// Lifetime of access tokens in seconds
+    pub access_token_ttl: u64,
+    /// Lifetime of a session since its last refresh in seconds
+    pub refresh_token_ttl: u64,
+}
@@ -0,0 +1,126 @@
+use std::{collections::HashMap, net::SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRef, FromRequestParts, Path},
+    http::request::Parts,
+};
+use chrono::Utc;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    models::share_link::SHARE_PASSWORD_HEADER,
+    services::{
+        content_storage::ContentStorage,
+        database::{
+            repositories::{
+                login_throttling::LoginThrottlingRepository, share_links::ShareLinksRepository,
+            },
+            DbPool,
+        },
+    },
+};
+
+use super::{
+    error::AuthError,
+    password::PasswordHashing,
+    throttling::{ThrottleScope, ThrottlingConfig},
+};
+
+/// Version exposed by the share link in the `token` path parameter,
+/// used instead of `Claims` by the anonymous share routes
+pub struct ShareLinkAccess {
+    pub document_id: Uuid,
+    pub version_id: Uuid,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ShareLinkAccess
+where
+    PasswordHashing: FromRef<S>,
+    ContentStorage: FromRef<S>,
+    DbPool: FromRef<S>,
+    ThrottlingConfig: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = AuthError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::InvalidToken)?;
+        let token = params.get("token").ok_or(AuthError::InvalidToken)?;
+        let share_links_repository = ShareLinksRepository::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::Internal)?;
+        let link = share_links_repository
+            .resolve(token)
+            .await
+            .map_err(|e| {
+                error!("{}", e);
+                AuthError::Internal
+            })?
+            .ok_or(AuthError::InvalidToken)?;
+        if link.password_hash.is_none() {
+            return Ok(Self {
+                document_id: link.document_id,
+                version_id: link.version_id,
+            });
+        }
+
+        // Password guesses are throttled like logins, per link and per address
+        let ConnectInfo(address) = ConnectInfo::<SocketAddr>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::Internal)?;
+        let address = address.ip().to_string();
+        let link_id = link.link_id.to_string();
+        let mut throttling_repository = LoginThrottlingRepository::from_request_parts(parts, state)
+            .await
+            .map_err(|_| AuthError::Internal)?;
+        let blocked_until = throttling_repository
+            .blocked_until(ThrottleScope::ShareLink, &link_id, &address)
+            .await
+            .map_err(|e| {
+                error!("{}", e);
+                AuthError::Internal
+            })?;
+        if let Some(blocked_until) = blocked_until {
+            info!({ address = address }, "Throttled share link attempt");
+            let retry_after = (blocked_until - Utc::now()).num_seconds();
+            return Err(AuthError::TooManyAttempts(retry_after));
+        }
+
+        let password = parts
+            .headers
+            .get(SHARE_PASSWORD_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default();
+        let password_matches = share_links_repository
+            .check_password(&link, password)
+            .await
+            .map_err(|e| {
+                error!("{}", e);
+                AuthError::Internal
+            })?;
+        let recorded = if password_matches {
+            throttling_repository
+                .record_success(ThrottleScope::ShareLink, &link_id)
+                .await
+        } else {
+            throttling_repository
+                .record_failure(ThrottleScope::ShareLink, &link_id, &address)
+                .await
+        };
+        if let Err(e) = recorded {
+            error!("{}", e);
+        }
+        if !password_matches {
+            return Err(AuthError::InvalidCredentials);
+        }
+        Ok(Self {
+            document_id: link.document_id,
+            version_id: link.version_id,
+        })
+    }
+}
@@ -0,0 +1,110 @@
+use chrono::Duration;
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThrottlingConfig {
+    pub username: BackoffLimits,
+    /// Passwords of share links
+    pub share_link: BackoffLimits,
+    pub address: ThrottlingLimits,
+    /// Delay after the first failure past the free attempts in seconds, doubled with every further one
+    pub base_delay: i64,
+    /// Upper bound of the delay in seconds
+    pub max_delay: i64,
+    /// Failures older than this many seconds are forgotten
+    pub failure_window: i64,
+    /// How long a lockout lasts in seconds
+    pub lockout_duration: i64,
+}
+
+/// Usernames and share links are only ever delayed, anybody can fail attempts for them
+/// so a lockout would let them lock out everybody else
+#[derive(Debug, Clone, Deserialize)]
+pub struct BackoffLimits {
+    /// Failures allowed before delays kick in
+    pub free_attempts: i32,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ThrottlingLimits {
+    /// Failures allowed before delays kick in
+    pub free_attempts: i32,
+    /// Failures which lock the address out
+    pub lockout_attempts: i32,
+}
+
+/// What attempts are tracked by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i16)]
+pub enum ThrottleScope {
+    Username = 0,
+    Address = 1,
+    ShareLink = 2,
+}
+
+/// Outcome of a failed attempt
+#[derive(Debug, PartialEq, Eq)]
+pub enum Throttle {
+    None,
+    Delay(Duration),
+    Lockout(Duration),
+}
+
+impl ThrottlingConfig {
+    /// Exponential backoff after the free attempts, addresses are locked out once their limit is reached
+    pub fn throttle(&self, scope: ThrottleScope, failures: i32) -> Throttle {
+        let free_attempts = match scope {
+            ThrottleScope::Username => self.username.free_attempts,
+            ThrottleScope::ShareLink => self.share_link.free_attempts,
+            ThrottleScope::Address => {
+                if failures >= self.address.lockout_attempts {
+                    return Throttle::Lockout(Duration::seconds(self.lockout_duration));
+                }
+                self.address.free_attempts
+            }
+        };
+        if failures <= free_attempts {
+            return Throttle::None;
+        }
+        let exponent = (failures - free_attempts - 1).min(32) as u32;
+        let delay = self
+            .base_delay
+            .saturating_mul(2i64.saturating_pow(exponent))
+            .min(self.max_delay);
+        Throttle::Delay(Duration::seconds(delay))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::{BackoffLimits, Throttle, ThrottleScope, ThrottlingConfig, ThrottlingLimits};
+
+    #[test]
+    fn backoff_grows_until_lockout() {
+        let config = ThrottlingConfig {
+            username: BackoffLimits { free_attempts: 3 },
+            share_link: BackoffLimits { free_attempts: 5 },
+            address: ThrottlingLimits {
+                free_attempts: 20,
+                lockout_attempts: 100,
+            },
+            base_delay: 1,
+            max_delay: 30,
+            failure_window: 3600,
+            lockout_duration: 900,
+        };
+        let throttle = |failures| config.throttle(ThrottleScope::Username, failures);
+        assert_eq!(throttle(3), Throttle::None);
+        assert_eq!(throttle(4), Throttle::Delay(Duration::seconds(1)));
+        assert_eq!(throttle(5), Throttle::Delay(Duration::seconds(2)));
+        assert_eq!(throttle(8), Throttle::Delay(Duration::seconds(16)));
+        assert_eq!(throttle(9), Throttle::Delay(Duration::seconds(30)));
+        assert_eq!(throttle(100), Throttle::Delay(Duration::seconds(30)));
+        let throttle = |failures| config.throttle(ThrottleScope::Address, failures);
+        assert_eq!(throttle(10), Throttle::None);
+        assert_eq!(throttle(21), Throttle::Delay(Duration::seconds(1)));
+        assert_eq!(throttle(100), Throttle::Lockout(Duration::seconds(900)));
+    }
+}
@@ -0,0 +1,116 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use rand::{rngs::OsRng, RngCore};
+use totp_rs::{Algorithm, Secret, TOTP};
+use url::Url;
+
+/// Name shown next to the account in authenticator apps
+const TOTP_ISSUER: &str = "apsi";
+const TOTP_DIGITS: usize = 6;
+const TOTP_STEP: u64 = 30;
+/// Number of steps before and after the current one which are still accepted
+const TOTP_SKEW: u64 = 1;
+pub const RECOVERY_CODE_COUNT: usize = 10;
+
+/// Generates a random 160 bit secret, base32 encoded
+pub fn generate_totp_secret() -> String {
+    let mut bytes = [0u8; 20];
+    OsRng.fill_bytes(&mut bytes);
+    Secret::Raw(bytes.to_vec()).to_encoded().to_string()
+}
+
+/// Builds the URI authenticator apps import, usually presented as a QR code
+pub fn otpauth_uri(secret: &str, username: &str) -> String {
+    let mut url = Url::parse("otpauth://totp/").unwrap();
+    url.set_path(&format!("{}:{}", TOTP_ISSUER, username));
+    url.query_pairs_mut()
+        .append_pair("secret", secret)
+        .append_pair("issuer", TOTP_ISSUER)
+        .append_pair("algorithm", "SHA1")
+        .append_pair("digits", &TOTP_DIGITS.to_string())
+        .append_pair("period", &TOTP_STEP.to_string());
+    url.into()
+}
+
+/// Generates a single use recovery code, e.g. `3f9a1-c27e0`
+pub fn generate_recovery_code() -> String {
+    let mut bytes = [0u8; 5];
+    OsRng.fill_bytes(&mut bytes);
+    let code = hex::encode(bytes);
+    format!("{}-{}", &code[..5], &code[5..])
+}
+
+/// Tells TOTP codes apart from recovery codes
+pub fn is_totp_code(code: &str) -> bool {
+    code.len() == TOTP_DIGITS && code.chars().all(|c| c.is_ascii_digit())
+}
+
+fn current_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+/// Checks a code at the given time, returning the matched time step.
+/// Steps up to `last_used_step` are rejected so a code can't be replayed.
+fn check_code_at(secret: &str, code: &str, last_used_step: Option<i64>, time: u64) -> Option<i64> {
+    let secret = Secret::Encoded(secret.to_owned()).to_bytes().ok()?;
+    let totp = TOTP::new(Algorithm::SHA1, TOTP_DIGITS, 0, TOTP_STEP, secret).ok()?;
+    let current_step = time / TOTP_STEP;
+    (current_step.saturating_sub(TOTP_SKEW)..=current_step + TOTP_SKEW)
+        .map(|step| step as i64)
+        .filter(|step| Some(*step) > last_used_step)
+        .find(|step| totp.check(code, *step as u64 * TOTP_STEP))
+}
+
+/// Checks a code against the current time, see [`check_code_at`]
+pub fn check_code(secret: &str, code: &str, last_used_step: Option<i64>) -> Option<i64> {
+    check_code_at(secret, code, last_used_step, current_time())
+}
+
+#[cfg(test)]
+mod tests {
+    use totp_rs::{Algorithm, Secret, TOTP};
+
+    use super::{
+        check_code_at, generate_recovery_code, generate_totp_secret, is_totp_code, otpauth_uri,
+    };
+
+    fn code_at(secret: &str, time: u64) -> String {
+        let secret = Secret::Encoded(secret.to_owned()).to_bytes().unwrap();
+        TOTP::new(Algorithm::SHA1, 6, 0, 30, secret)
+            .unwrap()
+            .generate(time)
+    }
+
+    #[test]
+    fn codes_are_accepted_once() {
+        let secret = generate_totp_secret();
+        let time = 1_700_000_000;
+        let step = (time / 30) as i64;
+        let code = code_at(&secret, time);
+        assert_eq!(check_code_at(&secret, &code, None, time), Some(step));
+        assert_eq!(check_code_at(&secret, &code, None, time + 30), Some(step));
+        assert_eq!(check_code_at(&secret, &code, None, time + 90), None);
+        assert_eq!(check_code_at(&secret, &code, Some(step), time), None);
+        assert_eq!(check_code_at(&secret, "000000", None, time), None);
+    }
+
+    #[test]
+    fn otpauth_uri_format() {
+        let uri = otpauth_uri("JBSWY3DPEHPK3PXP", "john doe");
+        assert_eq!(
+            uri,
+            "otpauth://totp/apsi:john%20doe?secret=JBSWY3DPEHPK3PXP&issuer=apsi&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn recovery_codes_are_not_totp_codes() {
+        let code = generate_recovery_code();
+        assert_eq!(code.len(), 11);
+        assert!(!is_totp_code(&code));
+        assert!(is_totp_code("123456"));
+    }
+}
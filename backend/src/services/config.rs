@@ -5,13 +5,28 @@ use serde::Deserialize;
 
 use crate::services::database::config::PostgresConfig;
 
-use super::{auth::auth_keys::AuthKeysConfig, s3storage::S3Config, tracing::TracingConfig};
+use super::{
+    auth::{
+        auth_keys::AuthKeysConfig, oidc::OidcConfig, password::PasswordHashingConfig,
+        session::SessionConfig, throttling::ThrottlingConfig,
+    },
+    content_storage::ContentStorageConfig,
+    grant_expiry::GrantExpiryConfig,
+    s3storage::S3Config,
+    tracing::TracingConfig,
+};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub webserver: WebserverConfig,
     pub tracing: TracingConfig,
     pub auth_keys: AuthKeysConfig,
+    pub password_hashing: PasswordHashingConfig,
+    pub session: SessionConfig,
+    pub throttling: ThrottlingConfig,
+    pub grant_expiry: GrantExpiryConfig,
+    pub content_storage: ContentStorageConfig,
+    pub oidc: Option<OidcConfig>,
     pub database: PostgresConfig,
     pub s3storage: S3Config,
 }
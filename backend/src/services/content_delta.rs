@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use similar::{capture_diff_slices_deadline, Algorithm, DiffOp};
+
+/// Longest time spent looking for a small delta, a larger one is used afterwards
+pub const DIFF_DEADLINE: Duration = Duration::from_secs(1);
+
+/// Step rebuilding content out of the content of its base version
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum DeltaOp {
+    /// Start and length of a byte range of the base content
+    Copy(usize, usize),
+    Insert(String),
+}
+
+/// Line based delta turning `base` into `content`
+pub fn diff(base: &str, content: &str) -> Vec<DeltaOp> {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mut offsets = Vec::with_capacity(base_lines.len() + 1);
+    offsets.push(0);
+    for line in &base_lines {
+        offsets.push(offsets[offsets.len() - 1] + line.len());
+    }
+    let deadline = Instant::now() + DIFF_DEADLINE;
+    let mut delta = Vec::new();
+    for op in capture_diff_slices_deadline(Algorithm::Myers, &base_lines, &lines, Some(deadline)) {
+        match op {
+            DiffOp::Equal { old_index, len, .. } => {
+                let start = offsets[old_index];
+                let len = offsets[old_index + len] - start;
+                match delta.last_mut() {
+                    Some(DeltaOp::Copy(copy_start, copy_len))
+                        if *copy_start + *copy_len == start =>
+                    {
+                        *copy_len += len
+                    }
+                    _ => delta.push(DeltaOp::Copy(start, len)),
+                }
+            }
+            DiffOp::Delete { .. } => {}
+            DiffOp::Insert {
+                new_index, new_len, ..
+            }
+            | DiffOp::Replace {
+                new_index, new_len, ..
+            } => {
+                let text = lines[new_index..new_index + new_len].concat();
+                match delta.last_mut() {
+                    Some(DeltaOp::Insert(inserted)) => inserted.push_str(&text),
+                    _ => delta.push(DeltaOp::Insert(text)),
+                }
+            }
+        }
+    }
+    delta
+}
+
+/// `None` when the delta does not fit the base
+pub fn apply(base: &str, delta: &[DeltaOp]) -> Option<String> {
+    let mut content = String::new();
+    for op in delta {
+        match op {
+            DeltaOp::Copy(start, len) => {
+                content.push_str(base.get(*start..start.checked_add(*len)?)?)
+            }
+            DeltaOp::Insert(text) => content.push_str(text),
+        }
+    }
+    Some(content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply, diff, DeltaOp};
+
+    #[test]
+    fn delta_rebuilds_content() {
+        let base = "first\nsecond\nthird\nfourth";
+        let content = "first\nchanged\nthird\nfourth\nfifth\n";
+        let delta = diff(base, content);
+        assert_eq!(
+            delta,
+            vec![
+                DeltaOp::Copy(0, 6),
+                DeltaOp::Insert("changed\n".to_owned()),
+                DeltaOp::Copy(13, 6),
+                DeltaOp::Insert("fourth\nfifth\n".to_owned()),
+            ]
+        );
+        assert_eq!(apply(base, &delta).unwrap(), content);
+        assert_eq!(apply("", &diff("", content)).unwrap(), content);
+        assert_eq!(apply(base, &diff(base, "")).unwrap(), "");
+    }
+
+    #[test]
+    fn delta_out_of_base_is_rejected() {
+        assert_eq!(apply("short", &[DeltaOp::Copy(2, 10)]), None);
+        assert_eq!(apply("żółw", &[DeltaOp::Copy(1, 2)]), None);
+    }
+}
@@ -0,0 +1,51 @@
+use std::time::Duration;
+
+use tracing::{error, info};
+
+use super::{
+    content_storage::{ContentStorage, ContentStorageConfig},
+    database::{repositories::documents::DocumentsRepository, DbPool},
+};
+
+/// Versions looked at on every tick
+const REPACK_BATCH_SIZE: i64 = 100;
+
+/// Periodically stores versions saved in full, such as those predating deltas
+/// or those whose delta base changed, as deltas against one of their parents
+pub fn spawn_content_repack(
+    database: DbPool,
+    content_storage: ContentStorage,
+    config: &ContentStorageConfig,
+) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.repack_interval));
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            let database = match database.get_owned().await {
+                Ok(database) => database,
+                Err(error) => {
+                    error!(
+                        { error = error.to_string() },
+                        "Error when repacking content"
+                    );
+                    continue;
+                }
+            };
+            let mut documents_repository =
+                DocumentsRepository::new(database, content_storage.clone());
+            match documents_repository
+                .repack_versions(REPACK_BATCH_SIZE)
+                .await
+            {
+                Ok(0) => {}
+                Ok(repacked) => info!({ repacked = repacked }, "Stored versions as deltas"),
+                Err(error) => {
+                    error!(
+                        { error = error.to_string() },
+                        "Error when repacking content"
+                    )
+                }
+            }
+        }
+    });
+}
@@ -0,0 +1,413 @@
+use std::{
+    error::Error,
+    fmt::Display,
+    io::{self, Read, Write},
+    string::FromUtf8Error,
+};
+
+use flate2::{read::DeflateDecoder, write::DeflateEncoder, Compression};
+use s3::{error::S3Error, Bucket};
+use serde::Deserialize;
+use tokio_postgres::{GenericClient, Row};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::models::RowError;
+
+use super::content_delta::{apply, diff, DeltaOp};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ContentStorageConfig {
+    /// Content of at least this many bytes is deflated, never if missing
+    pub compression_threshold: Option<usize>,
+    /// Content still taking at least this many bytes is moved into the bucket
+    pub external_threshold: usize,
+    /// Most deltas applied on top of a full copy, the next version is stored in full
+    pub snapshot_interval: i16,
+    /// How often versions are repacked into deltas in seconds
+    pub repack_interval: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(i16)]
+pub enum ContentEncoding {
+    Plain = 0,
+    Deflate = 1,
+}
+
+impl TryFrom<i16> for ContentEncoding {
+    type Error = i16;
+
+    fn try_from(value: i16) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Plain),
+            1 => Ok(Self::Deflate),
+            _ => Err(value),
+        }
+    }
+}
+
+impl From<ContentEncoding> for i16 {
+    fn from(value: ContentEncoding) -> Self {
+        value as i16
+    }
+}
+
+/// Values for `content`, `content_data`, `content_object`, `content_encoding`,
+/// `content_length`, `delta_base_id` and `delta_depth`
+pub type ContentColumns<'a> = (
+    Option<&'a str>,
+    Option<&'a [u8]>,
+    Option<&'a str>,
+    i16,
+    Option<i64>,
+    Option<Uuid>,
+    i16,
+);
+
+/// Bytes kept in `document_versions`, the full content or a delta
+#[derive(Debug)]
+pub enum StoredContent {
+    Inline(String),
+    Deflated(Vec<u8>),
+    External {
+        object_key: String,
+        encoding: ContentEncoding,
+    },
+}
+
+impl StoredContent {
+    /// Reads `content`, `content_data`, `content_object` and `content_encoding` starting at `index`
+    pub fn from_row(row: &Row, index: usize) -> Result<Self, ContentError> {
+        let content: Option<String> = row.try_get(index)?;
+        let data: Option<Vec<u8>> = row.try_get(index + 1)?;
+        let object_key: Option<String> = row.try_get(index + 2)?;
+        let encoding: i16 = row.try_get(index + 3)?;
+        let encoding = ContentEncoding::try_from(encoding)
+            .map_err(RowError::unknown_value("content_encoding"))?;
+
+        Ok(match (content, data, object_key) {
+            (Some(content), _, _) => Self::Inline(content),
+            (None, Some(data), _) => Self::Deflated(data),
+            (None, None, Some(object_key)) => Self::External {
+                object_key,
+                encoding,
+            },
+            (None, None, None) => return Err(ContentError::Missing),
+        })
+    }
+
+    pub fn object_key(&self) -> Option<&str> {
+        match self {
+            Self::External { object_key, .. } => Some(object_key),
+            _ => None,
+        }
+    }
+}
+
+/// Content of a version stored either in full or as a delta against its delta base
+#[derive(Debug)]
+pub struct VersionContent {
+    pub stored: StoredContent,
+    /// Size of the full content in bytes
+    pub length: i64,
+    pub delta_base: Option<Uuid>,
+    pub delta_depth: i16,
+}
+
+impl VersionContent {
+    pub fn to_sql(&self) -> ContentColumns<'_> {
+        // The length is only left out when `content` holds the full content
+        let length = match (&self.stored, self.delta_base) {
+            (StoredContent::Inline(_), None) => None,
+            _ => Some(self.length),
+        };
+        let (content, data, object_key, encoding) = match &self.stored {
+            StoredContent::Inline(content) => {
+                (Some(content.as_str()), None, None, ContentEncoding::Plain)
+            }
+            StoredContent::Deflated(data) => {
+                (None, Some(data.as_slice()), None, ContentEncoding::Deflate)
+            }
+            StoredContent::External {
+                object_key,
+                encoding,
+            } => (None, None, Some(object_key.as_str()), *encoding),
+        };
+        (
+            content,
+            data,
+            object_key,
+            i16::from(encoding),
+            length,
+            self.delta_base,
+            self.delta_depth,
+        )
+    }
+}
+
+#[derive(Debug)]
+pub enum ContentError {
+    Pg(tokio_postgres::Error),
+    Row(RowError),
+    Compression(io::Error),
+    Storage(S3Error),
+    Encoding(FromUtf8Error),
+    InvalidDelta,
+    Missing,
+}
+
+impl Display for ContentError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pg(error) => Display::fmt(error, f),
+            Self::Row(error) => Display::fmt(error, f),
+            Self::Compression(error) => Display::fmt(error, f),
+            Self::Storage(error) => Display::fmt(error, f),
+            Self::Encoding(error) => Display::fmt(error, f),
+            Self::InvalidDelta => f.write_str("Delta does not fit its base content"),
+            Self::Missing => f.write_str("Version content not found"),
+        }
+    }
+}
+
+impl Error for ContentError {}
+
+impl From<tokio_postgres::Error> for ContentError {
+    fn from(value: tokio_postgres::Error) -> Self {
+        Self::Pg(value)
+    }
+}
+
+impl From<RowError> for ContentError {
+    fn from(value: RowError) -> Self {
+        Self::Row(value)
+    }
+}
+
+impl From<io::Error> for ContentError {
+    fn from(value: io::Error) -> Self {
+        Self::Compression(value)
+    }
+}
+
+impl From<S3Error> for ContentError {
+    fn from(value: S3Error) -> Self {
+        Self::Storage(value)
+    }
+}
+
+impl From<FromUtf8Error> for ContentError {
+    fn from(value: FromUtf8Error) -> Self {
+        Self::Encoding(value)
+    }
+}
+
+impl From<serde_json::Error> for ContentError {
+    fn from(_: serde_json::Error) -> Self {
+        Self::InvalidDelta
+    }
+}
+
+/// Compresses and offloads version content into the bucket shared with file attachments
+#[derive(Clone)]
+pub struct ContentStorage {
+    config: ContentStorageConfig,
+    s3storage: Bucket,
+}
+
+impl ContentStorage {
+    pub fn new(config: &ContentStorageConfig, s3storage: Bucket) -> Self {
+        Self {
+            config: config.clone(),
+            s3storage,
+        }
+    }
+
+    fn deflate(content: &[u8]) -> Result<Vec<u8>, io::Error> {
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(content)?;
+        encoder.finish()
+    }
+
+    fn decode(encoding: ContentEncoding, data: Vec<u8>) -> Result<String, ContentError> {
+        let data = match encoding {
+            ContentEncoding::Plain => data,
+            ContentEncoding::Deflate => {
+                let mut inflated = Vec::new();
+                DeflateDecoder::new(data.as_slice()).read_to_end(&mut inflated)?;
+                inflated
+            }
+        };
+        Ok(String::from_utf8(data)?)
+    }
+
+    /// Every upload gets a fresh key, so a failed update never overwrites content still in use
+    pub async fn store(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+        content: &str,
+    ) -> Result<StoredContent, ContentError> {
+        let deflated = match self.config.compression_threshold {
+            Some(threshold) if content.len() >= threshold => {
+                Some(Self::deflate(content.as_bytes())?)
+            }
+            _ => None,
+        };
+        let (encoding, data) = match &deflated {
+            Some(data) => (ContentEncoding::Deflate, data.as_slice()),
+            None => (ContentEncoding::Plain, content.as_bytes()),
+        };
+        if data.len() < self.config.external_threshold {
+            return Ok(match deflated {
+                Some(data) => StoredContent::Deflated(data),
+                None => StoredContent::Inline(content.to_owned()),
+            });
+        }
+        let object_key = format!("content/{}/{}/{}", document_id, version_id, Uuid::new_v4());
+        self.s3storage.put_object(&object_key, data).await?;
+        Ok(StoredContent::External {
+            object_key,
+            encoding,
+        })
+    }
+
+    pub async fn load(&self, stored: StoredContent) -> Result<String, ContentError> {
+        match stored {
+            StoredContent::Inline(content) => Ok(content),
+            StoredContent::Deflated(data) => Self::decode(ContentEncoding::Deflate, data),
+            StoredContent::External {
+                object_key,
+                encoding,
+            } => {
+                let response = self.s3storage.get_object(&object_key).await?;
+                Self::decode(encoding, response.to_vec())
+            }
+        }
+    }
+
+    /// Content of a version along with its delta depth, applying the deltas
+    /// down from the nearest full copy
+    async fn load_chain<C>(
+        &self,
+        db: &C,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<(String, i16), ContentError>
+    where
+        C: GenericClient,
+    {
+        let chain = db
+            .query(
+                "
+                WITH RECURSIVE chain AS (
+                    SELECT v.version_id, v.delta_base_id, 0 AS position
+                    FROM document_versions v
+                    WHERE v.document_id = $1
+                    AND v.version_id = $2
+                    UNION ALL
+                    SELECT b.version_id, b.delta_base_id, c.position + 1
+                    FROM chain c
+                    JOIN document_versions b ON b.document_id = $1 AND b.version_id = c.delta_base_id
+                )
+                SELECT v.content, v.content_data, v.content_object, v.content_encoding, v.delta_depth
+                FROM chain c
+                JOIN document_versions v ON v.document_id = $1 AND v.version_id = c.version_id
+                ORDER BY c.position DESC
+                ",
+                &[&document_id, &version_id],
+            )
+            .await?;
+        let mut chain = chain.into_iter();
+        let Some(snapshot) = chain.next() else {
+            return Err(ContentError::Missing);
+        };
+        let mut depth: i16 = snapshot.try_get(4)?;
+        let mut content = self.load(StoredContent::from_row(&snapshot, 0)?).await?;
+        for delta in chain {
+            depth = delta.try_get(4)?;
+            let delta = self.load(StoredContent::from_row(&delta, 0)?).await?;
+            let delta: Vec<DeltaOp> = serde_json::from_str(&delta)?;
+            content = apply(&content, &delta).ok_or(ContentError::InvalidDelta)?;
+        }
+        Ok((content, depth))
+    }
+
+    pub async fn load_version<C>(
+        &self,
+        db: &C,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<String, ContentError>
+    where
+        C: GenericClient,
+    {
+        let (content, _) = self.load_chain(db, document_id, version_id).await?;
+        Ok(content)
+    }
+
+    /// Stores a delta against the content of `base` if it takes less than half the space
+    /// and the chain stays within `snapshot_interval`, the full content otherwise
+    pub async fn store_version<C>(
+        &self,
+        db: &C,
+        document_id: Uuid,
+        version_id: Uuid,
+        content: &str,
+        base: Option<Uuid>,
+    ) -> Result<VersionContent, ContentError>
+    where
+        C: GenericClient,
+    {
+        let length = content.len() as i64;
+        if let Some(base) = base {
+            let (base_content, base_depth) = self.load_chain(db, document_id, base).await?;
+            if base_depth < self.config.snapshot_interval {
+                let delta = serde_json::to_string(&diff(&base_content, content))?;
+                if delta.len() < content.len() / 2 {
+                    let stored = self.store(document_id, version_id, &delta).await?;
+                    return Ok(VersionContent {
+                        stored,
+                        length,
+                        delta_base: Some(base),
+                        delta_depth: base_depth + 1,
+                    });
+                }
+            }
+        }
+        let stored = self.store(document_id, version_id, content).await?;
+        Ok(VersionContent {
+            stored,
+            length,
+            delta_base: None,
+            delta_depth: 0,
+        })
+    }
+
+    /// Failing to remove an object only leaves it orphaned in the bucket
+    pub async fn discard(&self, object_key: &str) {
+        if let Err(error) = self.s3storage.delete_object(object_key).await {
+            error!(
+                { error = error.to_string(), object_key = object_key },
+                "Error when discarding stored content"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContentEncoding, ContentStorage};
+
+    #[test]
+    fn deflated_content_round_trips() {
+        let content = "line of a specification\n".repeat(1000);
+        let data = ContentStorage::deflate(content.as_bytes()).unwrap();
+        assert!(data.len() < content.len());
+        let decoded = ContentStorage::decode(ContentEncoding::Deflate, data).unwrap();
+        assert_eq!(decoded, content);
+        let plain = ContentStorage::decode(ContentEncoding::Plain, b"plain".to_vec()).unwrap();
+        assert_eq!(plain, "plain");
+    }
+}
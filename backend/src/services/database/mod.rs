@@ -1,5 +1,7 @@
 pub mod config;
 pub mod repositories;
+#[cfg(test)]
+pub mod testing;
 
 use std::{error::Error, path::Path};
 
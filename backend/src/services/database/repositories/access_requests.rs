@@ -0,0 +1,337 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use tokio_postgres::GenericClient;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        access_request::{AccessRequest, AccessRequestStatus},
+        role::{DocumentVersionRole, GrantValidity},
+    },
+    services::database::{DbConn, DbPool},
+};
+
+use super::documents::UniqueError;
+
+pub struct AccessRequestsRepository {
+    database: DbConn,
+}
+
+impl AccessRequestsRepository {
+    pub async fn create_request(
+        &self,
+        user_id: Uuid,
+        document_id: Uuid,
+        version_id: Uuid,
+        role: DocumentVersionRole,
+        message: Option<String>,
+    ) -> Result<AccessRequest, UniqueError> {
+        let row = self
+            .database
+            .query_one(
+                "
+                WITH r AS (
+                    INSERT INTO access_requests (request_id, document_id, version_id, user_id, role_id, message)
+                    VALUES ($1, $2, $3, $4, $5, $6)
+                    RETURNING *
+                )
+                SELECT u.user_id, u.username, u.display_name,
+                    r.request_id, r.document_id, r.version_id, r.role_id, r.message, r.status_id, r.created_at, r.decided_at
+                FROM r
+                JOIN users u ON u.user_id = r.user_id
+                ",
+                &[&Uuid::new_v4(), &document_id, &version_id, &user_id, &i16::from(role), &message],
+            )
+            .await
+            .map_err(|error| {
+                if let Some(db_error) = error.as_db_error() {
+                    if let Some(constraint) = db_error.constraint() {
+                        if constraint == "idx__access_requests__pending" {
+                            return UniqueError::UniqueValueViolation;
+                        }
+                    }
+                }
+                error.into()
+            })?;
+        let request = AccessRequest::try_from(row)?;
+        Ok(request)
+    }
+
+    /// Pending requests for a version
+    pub async fn get_version_requests(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<Vec<AccessRequest>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT u.user_id, u.username, u.display_name,
+                    r.request_id, r.document_id, r.version_id, r.role_id, r.message, r.status_id, r.created_at, r.decided_at
+                FROM access_requests r
+                JOIN users u ON u.user_id = r.user_id
+                WHERE r.document_id = $1
+                AND r.version_id = $2
+                AND r.status_id = $3
+                ORDER BY r.created_at
+                ",
+                &[&document_id, &version_id, &i16::from(AccessRequestStatus::Pending)],
+            )
+            .await?;
+        let requests = rows
+            .into_iter()
+            .map(AccessRequest::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(requests)
+    }
+
+    /// Requests made by the user
+    pub async fn get_user_requests(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<AccessRequest>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT u.user_id, u.username, u.display_name,
+                    r.request_id, r.document_id, r.version_id, r.role_id, r.message, r.status_id, r.created_at, r.decided_at
+                FROM access_requests r
+                JOIN users u ON u.user_id = r.user_id
+                WHERE r.user_id = $1
+                ORDER BY r.created_at DESC
+                ",
+                &[&user_id],
+            )
+            .await?;
+        let requests = rows
+            .into_iter()
+            .map(AccessRequest::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(requests)
+    }
+
+    /// Approves or denies a pending request, returns `None` if there is no such request
+    pub async fn decide_request(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+        request_id: Uuid,
+        decided_by: Uuid,
+        status: AccessRequestStatus,
+    ) -> Result<Option<AccessRequest>, Box<dyn Error + Send + Sync>> {
+        Self::decide(
+            &*self.database,
+            document_id,
+            version_id,
+            request_id,
+            decided_by,
+            status,
+        )
+        .await
+    }
+
+    /// Approves a pending request and grants its role in the same transaction,
+    /// returns `None` if there is no such request, otherwise whether the role was new
+    pub async fn approve_request(
+        &mut self,
+        document_id: Uuid,
+        version_id: Uuid,
+        request_id: Uuid,
+        decided_by: Uuid,
+        validity: &GrantValidity,
+    ) -> Result<Option<(AccessRequest, bool)>, Box<dyn Error + Send + Sync>> {
+        let transaction = self.database.transaction().await?;
+        let request = Self::decide(
+            &transaction,
+            document_id,
+            version_id,
+            request_id,
+            decided_by,
+            AccessRequestStatus::Approved,
+        )
+        .await?;
+        let Some(request) = request else {
+            return Ok(None);
+        };
+        // Approving a role the user already holds replaces its validity window, like granting it does
+        let granted: bool = transaction
+            .query_one(
+                "
+                INSERT INTO user_document_version_roles (user_id, document_id, version_id, role_id, valid_from, valid_until)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (user_id, document_id, version_id, role_id) DO UPDATE
+                SET valid_from = EXCLUDED.valid_from, valid_until = EXCLUDED.valid_until
+                RETURNING xmax = 0
+                ",
+                &[
+                    &request.user.user.user_id,
+                    &document_id,
+                    &version_id,
+                    &i16::from(request.role),
+                    &validity.valid_from,
+                    &validity.valid_until,
+                ],
+            )
+            .await?
+            .try_get(0)?;
+        transaction.commit().await?;
+        Ok(Some((request, granted)))
+    }
+
+    async fn decide<C>(
+        db: &C,
+        document_id: Uuid,
+        version_id: Uuid,
+        request_id: Uuid,
+        decided_by: Uuid,
+        status: AccessRequestStatus,
+    ) -> Result<Option<AccessRequest>, Box<dyn Error + Send + Sync>>
+    where
+        C: GenericClient,
+    {
+        let row = db
+            .query_opt(
+                "
+                WITH r AS (
+                    UPDATE access_requests
+                    SET status_id = $5, decided_at = now(), decided_by = $4
+                    WHERE document_id = $1
+                    AND version_id = $2
+                    AND request_id = $3
+                    AND status_id = $6
+                    RETURNING *
+                )
+                SELECT u.user_id, u.username, u.display_name,
+                    r.request_id, r.document_id, r.version_id, r.role_id, r.message, r.status_id, r.created_at, r.decided_at
+                FROM r
+                JOIN users u ON u.user_id = r.user_id
+                ",
+                &[
+                    &document_id,
+                    &version_id,
+                    &request_id,
+                    &decided_by,
+                    &i16::from(status),
+                    &i16::from(AccessRequestStatus::Pending),
+                ],
+            )
+            .await?;
+        let request = row.map(AccessRequest::try_from).transpose()?;
+        Ok(request)
+    }
+
+    /// Users who decide on access requests to a version
+    pub async fn get_version_owners(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<Vec<Uuid>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT DISTINCT user_id
+                FROM effective_document_version_roles
+                WHERE document_id = $1
+                AND version_id = $2
+                AND role_id = $3
+                ",
+                &[
+                    &document_id,
+                    &version_id,
+                    &i16::from(DocumentVersionRole::Owner),
+                ],
+            )
+            .await?;
+        let owners = rows
+            .into_iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<_, _>>()?;
+        Ok(owners)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AccessRequestsRepository
+where
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let database = DbPool::from_ref(state).get_owned().await.map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(Self { database })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use super::AccessRequestsRepository;
+    use crate::{
+        models::role::{DocumentVersionRole::Viewer, GrantValidity},
+        services::database::{
+            repositories::permission::PermissionRepository, testing::TestDatabase,
+        },
+    };
+
+    const D: &str = "65a45040-f418-11ed-a05b-0242ac120003";
+    const V: &str = "88c2e78e-f419-11ed-a05b-0242ac120003";
+    const W: &str = "65a452ca-f418-11ed-a05b-0242ac120003";
+    const E: &str = "65a45428-f418-11ed-a05b-0242ac120003";
+
+    #[tokio::test]
+    async fn approving_revives_an_expired_role() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let permissions = PermissionRepository::new(db.connection().await);
+        let mut requests = AccessRequestsRepository {
+            database: db.connection().await,
+        };
+        let (d, v, w, e) = (
+            Uuid::parse_str(D).unwrap(),
+            Uuid::parse_str(V).unwrap(),
+            Uuid::parse_str(W).unwrap(),
+            Uuid::parse_str(E).unwrap(),
+        );
+        permissions
+            .grant_document_version_role(
+                e,
+                d,
+                v,
+                Viewer,
+                Some(Utc::now() - Duration::days(2)),
+                Some(Utc::now() - Duration::days(1)),
+            )
+            .await
+            .unwrap();
+        let request = requests
+            .create_request(e, d, v, Viewer, None)
+            .await
+            .unwrap();
+        let (_, granted) = requests
+            .approve_request(d, v, request.request_id, w, &GrantValidity::default())
+            .await
+            .unwrap()
+            .unwrap();
+        assert!(!granted);
+        let roles = permissions.get_document_version_roles(e, d, v).await;
+        assert_eq!(roles.unwrap(), vec![Viewer]);
+        db.finish().await;
+    }
+}
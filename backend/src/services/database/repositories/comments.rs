@@ -37,13 +37,18 @@ impl CommentsRepository {
         ).await?;
         let row = self
             .database
-            .query_one("SELECT username FROM users WHERE user_id = $1", &[&user_id])
+            .query_one(
+                "SELECT username, display_name FROM users WHERE user_id = $1",
+                &[&user_id],
+            )
             .await?;
         let username: String = row.try_get(0)?;
+        let display_name: Option<String> = row.try_get(1)?;
         let comment = DocumentVersionComment {
             comment_id,
             user_id,
             username,
+            display_name,
             created_at,
             content,
         };
@@ -60,14 +65,14 @@ impl CommentsRepository {
             .database
             .query(
                 "
-                SELECT c.comment_id, c.user_id, u.username, c.created_at, c.content
+                SELECT c.comment_id, c.user_id, u.username, u.display_name, c.created_at, c.content
                 FROM document_version_comments c
                 JOIN users u ON u.user_id = c.user_id
                 WHERE c.document_id = $1
                 AND c.version_id = $2
                 AND EXISTS (
                     SELECT *
-                    FROM user_document_version_roles r
+                    FROM effective_document_version_roles r
                     WHERE r.user_id = $3
                     AND r.document_id = c.document_id
                     AND r.version_id = c.version_id
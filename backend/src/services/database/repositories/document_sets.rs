@@ -1,4 +1,4 @@
-use std::error::Error;
+use std::{collections::HashSet, error::Error};
 
 use axum::{
     async_trait,
@@ -12,7 +12,8 @@ use uuid::Uuid;
 
 use crate::{
     models::{
-        document_set::{DocumentSet, DocumentSetWithInitialVersion},
+        document_set::{DocumentSet, DocumentSetMember, DocumentSetWithInitialVersion},
+        role::DocumentSetRole,
         set_version::SetVersion,
     },
     services::database::{DbConn, DbPool},
@@ -25,7 +26,6 @@ pub struct DocumentSetsRepository {
 impl DocumentSetsRepository {
     async fn create_set_version_inner<'a>(
         db: &Transaction<'a>,
-        _user_id: Uuid,
         document_set_id: Uuid,
         set_version_name: String,
         document_version_ids: &[(Uuid, Uuid)],
@@ -111,9 +111,21 @@ impl DocumentSetsRepository {
                 &[&document_set_id, &document_set_name],
             )
             .await?;
+        transaction
+            .execute(
+                "
+                INSERT INTO user_document_set_roles (user_id, document_set_id, role_id)
+                VALUES ($1, $2, $3)
+                ",
+                &[
+                    &user_id,
+                    &document_set_id,
+                    &i16::from(DocumentSetRole::Owner),
+                ],
+            )
+            .await?;
         let initial_version = Self::create_set_version_inner(
             &transaction,
-            user_id,
             document_set_id,
             set_version_name,
             &document_version_ids,
@@ -133,8 +145,8 @@ impl DocumentSetsRepository {
 
     pub async fn get_document_set(
         &self,
-        _user_id: Uuid,
-        _document_set_id: Uuid,
+        user_id: Uuid,
+        document_set_id: Uuid,
     ) -> Result<DocumentSet, Box<dyn Error>> {
         let document_set = self
             .database
@@ -142,9 +154,10 @@ impl DocumentSetsRepository {
                 "
                 SELECT d.document_set_id, d.document_set_name
                 FROM document_sets d
-                WHERE document_set_id = $1
-                ", // TODO: potencjalnie filtrowanie dostępności
-                &[&_document_set_id],
+                WHERE d.document_set_id = $2
+                AND EXISTS (SELECT 1 FROM user_document_set_roles r WHERE r.user_id = $1 AND r.document_set_id = d.document_set_id)
+                ",
+                &[&user_id, &document_set_id],
             )
             .await?;
         let document = DocumentSet::try_from(document_set)?;
@@ -153,7 +166,7 @@ impl DocumentSetsRepository {
 
     pub async fn get_document_sets(
         &self,
-        _user_id: Uuid,
+        user_id: Uuid,
     ) -> Result<Vec<DocumentSet>, Box<dyn Error>> {
         let document_sets = self
             .database
@@ -161,8 +174,9 @@ impl DocumentSetsRepository {
                 "
                 SELECT d.document_set_id, d.document_set_name
                 FROM document_sets d
-                ", // TODO: potencjalnie filtrowanie dostępności
-                &[],
+                WHERE EXISTS (SELECT 1 FROM user_document_set_roles r WHERE r.user_id = $1 AND r.document_set_id = d.document_set_id)
+                ",
+                &[&user_id],
             )
             .await?;
         let documents = document_sets
@@ -172,18 +186,33 @@ impl DocumentSetsRepository {
         Ok(documents)
     }
 
+    /// Returns `None` when the parents aren't distinct versions of the same set
     pub async fn create_document_set_version(
         &mut self,
-        user_id: Uuid,
         document_set_id: Uuid,
         set_version_name: String,
         document_version_ids: Vec<(Uuid, Uuid)>,
         parents: Vec<Uuid>,
-    ) -> Result<SetVersion, Box<dyn Error>> {
+    ) -> Result<Option<SetVersion>, Box<dyn Error>> {
         let transaction = self.database.transaction().await?;
+        let distinct: HashSet<&Uuid> = parents.iter().collect();
+        let known: i64 = transaction
+            .query_one(
+                "
+                SELECT count(*)
+                FROM document_set_versions
+                WHERE document_set_id = $1
+                AND set_version_id = ANY($2)
+                ",
+                &[&document_set_id, &parents],
+            )
+            .await?
+            .try_get(0)?;
+        if distinct.len() != parents.len() || known != parents.len() as i64 {
+            return Ok(None);
+        }
         let document_version = Self::create_set_version_inner(
             &transaction,
-            user_id,
             document_set_id,
             set_version_name,
             &document_version_ids,
@@ -191,12 +220,12 @@ impl DocumentSetsRepository {
         )
         .await?;
         transaction.commit().await?;
-        Ok(document_version)
+        Ok(Some(document_version))
     }
 
     pub async fn get_document_set_versions(
         &self,
-        _user_id: Uuid,
+        user_id: Uuid,
         document_set_id: Uuid,
     ) -> Result<Vec<SetVersion>, Box<dyn Error>> {
         let versions = self
@@ -209,10 +238,11 @@ impl DocumentSetsRepository {
                     array(SELECT c.child_version_id FROM document_sets_dependencies c WHERE c.document_set_id = v.document_set_id AND c.parent_version_id = v.set_version_id),
                     array(SELECT p.parent_version_id FROM document_sets_dependencies p WHERE p.document_set_id = v.document_set_id AND p.child_version_id = v.set_version_id)
                 FROM document_set_versions v
-                WHERE v.document_set_id = $1
+                WHERE v.document_set_id = $2
+                AND EXISTS (SELECT 1 FROM user_document_set_roles r WHERE r.user_id = $1 AND r.document_set_id = v.document_set_id)
                 GROUP BY (v.document_set_id, v.set_version_id)
-                ", // TODO: potencjalnie filtrowanie dostępności
-                &[&document_set_id],
+                ",
+                &[&user_id, &document_set_id],
             )
             .await?;
         let versions = versions
@@ -224,7 +254,6 @@ impl DocumentSetsRepository {
 
     pub async fn add_to_document_set_version(
         &self,
-        _user_id: Uuid,
         document_set_id: Uuid,
         set_version_id: Uuid,
         document_id: Uuid,
@@ -245,7 +274,6 @@ impl DocumentSetsRepository {
 
     pub async fn remove_from_document_set_version(
         &self,
-        _user_id: Uuid,
         document_set_id: Uuid,
         set_version_id: Uuid,
         document_id: Uuid,
@@ -264,6 +292,91 @@ impl DocumentSetsRepository {
             .await?;
         Ok(removed == 1)
     }
+
+    /// Roles held on a document set
+    pub async fn get_document_set_roles(
+        &self,
+        user_id: Uuid,
+        document_set_id: Uuid,
+    ) -> Result<Vec<DocumentSetRole>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "SELECT role_id FROM user_document_set_roles WHERE user_id = $1 AND document_set_id = $2",
+                &[&user_id, &document_set_id],
+            )
+            .await?;
+        let mut roles = vec![];
+        for row in rows {
+            let role_id: i16 = row.try_get(0)?;
+            roles.push(
+                DocumentSetRole::try_from(role_id).map_err(|v| format!("Unknown role {}", v))?,
+            );
+        }
+        Ok(roles)
+    }
+
+    pub async fn get_document_set_members(
+        &self,
+        document_set_id: Uuid,
+    ) -> Result<Vec<DocumentSetMember>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT u.user_id, u.username, u.display_name, array_agg(r.role_id)
+                FROM user_document_set_roles r
+                JOIN users u ON r.user_id = u.user_id
+                WHERE r.document_set_id = $1
+                GROUP BY u.user_id
+                ",
+                &[&document_set_id],
+            )
+            .await?;
+        let members = rows
+            .into_iter()
+            .map(DocumentSetMember::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(members)
+    }
+
+    /// Returns whether the role was newly granted
+    pub async fn grant_document_set_role(
+        &self,
+        user_id: Uuid,
+        document_set_id: Uuid,
+        role: DocumentSetRole,
+    ) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "
+                INSERT INTO user_document_set_roles (user_id, document_set_id, role_id)
+                SELECT user_id, $2, $3 FROM users WHERE user_id = $1 AND deleted_at IS NULL
+                ON CONFLICT DO NOTHING
+                ",
+                &[&user_id, &document_set_id, &i16::from(role)],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    /// Returns whether the user had the role
+    pub async fn revoke_document_set_role(
+        &self,
+        user_id: Uuid,
+        document_set_id: Uuid,
+        role: DocumentSetRole,
+    ) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "DELETE FROM user_document_set_roles WHERE user_id = $1 AND document_set_id = $2 AND role_id = $3",
+                &[&user_id, &document_set_id, &i16::from(role)],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
 }
 
 #[async_trait]
@@ -282,3 +395,53 @@ where
         Ok(Self { database })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::DocumentSetsRepository;
+    use crate::services::database::testing::TestDatabase;
+
+    const Q: &str = "65a45040-f418-11ed-a05b-0242ac120003";
+
+    #[tokio::test]
+    async fn parents_must_belong_to_the_set() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let mut sets = DocumentSetsRepository {
+            database: db.connection().await,
+        };
+        let user_id = Uuid::parse_str(Q).unwrap();
+        let first = sets
+            .create_document_set(user_id, "first".to_owned(), "v1".to_owned(), vec![])
+            .await
+            .unwrap();
+        let second = sets
+            .create_document_set(user_id, "second".to_owned(), "v1".to_owned(), vec![])
+            .await
+            .unwrap();
+        let foreign = sets
+            .create_document_set_version(
+                first.document_set.document_set_id,
+                "v2".to_owned(),
+                vec![],
+                vec![second.initial_version.set_version_id],
+            )
+            .await
+            .unwrap();
+        assert!(foreign.is_none());
+        let own = sets
+            .create_document_set_version(
+                first.document_set.document_set_id,
+                "v2".to_owned(),
+                vec![],
+                vec![first.initial_version.set_version_id],
+            )
+            .await
+            .unwrap();
+        assert!(own.is_some());
+        db.finish().await;
+    }
+}
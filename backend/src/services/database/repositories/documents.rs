@@ -1,4 +1,5 @@
 use std::{
+    collections::HashSet,
     error::Error,
     fmt::{Debug, Display},
 };
@@ -17,30 +18,71 @@ use crate::{
     models::{
         attachment::File,
         document::{Document, DocumentWithInitialVersion},
+        event::{to_sql, EventType},
         role::DocumentVersionRole,
-        version::DocumentVersion,
+        sensitivity_label::SensitivityLabel,
+        version::{DocumentVersion, InheritMembers, VersionGraphNode},
         version_state::DocumentVersionState,
+        RowError,
+    },
+    services::{
+        content_storage::{ContentError, ContentStorage, VersionContent},
+        database::{DbConn, DbPool},
     },
-    services::database::{DbConn, DbPool},
 };
 
 use super::RepoError;
 
 pub struct DocumentsRepository {
     database: DbConn,
+    content_storage: ContentStorage,
+}
+
+/// Objects uploaded and replaced while changing stored content, whichever
+/// side ends up unreferenced is discarded once the transaction is over
+#[derive(Default)]
+struct ContentChanges {
+    uploaded: Vec<String>,
+    replaced: Vec<String>,
+}
+
+impl ContentChanges {
+    fn track(&mut self, version_content: &VersionContent, replaced_object: Option<String>) {
+        self.uploaded
+            .extend(version_content.stored.object_key().map(str::to_owned));
+        self.replaced.extend(replaced_object);
+    }
+
+    async fn finish(self, content_storage: &ContentStorage, committed: bool) {
+        let unreferenced = if committed {
+            self.replaced
+        } else {
+            self.uploaded
+        };
+        for object_key in unreferenced {
+            content_storage.discard(&object_key).await;
+        }
+    }
 }
 
 #[derive(Debug)]
 pub enum UniqueError {
     Pg(tokio_postgres::Error),
+    Row(RowError),
+    Content(ContentError),
     UniqueValueViolation,
+    /// Parents of a version are not distinct versions of its document the user has a role on
+    InvalidParents,
 }
 
 impl Display for UniqueError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Pg(error) => Display::fmt(error, f),
+            Self::Row(error) => Display::fmt(error, f),
+            Self::Content(error) => Display::fmt(error, f),
             Self::UniqueValueViolation => f.write_str("Key already exists"),
+            Self::InvalidParents => f.write_str("Invalid parent versions"),
         }
     }
 }
@@ -53,12 +95,25 @@ impl From<tokio_postgres::Error> for UniqueError {
     }
 }
 
+impl From<RowError> for UniqueError {
+    fn from(value: RowError) -> Self {
+        Self::Row(value)
+    }
+}
+
+impl From<ContentError> for UniqueError {
+    fn from(value: ContentError) -> Self {
+        Self::Content(value)
+    }
+}
+
 #[derive(Debug)]
 pub enum ConcurrencyError<T>
 where
     T: Debug,
 {
     Pg(tokio_postgres::Error),
+    Content(ContentError),
     UniqueValueViolation(T),
     Failed,
 }
@@ -70,6 +125,7 @@ where
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Pg(error) => Display::fmt(error, f),
+            Self::Content(error) => Display::fmt(error, f),
             Self::UniqueValueViolation(_) => f.write_str("Concurrency error"),
             Self::Failed => f.write_str("Failed to updated"),
         }
@@ -87,29 +143,106 @@ where
     }
 }
 
+impl<T> From<ContentError> for ConcurrencyError<T>
+where
+    T: Debug,
+{
+    fn from(value: ContentError) -> Self {
+        Self::Content(value)
+    }
+}
+
 impl DocumentsRepository {
+    pub fn new(database: DbConn, content_storage: ContentStorage) -> Self {
+        Self {
+            database,
+            content_storage,
+        }
+    }
+
+    async fn check_parents<'a>(
+        db: &Transaction<'a>,
+        user_id: Uuid,
+        document_id: Uuid,
+        parent_ids: &[Uuid],
+    ) -> Result<(), UniqueError> {
+        let distinct: HashSet<&Uuid> = parent_ids.iter().collect();
+        if distinct.len() != parent_ids.len() {
+            return Err(UniqueError::InvalidParents);
+        }
+        let visible: i64 = db
+            .query_one(
+                "
+                SELECT count(*)
+                FROM document_versions v
+                WHERE v.document_id = $1
+                AND v.version_id = ANY($2)
+                AND EXISTS (
+                    SELECT *
+                    FROM effective_document_version_roles r
+                    WHERE r.user_id = $3
+                    AND r.document_id = v.document_id
+                    AND r.version_id = v.version_id
+                )
+                ",
+                &[&document_id, &parent_ids, &user_id],
+            )
+            .await?
+            .try_get(0)?;
+        if visible != parent_ids.len() as i64 {
+            return Err(UniqueError::InvalidParents);
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
     async fn create_version_inner<'a>(
         db: &Transaction<'a>,
+        content_storage: &ContentStorage,
         user_id: Uuid,
         document_id: Uuid,
         version_name: String,
         content: String,
         parent_ids: &[Uuid],
+        inherit_members: Option<&InheritMembers>,
     ) -> Result<DocumentVersion, UniqueError> {
+        Self::check_parents(db, user_id, document_id, parent_ids).await?;
         let version_id = Uuid::new_v4();
         let created_at = Utc::now();
-        db
+        let delta_base = parent_ids.first().copied();
+        if let Some(delta_base) = delta_base {
+            Self::lock_content(db, document_id, delta_base).await?;
+        }
+        let version_content = content_storage
+            .store_version(db, document_id, version_id, &content, delta_base)
+            .await?;
+        let (
+            content_text,
+            content_data,
+            content_object,
+            content_encoding,
+            content_length,
+            delta_base_id,
+            delta_depth,
+        ) = version_content.to_sql();
+        let inserted = db
             .execute(
                 "
-                INSERT INTO document_versions (document_id, version_id, version_name, created_at, content)
-                VALUES ($1, $2, $3, $4, $5)
+                INSERT INTO document_versions (document_id, version_id, version_name, created_at, content, content_data, content_object, content_encoding, content_length, delta_base_id, delta_depth, content_packed)
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, true)
                 ",
                 &[
                     &document_id,
                     &version_id,
                     &version_name,
                     &created_at,
-                    &content,
+                    &content_text,
+                    &content_data,
+                    &content_object,
+                    &content_encoding,
+                    &content_length,
+                    &delta_base_id,
+                    &delta_depth,
                 ],
             )
             .await.map_err(|error| {
@@ -121,7 +254,13 @@ impl DocumentsRepository {
                     }
                 }
                 error.into()
-            })?;
+            });
+        if let Err(error) = inserted {
+            if let Some(object_key) = version_content.stored.object_key() {
+                content_storage.discard(object_key).await;
+            }
+            return Err(error);
+        }
         for parent_id in parent_ids {
             db
                 .execute(
@@ -131,7 +270,17 @@ impl DocumentsRepository {
                     ",
                     &[&document_id, &parent_id, &version_id],
                 )
-                .await?;
+                .await
+                .map_err(|error| {
+                    if let Some(db_error) = error.as_db_error() {
+                        if let Some(constraint) = db_error.constraint() {
+                            if constraint.starts_with("ck__documents_dependencies__") {
+                                return UniqueError::InvalidParents;
+                            }
+                        }
+                    }
+                    error.into()
+                })?;
         }
         db.execute(
             "
@@ -146,10 +295,70 @@ impl DocumentsRepository {
             ],
         )
         .await?;
+        db.execute(
+            "
+                INSERT INTO user_document_version_roles (user_id, document_id, version_id, role_id)
+                SELECT d.user_id, d.document_id, $2, d.role_id
+                FROM document_default_members d
+                JOIN users u ON u.user_id = d.user_id
+                WHERE d.document_id = $1
+                AND u.deleted_at IS NULL
+                ON CONFLICT DO NOTHING
+                ",
+            &[&document_id, &version_id],
+        )
+        .await?;
+        if let Some(inherit_members) = inherit_members {
+            Self::inherit_members(
+                db,
+                user_id,
+                document_id,
+                version_id,
+                parent_ids,
+                inherit_members,
+            )
+            .await?;
+        }
+        let members = db
+            .query(
+                "
+                SELECT user_id, role_id
+                FROM effective_document_version_roles
+                WHERE document_id = $1
+                AND version_id = $2
+                AND user_id <> $3
+                ",
+                &[&document_id, &version_id, &user_id],
+            )
+            .await?;
+        for member in members {
+            let member_id: Uuid = member.try_get(0)?;
+            let role_id: i16 = member.try_get(1)?;
+            let Ok(role) = DocumentVersionRole::try_from(role_id) else {
+                continue;
+            };
+            let (event_type, role_id, state_id) = to_sql(&EventType::RoleAdded(role));
+            db.execute(
+                "
+                INSERT INTO events (event_id, user_id, document_id, version_id, event_type, role_id, state_id)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ",
+                &[
+                    &Uuid::new_v4(),
+                    &member_id,
+                    &document_id,
+                    &version_id,
+                    &event_type,
+                    &role_id,
+                    &state_id,
+                ],
+            )
+            .await?;
+        }
         let document_version = db
             .query_one(
                 "
-                SELECT v.document_id, v.version_id, v.version_name, v.created_at, v.content, v.version_state, v.updated_at,
+                SELECT v.document_id, v.version_id, v.version_name, v.created_at, coalesce(v.content_length, octet_length(v.content)), v.version_state, v.updated_at,
                     array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
                     array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id)
                 FROM document_versions v
@@ -160,10 +369,89 @@ impl DocumentsRepository {
                 &[&document_id, &version_id],
             )
             .await?;
-        let document_version = DocumentVersion::try_from(document_version)?;
+        let mut document_version = DocumentVersion::try_from(document_version)?;
+        document_version.content = Some(content);
         Ok(document_version)
     }
 
+    /// Copies roles held on the parents the creator can see, keeping their validity
+    async fn inherit_members<'a>(
+        db: &Transaction<'a>,
+        user_id: Uuid,
+        document_id: Uuid,
+        version_id: Uuid,
+        parent_ids: &[Uuid],
+        inherit_members: &InheritMembers,
+    ) -> Result<(), tokio_postgres::Error> {
+        let roles: Option<Vec<i16>> = inherit_members
+            .roles
+            .as_ref()
+            .map(|roles| roles.iter().copied().map(i16::from).collect());
+        let owner = i16::from(DocumentVersionRole::Owner);
+        db.execute(
+            "
+            INSERT INTO user_document_version_roles (user_id, document_id, version_id, role_id, valid_from, valid_until)
+            SELECT DISTINCT ON (r.user_id, r.role_id) r.user_id, r.document_id, $2::uuid, r.role_id, r.valid_from, r.valid_until
+            FROM user_document_version_roles r
+            JOIN users u ON u.user_id = r.user_id
+            WHERE r.document_id = $1
+            AND r.version_id = ANY($3)
+            AND r.role_id <> $4
+            AND ($5::smallint[] IS NULL OR r.role_id = ANY($5))
+            AND ($6::uuid[] IS NULL OR r.user_id = ANY($6))
+            AND (r.valid_until IS NULL OR r.valid_until > now())
+            AND u.deleted_at IS NULL
+            AND EXISTS (
+                SELECT 1 FROM effective_document_version_roles e
+                WHERE e.user_id = $7
+                AND e.document_id = r.document_id
+                AND e.version_id = r.version_id
+            )
+            ON CONFLICT DO NOTHING
+            ",
+            &[
+                &document_id,
+                &version_id,
+                &parent_ids,
+                &owner,
+                &roles,
+                &inherit_members.users,
+                &user_id,
+            ],
+        )
+        .await?;
+        if inherit_members.users.is_none() {
+            db.execute(
+                "
+                INSERT INTO group_document_version_roles (group_id, document_id, version_id, role_id)
+                SELECT DISTINCT r.group_id, r.document_id, $2::uuid, r.role_id
+                FROM group_document_version_roles r
+                WHERE r.document_id = $1
+                AND r.version_id = ANY($3)
+                AND r.role_id <> $4
+                AND ($5::smallint[] IS NULL OR r.role_id = ANY($5))
+                AND EXISTS (
+                    SELECT 1 FROM effective_document_version_roles e
+                    WHERE e.user_id = $6
+                    AND e.document_id = r.document_id
+                    AND e.version_id = r.version_id
+                )
+                ON CONFLICT DO NOTHING
+                ",
+                &[
+                    &document_id,
+                    &version_id,
+                    &parent_ids,
+                    &owner,
+                    &roles,
+                    &user_id,
+                ],
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
     pub async fn create_document(
         &mut self,
         document_name: String,
@@ -194,17 +482,20 @@ impl DocumentsRepository {
             })?;
         let initial_version = Self::create_version_inner(
             &transaction,
+            &self.content_storage,
             user_id,
             document_id,
             version_name,
             content,
             &[],
+            None,
         )
         .await?;
         transaction.commit().await?;
         let document = Document {
             document_id,
             document_name,
+            label: SensitivityLabel::Public,
         };
         Ok(DocumentWithInitialVersion {
             document,
@@ -221,12 +512,12 @@ impl DocumentsRepository {
             .database
             .query(
                 "
-                SELECT d.document_id, d.document_name
+                SELECT d.document_id, d.document_name, d.label_id
                 FROM documents d
                 WHERE d.document_id = $1
                 AND EXISTS (
                     SELECT *
-                    FROM user_document_version_roles r
+                    FROM effective_document_version_roles r
                     WHERE r.user_id = $2
                     AND r.document_id = d.document_id
                 )
@@ -249,11 +540,11 @@ impl DocumentsRepository {
             .database
             .query(
                 "
-                SELECT d.document_id, d.document_name
+                SELECT d.document_id, d.document_name, d.label_id
                 FROM documents d
                 WHERE EXISTS (
                     SELECT *
-                    FROM user_document_version_roles r
+                    FROM effective_document_version_roles r
                     WHERE r.user_id = $1
                     AND r.document_id = d.document_id
                 )
@@ -275,15 +566,18 @@ impl DocumentsRepository {
         version_name: String,
         content: String,
         parents: Vec<Uuid>,
+        inherit_members: Option<InheritMembers>,
     ) -> Result<DocumentVersion, UniqueError> {
         let transaction = self.database.transaction().await?;
         let document_version = Self::create_version_inner(
             &transaction,
+            &self.content_storage,
             user_id,
             document_id,
             version_name,
             content,
             &parents,
+            inherit_members.as_ref(),
         )
         .await?;
         transaction.commit().await?;
@@ -300,7 +594,7 @@ impl DocumentsRepository {
             .database
             .query_opt(
                 "
-                SELECT v.document_id, v.version_id, v.version_name, v.created_at, v.content, v.version_state, v.updated_at,
+                SELECT v.document_id, v.version_id, v.version_name, v.created_at, coalesce(v.content_length, octet_length(v.content)), v.version_state, v.updated_at,
                     array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
                     array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id)
                 FROM document_versions v
@@ -308,7 +602,7 @@ impl DocumentsRepository {
                 AND v.version_id = $2
                 AND EXISTS (
                     SELECT *
-                    FROM user_document_version_roles r
+                    FROM effective_document_version_roles r
                     WHERE r.user_id = $3
                     AND r.document_id = v.document_id
                     AND r.version_id = v.version_id
@@ -321,7 +615,12 @@ impl DocumentsRepository {
         match version {
             None => Err(RepoError::Forbidden),
             Some(version) => {
-                let version = DocumentVersion::try_from(version)?;
+                let mut version = DocumentVersion::try_from(version)?;
+                let content = self
+                    .content_storage
+                    .load_version(&*self.database, document_id, version_id)
+                    .await?;
+                version.content = Some(content);
                 Ok(version)
             }
         }
@@ -336,14 +635,14 @@ impl DocumentsRepository {
             .database
             .query(
                 "
-                SELECT v.document_id, v.version_id, v.version_name, v.created_at, v.content, v.version_state, v.updated_at,
+                SELECT v.document_id, v.version_id, v.version_name, v.created_at, coalesce(v.content_length, octet_length(v.content)), v.version_state, v.updated_at,
                     array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
                     array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id)
                 FROM document_versions v
                 WHERE v.document_id = $1
                 AND EXISTS (
                     SELECT *
-                    FROM user_document_version_roles r
+                    FROM effective_document_version_roles r
                     WHERE r.user_id = $2
                     AND r.document_id = v.document_id
                     AND r.version_id = v.version_id
@@ -360,40 +659,555 @@ impl DocumentsRepository {
         Ok(versions)
     }
 
-    pub async fn update_version(
+    /// Common ancestors of two versions none of whose children are common ancestors,
+    /// most recently created first, versions are their own ancestors
+    pub async fn get_lowest_common_ancestors(
+        &self,
+        document_id: Uuid,
+        first_id: Uuid,
+        second_id: Uuid,
+    ) -> Result<Vec<Uuid>, tokio_postgres::Error> {
+        let ancestors = self
+            .database
+            .query(
+                "
+                WITH RECURSIVE ancestors (version_id, side) AS (
+                    SELECT * FROM (VALUES ($2::uuid, 1), ($3::uuid, 2)) a
+                    UNION
+                    SELECT d.parent_version_id, a.side
+                    FROM ancestors a
+                    JOIN documents_dependencies d ON d.document_id = $1 AND d.child_version_id = a.version_id
+                ),
+                common AS (
+                    SELECT a.version_id
+                    FROM ancestors a
+                    GROUP BY a.version_id
+                    HAVING count(DISTINCT a.side) = 2
+                )
+                SELECT c.version_id
+                FROM common c
+                JOIN document_versions v ON v.document_id = $1 AND v.version_id = c.version_id
+                WHERE NOT EXISTS (
+                    SELECT *
+                    FROM documents_dependencies d
+                    JOIN common cc ON cc.version_id = d.child_version_id
+                    WHERE d.document_id = $1
+                    AND d.parent_version_id = c.version_id
+                )
+                ORDER BY v.created_at DESC
+                ",
+                &[&document_id, &first_id, &second_id],
+            )
+            .await?;
+        ancestors.iter().map(|row| row.try_get(0)).collect()
+    }
+
+    /// Lowest common ancestors of two versions the user has a role on, both versions need a role
+    pub async fn get_common_ancestors(
         &self,
+        user_id: Uuid,
+        document_id: Uuid,
+        first_id: Uuid,
+        second_id: Uuid,
+    ) -> Result<Vec<DocumentVersion>, RepoError> {
+        let visible: i64 = self
+            .database
+            .query_one(
+                "
+                SELECT count(*)
+                FROM document_versions v
+                WHERE v.document_id = $1
+                AND v.version_id IN ($2, $3)
+                AND EXISTS (
+                    SELECT *
+                    FROM effective_document_version_roles r
+                    WHERE r.user_id = $4
+                    AND r.document_id = v.document_id
+                    AND r.version_id = v.version_id
+                )
+                ",
+                &[&document_id, &first_id, &second_id, &user_id],
+            )
+            .await?
+            .try_get(0)?;
+        let expected = if first_id == second_id { 1 } else { 2 };
+        if visible != expected {
+            return Err(RepoError::Forbidden);
+        }
+        let ancestor_ids = self
+            .get_lowest_common_ancestors(document_id, first_id, second_id)
+            .await?;
+        let ancestors = self
+            .database
+            .query(
+                "
+                SELECT v.document_id, v.version_id, v.version_name, v.created_at, coalesce(v.content_length, octet_length(v.content)), v.version_state, v.updated_at,
+                    array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
+                    array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id)
+                FROM document_versions v
+                WHERE v.document_id = $1
+                AND v.version_id = ANY($2)
+                AND EXISTS (
+                    SELECT *
+                    FROM effective_document_version_roles r
+                    WHERE r.user_id = $3
+                    AND r.document_id = v.document_id
+                    AND r.version_id = v.version_id
+                )
+                ORDER BY v.created_at DESC
+                ",
+                &[&document_id, &ancestor_ids, &user_id],
+            )
+            .await?;
+        let ancestors = ancestors
+            .into_iter()
+            .map(DocumentVersion::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(ancestors)
+    }
+
+    /// Versions the user has a role on reachable by following parents, oldest first
+    pub async fn get_ancestors(
+        &self,
+        user_id: Uuid,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<Vec<DocumentVersion>, Box<dyn Error>> {
+        let ancestors = self
+            .database
+            .query(
+                "
+                WITH RECURSIVE ancestors (version_id) AS (
+                    SELECT d.parent_version_id
+                    FROM documents_dependencies d
+                    WHERE d.document_id = $1
+                    AND d.child_version_id = $2
+                    UNION
+                    SELECT d.parent_version_id
+                    FROM ancestors a
+                    JOIN documents_dependencies d ON d.document_id = $1 AND d.child_version_id = a.version_id
+                )
+                SELECT v.document_id, v.version_id, v.version_name, v.created_at, coalesce(v.content_length, octet_length(v.content)), v.version_state, v.updated_at,
+                    array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
+                    array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id)
+                FROM ancestors a
+                JOIN document_versions v ON v.document_id = $1 AND v.version_id = a.version_id
+                WHERE EXISTS (
+                    SELECT *
+                    FROM effective_document_version_roles r
+                    WHERE r.user_id = $3
+                    AND r.document_id = v.document_id
+                    AND r.version_id = v.version_id
+                )
+                ORDER BY v.created_at
+                ",
+                &[&document_id, &version_id, &user_id],
+            )
+            .await?;
+        let ancestors = ancestors
+            .into_iter()
+            .map(DocumentVersion::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(ancestors)
+    }
+
+    /// Versions the user has a role on reachable by following children, oldest first
+    pub async fn get_descendants(
+        &self,
+        user_id: Uuid,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<Vec<DocumentVersion>, Box<dyn Error>> {
+        let descendants = self
+            .database
+            .query(
+                "
+                WITH RECURSIVE descendants (version_id) AS (
+                    SELECT d.child_version_id
+                    FROM documents_dependencies d
+                    WHERE d.document_id = $1
+                    AND d.parent_version_id = $2
+                    UNION
+                    SELECT d.child_version_id
+                    FROM descendants a
+                    JOIN documents_dependencies d ON d.document_id = $1 AND d.parent_version_id = a.version_id
+                )
+                SELECT v.document_id, v.version_id, v.version_name, v.created_at, coalesce(v.content_length, octet_length(v.content)), v.version_state, v.updated_at,
+                    array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
+                    array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id)
+                FROM descendants a
+                JOIN document_versions v ON v.document_id = $1 AND v.version_id = a.version_id
+                WHERE EXISTS (
+                    SELECT *
+                    FROM effective_document_version_roles r
+                    WHERE r.user_id = $3
+                    AND r.document_id = v.document_id
+                    AND r.version_id = v.version_id
+                )
+                ORDER BY v.created_at
+                ",
+                &[&document_id, &version_id, &user_id],
+            )
+            .await?;
+        let descendants = descendants
+            .into_iter()
+            .map(DocumentVersion::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(descendants)
+    }
+
+    /// Versions without children the user has a role on, oldest first
+    pub async fn get_heads(
+        &self,
+        user_id: Uuid,
+        document_id: Uuid,
+    ) -> Result<Vec<DocumentVersion>, Box<dyn Error>> {
+        let heads = self
+            .database
+            .query(
+                "
+                SELECT v.document_id, v.version_id, v.version_name, v.created_at, coalesce(v.content_length, octet_length(v.content)), v.version_state, v.updated_at,
+                    array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
+                    array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id)
+                FROM document_versions v
+                WHERE v.document_id = $1
+                AND NOT EXISTS (
+                    SELECT *
+                    FROM documents_dependencies c
+                    WHERE c.document_id = v.document_id
+                    AND c.parent_version_id = v.version_id
+                )
+                AND EXISTS (
+                    SELECT *
+                    FROM effective_document_version_roles r
+                    WHERE r.user_id = $2
+                    AND r.document_id = v.document_id
+                    AND r.version_id = v.version_id
+                )
+                ORDER BY v.created_at
+                ",
+                &[&document_id, &user_id],
+            )
+            .await?;
+        let heads = heads
+            .into_iter()
+            .map(DocumentVersion::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(heads)
+    }
+
+    /// Versions the user has a role on ordered so that parents come before their children
+    pub async fn get_version_graph(
+        &self,
+        user_id: Uuid,
+        document_id: Uuid,
+    ) -> Result<Vec<VersionGraphNode>, Box<dyn Error>> {
+        let nodes = self
+            .database
+            .query(
+                "
+                WITH RECURSIVE depths (version_id, depth) AS (
+                    SELECT v.version_id, 0
+                    FROM document_versions v
+                    WHERE v.document_id = $1
+                    AND NOT EXISTS (
+                        SELECT *
+                        FROM documents_dependencies p
+                        WHERE p.document_id = v.document_id
+                        AND p.child_version_id = v.version_id
+                    )
+                    UNION
+                    SELECT d.child_version_id, g.depth + 1
+                    FROM depths g
+                    JOIN documents_dependencies d ON d.document_id = $1 AND d.parent_version_id = g.version_id
+                ),
+                generations AS (
+                    SELECT g.version_id, max(g.depth) AS generation
+                    FROM depths g
+                    GROUP BY g.version_id
+                )
+                SELECT v.document_id, v.version_id, v.version_name, v.created_at, coalesce(v.content_length, octet_length(v.content)), v.version_state, v.updated_at,
+                    array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
+                    array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id),
+                    g.generation
+                FROM generations g
+                JOIN document_versions v ON v.document_id = $1 AND v.version_id = g.version_id
+                WHERE EXISTS (
+                    SELECT *
+                    FROM effective_document_version_roles r
+                    WHERE r.user_id = $2
+                    AND r.document_id = v.document_id
+                    AND r.version_id = v.version_id
+                )
+                ORDER BY g.generation, v.created_at, v.version_id
+                ",
+                &[&document_id, &user_id],
+            )
+            .await?;
+        let nodes = nodes
+            .into_iter()
+            .map(VersionGraphNode::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(nodes)
+    }
+
+    /// Reads the content of a version without checking roles
+    pub async fn get_content(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<String, ContentError> {
+        self.content_storage
+            .load_version(&*self.database, document_id, version_id)
+            .await
+    }
+
+    /// Reads a version along with its content without checking roles
+    async fn get_version_content(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<DocumentVersion, ConcurrencyError<DocumentVersion>> {
+        let version = self
+            .database
+            .query_one(
+                "
+                SELECT v.document_id, v.version_id, v.version_name, v.created_at, coalesce(v.content_length, octet_length(v.content)), v.version_state, v.updated_at,
+                    array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
+                    array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id)
+                FROM document_versions v
+                WHERE v.document_id = $1
+                AND v.version_id = $2
+                ",
+                &[&document_id, &version_id],
+            )
+            .await?;
+        let mut version = DocumentVersion::try_from(version)?;
+        let content = self
+            .content_storage
+            .load_version(&*self.database, document_id, version_id)
+            .await?;
+        version.content = Some(content);
+        Ok(version)
+    }
+
+    /// Keeps the content of a version from changing until the transaction ends
+    async fn lock_content<'a>(
+        db: &Transaction<'a>,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<(), tokio_postgres::Error> {
+        db.execute(
+            "
+            SELECT 1
+            FROM document_versions v
+            WHERE v.document_id = $1
+            AND v.version_id = $2
+            FOR SHARE
+            ",
+            &[&document_id, &version_id],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Locks the content a version may be stored as a delta against,
+    /// its current delta base or else one of its parents
+    async fn lock_delta_base<'a>(
+        db: &Transaction<'a>,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<Option<Uuid>, tokio_postgres::Error> {
+        let delta_base: Option<Uuid> = db
+            .query_one(
+                "
+                SELECT coalesce(v.delta_base_id, (
+                    SELECT p.parent_version_id
+                    FROM documents_dependencies p
+                    WHERE p.document_id = v.document_id
+                    AND p.child_version_id = v.version_id
+                    ORDER BY p.parent_version_id
+                    LIMIT 1
+                ))
+                FROM document_versions v
+                WHERE v.document_id = $1
+                AND v.version_id = $2
+                ",
+                &[&document_id, &version_id],
+            )
+            .await?
+            .try_get(0)?;
+        if let Some(delta_base) = delta_base {
+            Self::lock_content(db, document_id, delta_base).await?;
+        }
+        Ok(delta_base)
+    }
+
+    async fn write_content<'a>(
+        db: &Transaction<'a>,
+        document_id: Uuid,
+        version_id: Uuid,
+        version_content: &VersionContent,
+        packed: bool,
+    ) -> Result<(), tokio_postgres::Error> {
+        let (
+            content_text,
+            content_data,
+            content_object,
+            content_encoding,
+            content_length,
+            delta_base_id,
+            delta_depth,
+        ) = version_content.to_sql();
+        db.execute(
+            "
+            UPDATE document_versions
+            SET content = $1, content_data = $2, content_object = $3, content_encoding = $4, content_length = $5,
+                delta_base_id = $6, delta_depth = $7, content_packed = $8
+            WHERE document_id = $9
+            AND version_id = $10
+            ",
+            &[
+                &content_text,
+                &content_data,
+                &content_object,
+                &content_encoding,
+                &content_length,
+                &delta_base_id,
+                &delta_depth,
+                &packed,
+                &document_id,
+                &version_id,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Stores new content of a locked version, versions stored as deltas against it
+    /// are first stored in full and left for the repacking job
+    async fn replace_content<'a>(
+        db: &Transaction<'a>,
+        content_storage: &ContentStorage,
+        document_id: Uuid,
+        version_id: Uuid,
+        content: &str,
+        delta_base: Option<Uuid>,
+        changes: &mut ContentChanges,
+    ) -> Result<(), ContentError> {
+        let dependents = db
+            .query(
+                "
+                SELECT v.version_id, v.content_object
+                FROM document_versions v
+                WHERE v.document_id = $1
+                AND v.delta_base_id = $2
+                FOR UPDATE
+                ",
+                &[&document_id, &version_id],
+            )
+            .await?;
+        for dependent in dependents {
+            let dependent_id: Uuid = dependent.try_get(0)?;
+            let replaced_object: Option<String> = dependent.try_get(1)?;
+            let dependent_content = content_storage
+                .load_version(db, document_id, dependent_id)
+                .await?;
+            let version_content = content_storage
+                .store_version(db, document_id, dependent_id, &dependent_content, None)
+                .await?;
+            changes.track(&version_content, replaced_object);
+            Self::write_content(db, document_id, dependent_id, &version_content, false).await?;
+        }
+        let replaced_object: Option<String> = db
+            .query_one(
+                "
+                SELECT v.content_object
+                FROM document_versions v
+                WHERE v.document_id = $1
+                AND v.version_id = $2
+                ",
+                &[&document_id, &version_id],
+            )
+            .await?
+            .try_get(0)?;
+        let version_content = content_storage
+            .store_version(db, document_id, version_id, content, delta_base)
+            .await?;
+        changes.track(&version_content, replaced_object);
+        Self::write_content(db, document_id, version_id, &version_content, true).await?;
+        Ok(())
+    }
+
+    pub async fn update_version(
+        &mut self,
         document_id: Uuid,
         version_id: Uuid,
         content: String,
         updated_at: DateTime<Utc>,
     ) -> Result<DocumentVersion, ConcurrencyError<DocumentVersion>> {
-        let now = Utc::now();
-        let updated = self
-            .database
-            .execute(
+        let transaction = self.database.transaction().await?;
+        let delta_base = Self::lock_delta_base(&transaction, document_id, version_id).await?;
+        let current = transaction
+            .query_one(
                 "
-                UPDATE document_versions
-                SET content = $1, updated_at = $2
-                WHERE document_id = $3
-                AND version_id = $4
-                AND updated_at = $5
-                AND version_state = $6
+                SELECT v.updated_at, v.version_state
+                FROM document_versions v
+                WHERE v.document_id = $1
+                AND v.version_id = $2
+                FOR UPDATE
                 ",
-                &[
-                    &content,
-                    &now,
-                    &document_id,
-                    &version_id,
-                    &updated_at,
-                    &i16::from(DocumentVersionState::InProgress),
-                ],
+                &[&document_id, &version_id],
+            )
+            .await?;
+        let current_updated_at: DateTime<Utc> = current.try_get(0)?;
+        let current_state: i16 = current.try_get(1)?;
+        if current_updated_at != updated_at
+            || current_state != i16::from(DocumentVersionState::InProgress)
+        {
+            transaction.rollback().await?;
+            let version = self.get_version_content(document_id, version_id).await?;
+            return if updated_at != version.updated_at {
+                Err(ConcurrencyError::UniqueValueViolation(version))
+            } else {
+                Err(ConcurrencyError::Failed)
+            };
+        }
+        let now = Utc::now();
+        let mut changes = ContentChanges::default();
+        let updated = async {
+            Self::replace_content(
+                &transaction,
+                &self.content_storage,
+                document_id,
+                version_id,
+                &content,
+                delta_base,
+                &mut changes,
             )
             .await?;
+            transaction
+                .execute(
+                    "
+                    UPDATE document_versions
+                    SET updated_at = $1
+                    WHERE document_id = $2
+                    AND version_id = $3
+                    ",
+                    &[&now, &document_id, &version_id],
+                )
+                .await?;
+            transaction.commit().await?;
+            Ok::<_, ContentError>(())
+        }
+        .await;
+        changes.finish(&self.content_storage, updated.is_ok()).await;
+        updated?;
         let version = self
             .database
             .query_one(
                 "
-                SELECT v.document_id, v.version_id, v.version_name, v.created_at, v.content, v.version_state, v.updated_at,
+                SELECT v.document_id, v.version_id, v.version_name, v.created_at, coalesce(v.content_length, octet_length(v.content)), v.version_state, v.updated_at,
                     array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
                     array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id)
                 FROM document_versions v
@@ -403,14 +1217,117 @@ impl DocumentsRepository {
                 &[&document_id, &version_id],
             )
             .await?;
-        let version = DocumentVersion::try_from(version)?;
-        if updated == 1 {
-            Ok(version)
-        } else if updated == 0 && updated_at != version.updated_at {
-            Err(ConcurrencyError::UniqueValueViolation(version))
-        } else {
-            Err(ConcurrencyError::Failed)
+        let mut version = DocumentVersion::try_from(version)?;
+        version.content = Some(content);
+        Ok(version)
+    }
+
+    /// Stores the oldest versions not looked at yet as deltas against their delta base
+    /// where it saves space, returns how many versions are now stored as deltas
+    pub async fn repack_versions(&mut self, limit: i64) -> Result<usize, ContentError> {
+        let candidates = self
+            .database
+            .query(
+                "
+                SELECT v.document_id, v.version_id
+                FROM document_versions v
+                WHERE NOT v.content_packed
+                ORDER BY v.created_at
+                LIMIT $1
+                ",
+                &[&limit],
+            )
+            .await?;
+        let mut repacked = 0;
+        for candidate in candidates {
+            let document_id: Uuid = candidate.try_get(0)?;
+            let version_id: Uuid = candidate.try_get(1)?;
+            let transaction = self.database.transaction().await?;
+            let mut changes = ContentChanges::default();
+            let packed = async {
+                let delta_base =
+                    Self::lock_delta_base(&transaction, document_id, version_id).await?;
+                let version = transaction
+                    .query_one(
+                        "
+                        SELECT v.content_packed, v.delta_base_id, v.content_object, EXISTS (
+                            SELECT *
+                            FROM document_versions d
+                            WHERE d.document_id = v.document_id
+                            AND d.delta_base_id = v.version_id
+                        )
+                        FROM document_versions v
+                        WHERE v.document_id = $1
+                        AND v.version_id = $2
+                        FOR UPDATE
+                        ",
+                        &[&document_id, &version_id],
+                    )
+                    .await?;
+                let packed: bool = version.try_get(0)?;
+                let current_base: Option<Uuid> = version.try_get(1)?;
+                let replaced_object: Option<String> = version.try_get(2)?;
+                let has_dependents: bool = version.try_get(3)?;
+                if packed {
+                    return Ok(false);
+                }
+                // Restoring a version with dependents would mean restoring them as well
+                let mut delta = false;
+                if let (Some(delta_base), None, false) = (delta_base, current_base, has_dependents)
+                {
+                    let content = self
+                        .content_storage
+                        .load_version(&transaction, document_id, version_id)
+                        .await?;
+                    let version_content = self
+                        .content_storage
+                        .store_version(
+                            &transaction,
+                            document_id,
+                            version_id,
+                            &content,
+                            Some(delta_base),
+                        )
+                        .await?;
+                    changes.track(&version_content, replaced_object);
+                    if version_content.delta_base.is_some() {
+                        Self::write_content(
+                            &transaction,
+                            document_id,
+                            version_id,
+                            &version_content,
+                            true,
+                        )
+                        .await?;
+                        delta = true;
+                    } else {
+                        // Not worth a delta, the copy just uploaded is dropped instead
+                        changes.replaced = std::mem::take(&mut changes.uploaded);
+                    }
+                }
+                if !delta {
+                    transaction
+                        .execute(
+                            "
+                            UPDATE document_versions
+                            SET content_packed = true
+                            WHERE document_id = $1
+                            AND version_id = $2
+                            ",
+                            &[&document_id, &version_id],
+                        )
+                        .await?;
+                }
+                transaction.commit().await?;
+                Ok::<_, ContentError>(delta)
+            }
+            .await;
+            changes.finish(&self.content_storage, packed.is_ok()).await;
+            if packed? {
+                repacked += 1;
+            }
         }
+        Ok(repacked)
     }
 
     pub async fn get_file_attachments(
@@ -430,7 +1347,7 @@ impl DocumentsRepository {
                 AND a.version_id = $2
                 AND EXISTS (
                     SELECT *
-                    FROM user_document_version_roles r
+                    FROM effective_document_version_roles r
                     WHERE r.user_id = $3
                     AND r.document_id = a.document_id
                     AND r.version_id = a.version_id
@@ -465,7 +1382,7 @@ impl DocumentsRepository {
                 AND a.file_id = $3
                 AND EXISTS (
                     SELECT *
-                    FROM user_document_version_roles r
+                    FROM effective_document_version_roles r
                     WHERE r.user_id = $4
                     AND r.document_id = a.document_id
                     AND r.version_id = a.version_id
@@ -564,21 +1481,7 @@ impl DocumentsRepository {
                 ],
             )
             .await?;
-        let version = self
-            .database
-            .query_one(
-                "
-                SELECT v.document_id, v.version_id, v.version_name, v.created_at, v.content, v.version_state, v.updated_at,
-                    array(SELECT c.child_version_id FROM documents_dependencies c WHERE c.document_id = v.document_id AND c.parent_version_id = v.version_id),
-                    array(SELECT p.parent_version_id FROM documents_dependencies p WHERE p.document_id = v.document_id AND p.child_version_id = v.version_id)
-                FROM document_versions v
-                WHERE v.document_id = $1
-                AND v.version_id = $2
-                ",
-                &[&document_id, &version_id],
-            )
-            .await?;
-        let version = DocumentVersion::try_from(version)?;
+        let version = self.get_version_content(document_id, version_id).await?;
         if modified == 1 {
             Ok(version)
         } else if modified == 0 && updated_at != version.updated_at {
@@ -593,6 +1496,7 @@ impl DocumentsRepository {
 impl<S> FromRequestParts<S> for DocumentsRepository
 where
     DbPool: FromRef<S>,
+    ContentStorage: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = StatusCode;
@@ -602,6 +1506,10 @@ where
             error!("{}", e);
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
-        Ok(Self { database })
+        let content_storage = ContentStorage::from_ref(state);
+        Ok(Self {
+            database,
+            content_storage,
+        })
     }
 }
@@ -40,7 +40,7 @@ impl EventsRepository {
     pub async fn get_events_for_user(&self, user_id: Uuid) -> Result<Vec<Event>, Box<dyn Error>> {
         let events = self.database.query(
             "
-                SELECT event_id, user_id, document_id, version_id, event_type, role_id, state_id, seen, created_at
+                SELECT event_id, user_id, document_id, version_id, event_type, role_id, state_id, seen, created_at, version_ids
                 FROM events
                 WHERE user_id = $1
                 ORDER BY seen DESC, created_at DESC
@@ -53,14 +53,16 @@ impl EventsRepository {
         Ok(events)
     }
 
-    pub async fn mark_read(&self, event_id: Uuid) -> Result<(), Box<dyn Error>> {
-        self.database
+    /// Marks one of the user's events as seen, returns false if they have no such event
+    pub async fn mark_read(&self, user_id: Uuid, event_id: Uuid) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
             .execute(
-                "UPDATE events SET seen = TRUE WHERE event_id = $1",
-                &[&event_id],
+                "UPDATE events SET seen = TRUE WHERE event_id = $1 AND user_id = $2",
+                &[&event_id, &user_id],
             )
             .await?;
-        Ok(())
+        Ok(modified == 1)
     }
 }
 
@@ -0,0 +1,215 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::group::{Group, GroupMember},
+    services::database::{DbConn, DbPool},
+};
+
+use super::documents::UniqueError;
+
+pub struct GroupsRepository {
+    database: DbConn,
+}
+
+fn map_unique_error(error: tokio_postgres::Error) -> UniqueError {
+    if let Some(db_error) = error.as_db_error() {
+        if let Some(constraint) = db_error.constraint() {
+            if constraint == "groups_group_name_key" {
+                return UniqueError::UniqueValueViolation;
+            }
+        }
+    }
+    error.into()
+}
+
+impl GroupsRepository {
+    /// Creates a group managed by its creator
+    pub async fn create_group(
+        &mut self,
+        user_id: Uuid,
+        group_name: String,
+    ) -> Result<Group, UniqueError> {
+        let transaction = self.database.transaction().await?;
+        let row = transaction
+            .query_one(
+                "
+                INSERT INTO groups (group_id, group_name) VALUES ($1, $2)
+                RETURNING group_id, group_name, created_at
+                ",
+                &[&Uuid::new_v4(), &group_name],
+            )
+            .await
+            .map_err(map_unique_error)?;
+        let group = Group::try_from(row)?;
+        transaction
+            .execute(
+                "INSERT INTO group_members (group_id, user_id, is_manager) VALUES ($1, $2, TRUE)",
+                &[&group.group_id, &user_id],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(group)
+    }
+
+    /// Groups the user is a member of
+    pub async fn get_groups(&self, user_id: Uuid) -> Result<Vec<Group>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT g.group_id, g.group_name, g.created_at
+                FROM groups g
+                JOIN group_members m ON m.group_id = g.group_id
+                WHERE m.user_id = $1
+                ORDER BY g.group_name
+                ",
+                &[&user_id],
+            )
+            .await?;
+        let groups = rows
+            .into_iter()
+            .map(Group::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(groups)
+    }
+
+    pub async fn get_group(&self, group_id: Uuid) -> Result<Option<Group>, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_opt(
+                "SELECT group_id, group_name, created_at FROM groups WHERE group_id = $1",
+                &[&group_id],
+            )
+            .await?;
+        let group = row.map(Group::try_from).transpose()?;
+        Ok(group)
+    }
+
+    pub async fn get_members(&self, group_id: Uuid) -> Result<Vec<GroupMember>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT u.user_id, u.username, u.display_name, m.is_manager
+                FROM group_members m
+                JOIN users u ON u.user_id = m.user_id
+                WHERE m.group_id = $1
+                ORDER BY u.username
+                ",
+                &[&group_id],
+            )
+            .await?;
+        let members = rows
+            .into_iter()
+            .map(GroupMember::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(members)
+    }
+
+    /// Returns whether the user manages the group, `None` if they aren't a member
+    pub async fn get_membership(
+        &self,
+        group_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<Option<bool>, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_opt(
+                "SELECT is_manager FROM group_members WHERE group_id = $1 AND user_id = $2",
+                &[&group_id, &user_id],
+            )
+            .await?;
+        let is_manager = match row {
+            Some(row) => Some(row.try_get(0)?),
+            None => None,
+        };
+        Ok(is_manager)
+    }
+
+    pub async fn rename_group(
+        &self,
+        group_id: Uuid,
+        group_name: String,
+    ) -> Result<bool, UniqueError> {
+        let modified = self
+            .database
+            .execute(
+                "UPDATE groups SET group_name = $2 WHERE group_id = $1",
+                &[&group_id, &group_name],
+            )
+            .await
+            .map_err(map_unique_error)?;
+        Ok(modified == 1)
+    }
+
+    /// Removes the group along with its memberships and grants
+    pub async fn delete_group(&self, group_id: Uuid) -> Result<bool, Box<dyn Error>> {
+        let removed = self
+            .database
+            .execute("DELETE FROM groups WHERE group_id = $1", &[&group_id])
+            .await?;
+        Ok(removed == 1)
+    }
+
+    /// Adds a member or changes whether they manage the group,
+    /// returns false if there's no such user
+    pub async fn set_member(
+        &self,
+        group_id: Uuid,
+        user_id: Uuid,
+        is_manager: bool,
+    ) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "
+                INSERT INTO group_members (group_id, user_id, is_manager)
+                SELECT $1, user_id, $3 FROM users WHERE user_id = $2 AND deleted_at IS NULL
+                ON CONFLICT (group_id, user_id) DO UPDATE SET is_manager = EXCLUDED.is_manager
+                ",
+                &[&group_id, &user_id, &is_manager],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    pub async fn remove_member(
+        &self,
+        group_id: Uuid,
+        user_id: Uuid,
+    ) -> Result<bool, Box<dyn Error>> {
+        let removed = self
+            .database
+            .execute(
+                "DELETE FROM group_members WHERE group_id = $1 AND user_id = $2",
+                &[&group_id, &user_id],
+            )
+            .await?;
+        Ok(removed == 1)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for GroupsRepository
+where
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let database = DbPool::from_ref(state).get_owned().await.map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(Self { database })
+    }
+}
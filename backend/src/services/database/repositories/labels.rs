@@ -0,0 +1,112 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        sensitivity_label::{DocumentLabelChange, SensitivityLabel},
+        RowError,
+    },
+    services::database::{DbConn, DbPool},
+};
+
+pub struct LabelsRepository {
+    database: DbConn,
+}
+
+impl LabelsRepository {
+    /// Relabels the document and records the change,
+    /// returns the previous label or `None` if the document doesn't exist
+    pub async fn set_document_label(
+        &mut self,
+        document_id: Uuid,
+        label: SensitivityLabel,
+        changed_by: Uuid,
+    ) -> Result<Option<SensitivityLabel>, Box<dyn Error + Send + Sync>> {
+        let transaction = self.database.transaction().await?;
+        let row = transaction
+            .query_opt(
+                "SELECT label_id FROM documents WHERE document_id = $1 FOR UPDATE",
+                &[&document_id],
+            )
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let old_label_id: i16 = row.try_get(0)?;
+        let old_label = SensitivityLabel::try_from(old_label_id)
+            .map_err(RowError::unknown_value("label_id"))?;
+        let new_label = i16::from(label);
+        if old_label_id != new_label {
+            transaction
+                .execute(
+                    "UPDATE documents SET label_id = $2 WHERE document_id = $1",
+                    &[&document_id, &new_label],
+                )
+                .await?;
+            transaction
+                .execute(
+                    "
+                    INSERT INTO document_label_changes (change_id, document_id, old_label_id, new_label_id, changed_by)
+                    VALUES ($1, $2, $3, $4, $5)
+                    ",
+                    &[
+                        &Uuid::new_v4(),
+                        &document_id,
+                        &old_label_id,
+                        &new_label,
+                        &changed_by,
+                    ],
+                )
+                .await?;
+            transaction.commit().await?;
+        }
+        Ok(Some(old_label))
+    }
+
+    pub async fn get_label_changes(
+        &self,
+        document_id: Uuid,
+    ) -> Result<Vec<DocumentLabelChange>, Box<dyn Error>> {
+        let changes = self
+            .database
+            .query(
+                "
+                SELECT change_id, document_id, old_label_id, new_label_id, changed_by, changed_at
+                FROM document_label_changes
+                WHERE document_id = $1
+                ORDER BY changed_at DESC
+                ",
+                &[&document_id],
+            )
+            .await?;
+        let changes = changes
+            .into_iter()
+            .map(DocumentLabelChange::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(changes)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for LabelsRepository
+where
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let database = DbPool::from_ref(state).get_owned().await.map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(Self { database })
+    }
+}
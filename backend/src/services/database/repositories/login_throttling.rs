@@ -0,0 +1,302 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use chrono::{DateTime, Duration, Utc};
+use tokio_postgres::Transaction;
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::{
+    models::security_event::{SecurityEvent, SecurityEventType},
+    services::{
+        auth::throttling::{Throttle, ThrottleScope, ThrottlingConfig},
+        database::{DbConn, DbPool},
+    },
+};
+
+pub struct LoginThrottlingRepository {
+    database: DbConn,
+    config: ThrottlingConfig,
+}
+
+/// Keys longer than the column are cut, they are only compared against each other
+fn throttle_key(key: &str) -> String {
+    key.chars().take(255).collect()
+}
+
+impl LoginThrottlingRepository {
+    /// Returns until when attempts for the key, a username or share link id depending on the scope,
+    /// or from the address are refused
+    pub async fn blocked_until(
+        &self,
+        scope: ThrottleScope,
+        key: &str,
+        address: &str,
+    ) -> Result<Option<DateTime<Utc>>, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_one(
+                "
+                SELECT max(blocked_until) FROM login_throttles
+                WHERE ((scope = $1 AND key = $2) OR (scope = $3 AND key = $4))
+                AND blocked_until > now()
+                ",
+                &[
+                    &(scope as i16),
+                    &throttle_key(key),
+                    &(ThrottleScope::Address as i16),
+                    &throttle_key(address),
+                ],
+            )
+            .await?;
+        Ok(row.try_get(0)?)
+    }
+
+    /// Counts a failed attempt against both the key and the address
+    pub async fn record_failure(
+        &mut self,
+        scope: ThrottleScope,
+        key: &str,
+        address: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        let transaction = self.database.transaction().await?;
+        for (scope, key) in [
+            (scope, throttle_key(key)),
+            (ThrottleScope::Address, throttle_key(address)),
+        ] {
+            let failures: i32 = transaction
+                .query_one(
+                    "
+                    INSERT INTO login_throttles (scope, key, failures) VALUES ($1, $2, 1)
+                    ON CONFLICT (scope, key) DO UPDATE
+                    SET failures = CASE
+                        WHEN login_throttles.last_failure_at < now() - make_interval(secs => $3) THEN 1
+                        ELSE login_throttles.failures + 1
+                    END,
+                    last_failure_at = now()
+                    RETURNING failures
+                    ",
+                    &[&(scope as i16), &key, &(self.config.failure_window as f64)],
+                )
+                .await?
+                .try_get(0)?;
+            match self.config.throttle(scope, failures) {
+                Throttle::None => {}
+                Throttle::Delay(delay) => {
+                    Self::block(&transaction, scope, &key, delay, false).await?;
+                }
+                Throttle::Lockout(duration) => {
+                    Self::block(&transaction, scope, &key, duration, true).await?;
+                    Self::lockout(&transaction, address).await?;
+                }
+            }
+        }
+        transaction.commit().await?;
+        Ok(())
+    }
+
+    async fn block<'a>(
+        db: &Transaction<'a>,
+        scope: ThrottleScope,
+        key: &str,
+        duration: Duration,
+        reset: bool,
+    ) -> Result<(), tokio_postgres::Error> {
+        let blocked_until = Utc::now() + duration;
+        db.execute(
+            "
+            UPDATE login_throttles
+            SET blocked_until = $3, failures = CASE WHEN $4 THEN 0 ELSE failures END
+            WHERE scope = $1 AND key = $2
+            ",
+            &[&(scope as i16), &key, &blocked_until, &reset],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Records an event for admins, only addresses are locked out
+    async fn lockout<'a>(db: &Transaction<'a>, address: &str) -> Result<(), tokio_postgres::Error> {
+        info!({ address = address }, "Address blocked");
+        db.execute(
+            "
+            INSERT INTO security_events (event_id, event_type, user_id, address)
+            VALUES ($1, $2, NULL, $3)
+            ",
+            &[
+                &Uuid::new_v4(),
+                &i16::from(SecurityEventType::AddressBlocked),
+                &throttle_key(address),
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Forgets failures of the key after a successful attempt
+    pub async fn record_success(
+        &self,
+        scope: ThrottleScope,
+        key: &str,
+    ) -> Result<(), Box<dyn Error>> {
+        self.database
+            .execute(
+                "DELETE FROM login_throttles WHERE scope = $1 AND key = $2",
+                &[&(scope as i16), &throttle_key(key)],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Lifts throttling of a user's username, e.g. when an admin unlocks the account
+    pub async fn clear_user(&self, user_id: Uuid) -> Result<(), Box<dyn Error>> {
+        self.database
+            .execute(
+                "
+                DELETE FROM login_throttles
+                WHERE scope = $1
+                AND key = (SELECT username FROM users WHERE user_id = $2)
+                ",
+                &[&(ThrottleScope::Username as i16), &user_id],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_security_events(&self) -> Result<Vec<SecurityEvent>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT e.event_id, e.event_type, e.user_id, u.username, e.address, e.created_at
+                FROM security_events e
+                LEFT JOIN users u ON u.user_id = e.user_id
+                ORDER BY e.created_at DESC
+                ",
+                &[],
+            )
+            .await?;
+        let events = rows
+            .into_iter()
+            .map(SecurityEvent::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(events)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for LoginThrottlingRepository
+where
+    DbPool: FromRef<S>,
+    ThrottlingConfig: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let database = DbPool::from_ref(state).get_owned().await.map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        let config = ThrottlingConfig::from_ref(state);
+        Ok(Self { database, config })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LoginThrottlingRepository;
+    use crate::services::{
+        auth::throttling::{
+            BackoffLimits,
+            ThrottleScope::{ShareLink, Username},
+            ThrottlingConfig, ThrottlingLimits,
+        },
+        database::testing::TestDatabase,
+    };
+
+    async fn repository(db: &TestDatabase) -> LoginThrottlingRepository {
+        LoginThrottlingRepository {
+            database: db.connection().await,
+            config: ThrottlingConfig {
+                username: BackoffLimits { free_attempts: 3 },
+                share_link: BackoffLimits { free_attempts: 3 },
+                address: ThrottlingLimits {
+                    free_attempts: 20,
+                    lockout_attempts: 100,
+                },
+                base_delay: 60,
+                max_delay: 300,
+                failure_window: 3600,
+                lockout_duration: 1800,
+            },
+        }
+    }
+
+    #[tokio::test]
+    async fn username_failures_add_up_across_addresses() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let mut throttling = repository(&db).await;
+        for address in ["10.0.0.1", "10.0.0.1", "10.0.0.2", "10.0.0.2"] {
+            assert!(throttling
+                .blocked_until(Username, "q", "10.0.0.3")
+                .await
+                .unwrap()
+                .is_none());
+            throttling
+                .record_failure(Username, "q", address)
+                .await
+                .unwrap();
+        }
+        let blocked_until = throttling
+            .blocked_until(Username, "q", "10.0.0.3")
+            .await
+            .unwrap();
+        assert!(blocked_until.is_some());
+        assert!(throttling
+            .blocked_until(Username, "w", "10.0.0.3")
+            .await
+            .unwrap()
+            .is_none());
+        throttling.record_success(Username, "q").await.unwrap();
+        assert!(throttling
+            .blocked_until(Username, "q", "10.0.0.3")
+            .await
+            .unwrap()
+            .is_none());
+        db.finish().await;
+    }
+
+    #[tokio::test]
+    async fn share_link_failures_add_up_across_addresses() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let mut throttling = repository(&db).await;
+        let link_id = "5b0bbd50-2bd6-4a5b-a8c6-6d1f7fc1b6a7";
+        for address in ["10.0.0.1", "10.0.0.2", "10.0.0.3", "10.0.0.4"] {
+            throttling
+                .record_failure(ShareLink, link_id, address)
+                .await
+                .unwrap();
+        }
+        let blocked_until = throttling
+            .blocked_until(ShareLink, link_id, "10.0.0.5")
+            .await
+            .unwrap();
+        assert!(blocked_until.is_some());
+        // Scopes are kept apart even for equal keys
+        let blocked_until = throttling
+            .blocked_until(Username, link_id, "10.0.0.5")
+            .await
+            .unwrap();
+        assert!(blocked_until.is_none());
+        db.finish().await;
+    }
+}
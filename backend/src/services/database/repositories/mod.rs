@@ -3,12 +3,24 @@ use std::{
     fmt::{Debug, Display},
 };
 
+use crate::{models::RowError, services::content_storage::ContentError};
+
+pub mod access_requests;
 pub mod comments;
 pub mod document_sets;
 pub mod documents;
 pub mod events;
 pub mod files;
+pub mod groups;
+pub mod labels;
+pub mod login_throttling;
+pub mod oidc_logins;
+pub mod ownership;
 pub mod permission;
+pub mod personal_tokens;
+pub mod sessions;
+pub mod share_links;
+pub mod totp;
 pub mod users;
 
 #[derive(Debug)]
@@ -37,3 +49,15 @@ impl From<tokio_postgres::Error> for RepoError {
         Self::Database(Box::new(value))
     }
 }
+
+impl From<RowError> for RepoError {
+    fn from(value: RowError) -> Self {
+        Self::Database(Box::new(value))
+    }
+}
+
+impl From<ContentError> for RepoError {
+    fn from(value: ContentError) -> Self {
+        Self::Database(Box::new(value))
+    }
+}
@@ -0,0 +1,76 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use tracing::error;
+
+use crate::services::database::{DbConn, DbPool};
+
+/// How long a started login may wait for the provider's callback
+const LOGIN_TIMEOUT_MINUTES: i32 = 10;
+
+pub struct OidcLoginsRepository {
+    database: DbConn,
+}
+
+impl OidcLoginsRepository {
+    pub async fn start_login(
+        &self,
+        state: &str,
+        code_verifier: &str,
+        nonce: &str,
+    ) -> Result<(), Box<dyn Error + Send + Sync>> {
+        self.database
+            .execute(
+                "INSERT INTO oidc_logins (state, code_verifier, nonce) VALUES ($1, $2, $3)",
+                &[&state, &code_verifier, &nonce],
+            )
+            .await?;
+        Ok(())
+    }
+
+    /// Consumes a started login, returning its code verifier and nonce
+    pub async fn finish_login(
+        &self,
+        state: &str,
+    ) -> Result<Option<(String, String)>, Box<dyn Error + Send + Sync>> {
+        self.database
+            .execute(
+                "DELETE FROM oidc_logins WHERE created_at < now() - make_interval(mins => $1)",
+                &[&LOGIN_TIMEOUT_MINUTES],
+            )
+            .await?;
+        let row = self
+            .database
+            .query_opt(
+                "DELETE FROM oidc_logins WHERE state = $1 RETURNING code_verifier, nonce",
+                &[&state],
+            )
+            .await?;
+        let login = match row {
+            Some(row) => Some((row.try_get(0)?, row.try_get(1)?)),
+            None => None,
+        };
+        Ok(login)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for OidcLoginsRepository
+where
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let database = DbPool::from_ref(state).get_owned().await.map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(Self { database })
+    }
+}
@@ -0,0 +1,216 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::{ownership::OwnershipTransfer, role::DocumentVersionRole},
+    services::database::{DbConn, DbPool},
+};
+
+use super::documents::UniqueError;
+
+pub struct OwnershipRepository {
+    database: DbConn,
+}
+
+/// Outcome of taking the ownership of a version away from a user
+#[derive(Debug, PartialEq, Eq)]
+pub enum OwnerRemoval {
+    Removed,
+    NotOwner,
+    /// Every version keeps at least one owner
+    LastOwner,
+}
+
+impl OwnershipRepository {
+    pub async fn offer_transfer(
+        &self,
+        from_user_id: Uuid,
+        to_user_id: Uuid,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<OwnershipTransfer, UniqueError> {
+        let row = self
+            .database
+            .query_one(
+                "
+                INSERT INTO ownership_transfers (transfer_id, document_id, version_id, from_user_id, to_user_id)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING transfer_id, document_id, version_id, from_user_id, to_user_id, created_at
+                ",
+                &[&Uuid::new_v4(), &document_id, &version_id, &from_user_id, &to_user_id],
+            )
+            .await
+            .map_err(|error| {
+                if let Some(db_error) = error.as_db_error() {
+                    if let Some(constraint) = db_error.constraint() {
+                        if constraint == "ownership_transfers_document_id_version_id_to_user_id_key" {
+                            return UniqueError::UniqueValueViolation;
+                        }
+                    }
+                }
+                error.into()
+            })?;
+        let transfer = OwnershipTransfer::try_from(row)?;
+        Ok(transfer)
+    }
+
+    /// Pending transfers offered by or to the user
+    pub async fn get_transfers(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Vec<OwnershipTransfer>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT transfer_id, document_id, version_id, from_user_id, to_user_id, created_at
+                FROM ownership_transfers
+                WHERE from_user_id = $1 OR to_user_id = $1
+                ORDER BY created_at DESC
+                ",
+                &[&user_id],
+            )
+            .await?;
+        let transfers = rows
+            .into_iter()
+            .map(OwnershipTransfer::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(transfers)
+    }
+
+    /// Moves the ownership to the recipient, returns `None` if there is no such transfer for them
+    /// or the offering user is no longer an owner
+    pub async fn accept_transfer(
+        &mut self,
+        user_id: Uuid,
+        transfer_id: Uuid,
+    ) -> Result<Option<OwnershipTransfer>, Box<dyn Error + Send + Sync>> {
+        let transaction = self.database.transaction().await?;
+        let Some(row) = transaction
+            .query_opt(
+                "
+                DELETE FROM ownership_transfers
+                WHERE transfer_id = $1
+                AND to_user_id = $2
+                RETURNING transfer_id, document_id, version_id, from_user_id, to_user_id, created_at
+                ",
+                &[&transfer_id, &user_id],
+            )
+            .await?
+        else {
+            return Ok(None);
+        };
+        let transfer = OwnershipTransfer::try_from(row)?;
+        let owner = i16::from(DocumentVersionRole::Owner);
+        let removed = transaction
+            .execute(
+                "DELETE FROM user_document_version_roles WHERE user_id = $1 AND document_id = $2 AND version_id = $3 AND role_id = $4",
+                &[&transfer.from_user_id, &transfer.document_id, &transfer.version_id, &owner],
+            )
+            .await?;
+        if removed == 0 {
+            transaction.commit().await?;
+            return Ok(None);
+        }
+        transaction
+            .execute(
+                "
+                INSERT INTO user_document_version_roles (user_id, document_id, version_id, role_id)
+                VALUES ($1, $2, $3, $4)
+                ON CONFLICT DO NOTHING
+                ",
+                &[
+                    &transfer.to_user_id,
+                    &transfer.document_id,
+                    &transfer.version_id,
+                    &owner,
+                ],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(Some(transfer))
+    }
+
+    /// Withdraws or declines a transfer offered by or to the user
+    pub async fn cancel_transfer(
+        &self,
+        user_id: Uuid,
+        transfer_id: Uuid,
+    ) -> Result<bool, Box<dyn Error>> {
+        let removed = self
+            .database
+            .execute(
+                "DELETE FROM ownership_transfers WHERE transfer_id = $1 AND (from_user_id = $2 OR to_user_id = $2)",
+                &[&transfer_id, &user_id],
+            )
+            .await?;
+        Ok(removed == 1)
+    }
+
+    pub async fn remove_owner(
+        &mut self,
+        user_id: Uuid,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<OwnerRemoval, Box<dyn Error + Send + Sync>> {
+        let owner = i16::from(DocumentVersionRole::Owner);
+        let transaction = self.database.transaction().await?;
+        let owners = transaction
+            .query(
+                "
+                SELECT user_id FROM user_document_version_roles
+                WHERE document_id = $1 AND version_id = $2 AND role_id = $3
+                FOR UPDATE
+                ",
+                &[&document_id, &version_id, &owner],
+            )
+            .await?
+            .into_iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<Vec<Uuid>, _>>()?;
+        if !owners.contains(&user_id) {
+            return Ok(OwnerRemoval::NotOwner);
+        }
+        if owners.len() == 1 {
+            return Ok(OwnerRemoval::LastOwner);
+        }
+        transaction
+            .execute(
+                "DELETE FROM user_document_version_roles WHERE user_id = $1 AND document_id = $2 AND version_id = $3 AND role_id = $4",
+                &[&user_id, &document_id, &version_id, &owner],
+            )
+            .await?;
+        transaction
+            .execute(
+                "DELETE FROM ownership_transfers WHERE from_user_id = $1 AND document_id = $2 AND version_id = $3",
+                &[&user_id, &document_id, &version_id],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(OwnerRemoval::Removed)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for OwnershipRepository
+where
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let database = DbPool::from_ref(state).get_owned().await.map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(Self { database })
+    }
+}
@@ -5,13 +5,20 @@ use axum::{
     extract::{FromRef, FromRequestParts},
     http::{request::Parts, StatusCode},
 };
+use chrono::{DateTime, Utc};
+use tokio_postgres::{Row, Transaction};
 use tracing::error;
 use uuid::Uuid;
 
 use crate::{
     models::{
-        role::{DocumentVersionRole, Role},
-        user::{PublicUser, PublicUserWithRoles},
+        event::{to_sql, EventType},
+        group::GroupWithRoles,
+        role::{
+            BulkGrant, BulkRevoke, BulkRoleChange, DocumentVersionRole, GrantValidity, Role,
+            VersionSelector,
+        },
+        user::{PublicUserWithRoles, UserProfile},
     },
     services::database::{DbConn, DbPool},
 };
@@ -20,7 +27,18 @@ pub struct PermissionRepository {
     database: DbConn,
 }
 
+/// Outcome of a bulk role change, nothing is changed unless the caller owns every selected version
+pub enum BulkOutcome {
+    Changed(Vec<BulkRoleChange>),
+    NoVersions,
+    NotOwner,
+}
+
 impl PermissionRepository {
+    pub fn new(database: DbConn) -> Self {
+        Self { database }
+    }
+
     pub async fn is_admin(&self, user_id: Uuid) -> Result<bool, Box<dyn Error>> {
         let row = self
             .database
@@ -33,38 +51,65 @@ impl PermissionRepository {
         Ok(count >= 1)
     }
 
-    pub async fn is_owner(
-        &self,
-        user_id: Uuid,
-        document_id: Uuid,
-        version_id: Uuid,
-    ) -> Result<bool, Box<dyn Error>> {
-        let row = self
+    /// Returns whether the role was newly granted
+    pub async fn grant_role(&self, user_id: Uuid, role: Role) -> Result<bool, Box<dyn Error>> {
+        let modified = self
             .database
-            .query_one(
-                "SELECT COUNT(*) FROM user_document_version_roles WHERE user_id = $1 AND document_id = $2 AND version_id = $3 AND role_id = $4",
-                &[&user_id, &document_id, &version_id, &i16::from(DocumentVersionRole::Owner)],
+            .execute(
+                "INSERT INTO user_roles (user_id, role_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+                &[&user_id, &i16::from(role)],
             )
             .await?;
-        let count: i64 = row.try_get(0)?;
-        Ok(count >= 1)
+        Ok(modified == 1)
     }
 
+    /// Returns whether the user had the role
+    pub async fn revoke_role(&self, user_id: Uuid, role: Role) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "DELETE FROM user_roles WHERE user_id = $1 AND role_id = $2",
+                &[&user_id, &i16::from(role)],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    /// Grants a role, optionally only valid from or until a point in time.
+    /// Granting a role the user already holds replaces its validity window, e.g. to revive an expired grant.
+    /// Returns whether the role was newly granted rather than updated
     pub async fn grant_document_version_role(
         &self,
         user_id: Uuid,
         document_id: Uuid,
         version_id: Uuid,
         role: DocumentVersionRole,
+        valid_from: Option<DateTime<Utc>>,
+        valid_until: Option<DateTime<Utc>>,
     ) -> Result<bool, Box<dyn Error + Send + Sync>> {
-        let modified = self
+        // xmax is only set on rows the upsert updated
+        let inserted = self
             .database
-            .execute(
-                "INSERT INTO user_document_version_roles VALUES ($1, $2, $3, $4)",
-                &[&user_id, &document_id, &version_id, &i16::from(role)],
+            .query_one(
+                "
+                INSERT INTO user_document_version_roles (user_id, document_id, version_id, role_id, valid_from, valid_until)
+                VALUES ($1, $2, $3, $4, $5, $6)
+                ON CONFLICT (user_id, document_id, version_id, role_id) DO UPDATE
+                SET valid_from = EXCLUDED.valid_from, valid_until = EXCLUDED.valid_until
+                RETURNING xmax = 0
+                ",
+                &[
+                    &user_id,
+                    &document_id,
+                    &version_id,
+                    &i16::from(role),
+                    &valid_from,
+                    &valid_until,
+                ],
             )
-            .await?;
-        Ok(modified == 1)
+            .await?
+            .try_get(0)?;
+        Ok(inserted)
     }
 
     pub async fn revoke_document_version_role(
@@ -92,7 +137,16 @@ impl PermissionRepository {
         let rows = self
             .database
             .query(
-                "SELECT u.user_id, u.username, array_agg(r.role_id) FROM user_document_version_roles r JOIN users u ON r.user_id = u.user_id WHERE r.document_id = $1 AND r.version_id = $2 GROUP BY (u.user_id, u.username)",
+                "
+                SELECT u.user_id, u.username, u.display_name, array_agg(r.role_id)
+                FROM user_document_version_roles r
+                JOIN users u ON r.user_id = u.user_id
+                WHERE r.document_id = $1
+                AND r.version_id = $2
+                AND (r.valid_from IS NULL OR r.valid_from <= now())
+                AND (r.valid_until IS NULL OR r.valid_until > now())
+                GROUP BY u.user_id
+                ",
                 &[&document_id, &version_id],
             )
             .await?;
@@ -113,13 +167,13 @@ impl PermissionRepository {
             .database
             .query_one(
                 "
-                SELECT u.user_id, u.username, array_agg(r.role_id)
-                FROM user_document_version_roles r
+                SELECT u.user_id, u.username, u.display_name, array_agg(r.role_id)
+                FROM effective_document_version_roles r
                 JOIN users u ON r.user_id = u.user_id
                 WHERE r.user_id = $1
                 AND r.document_id = $2
                 AND r.version_id = $3
-                GROUP BY (u.user_id, u.username)
+                GROUP BY u.user_id
                 ",
                 &[&user_id, &document_id, &version_id],
             )
@@ -128,39 +182,669 @@ impl PermissionRepository {
         Ok(user)
     }
 
-    pub async fn does_user_have_document_version_roles(
+    /// Roles held on a version, directly or through groups
+    pub async fn get_document_version_roles(
         &self,
         user_id: Uuid,
         document_id: Uuid,
         version_id: Uuid,
-        roles: &[DocumentVersionRole],
-    ) -> Result<bool, Box<dyn Error>> {
-        let roles: Vec<i16> = roles.iter().map(|r| i16::from(*r)).collect();
-        let row = self
+    ) -> Result<Vec<DocumentVersionRole>, Box<dyn Error>> {
+        let rows = self
             .database
-            .query_one(
+            .query(
                 "
-                SELECT count(*)
-                FROM user_document_version_roles
+                SELECT DISTINCT role_id
+                FROM effective_document_version_roles
                 WHERE user_id = $1
                 AND document_id = $2
                 AND version_id = $3
-                AND role_id = ANY($4)",
-                &[&user_id, &document_id, &version_id, &roles],
+                ",
+                &[&user_id, &document_id, &version_id],
             )
             .await?;
-        let count: i64 = row.try_get(0)?;
-        Ok(count >= 1)
+        Self::roles_from_rows(rows)
+    }
+
+    /// Roles held on any version of a document
+    pub async fn get_document_roles(
+        &self,
+        user_id: Uuid,
+        document_id: Uuid,
+    ) -> Result<Vec<DocumentVersionRole>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT DISTINCT role_id
+                FROM effective_document_version_roles
+                WHERE user_id = $1
+                AND document_id = $2
+                ",
+                &[&user_id, &document_id],
+            )
+            .await?;
+        Self::roles_from_rows(rows)
+    }
+
+    /// Roles held on every version of a document
+    pub async fn get_document_wide_roles(
+        &self,
+        user_id: Uuid,
+        document_id: Uuid,
+    ) -> Result<Vec<DocumentVersionRole>, Box<dyn Error>> {
+        let versions = self
+            .database
+            .query(
+                "SELECT version_id FROM document_versions WHERE document_id = $1",
+                &[&document_id],
+            )
+            .await?
+            .into_iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<Vec<Uuid>, _>>()?;
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT DISTINCT version_id, role_id
+                FROM effective_document_version_roles
+                WHERE user_id = $1
+                AND document_id = $2
+                ",
+                &[&user_id, &document_id],
+            )
+            .await?;
+        let mut held = vec![];
+        for row in rows {
+            let role_id: i16 = row.try_get(1)?;
+            let role = DocumentVersionRole::try_from(role_id)
+                .map_err(|v| format!("Unknown role {}", v))?;
+            held.push((row.try_get(0)?, role));
+        }
+        Ok(DocumentVersionRole::held_on_every_version(&versions, &held))
+    }
+
+    /// Whether the user holds a role on every one of the document versions
+    pub async fn can_see_document_versions(
+        &self,
+        user_id: Uuid,
+        document_version_ids: &[(Uuid, Uuid)],
+    ) -> Result<bool, Box<dyn Error>> {
+        let (document_ids, version_ids): (Vec<Uuid>, Vec<Uuid>) =
+            document_version_ids.iter().copied().unzip();
+        let row = self
+            .database
+            .query_one(
+                "
+                SELECT COUNT(*)
+                FROM unnest($2::uuid[], $3::uuid[]) AS e(document_id, version_id)
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM effective_document_version_roles r
+                    WHERE r.user_id = $1
+                    AND r.document_id = e.document_id
+                    AND r.version_id = e.version_id
+                )
+                ",
+                &[&user_id, &document_ids, &version_ids],
+            )
+            .await?;
+        let hidden: i64 = row.try_get(0)?;
+        Ok(hidden == 0)
+    }
+
+    fn roles_from_rows(rows: Vec<Row>) -> Result<Vec<DocumentVersionRole>, Box<dyn Error>> {
+        let mut roles = vec![];
+        for row in rows {
+            let role_id: i16 = row.try_get(0)?;
+            roles.push(
+                DocumentVersionRole::try_from(role_id)
+                    .map_err(|v| format!("Unknown role {}", v))?,
+            );
+        }
+        Ok(roles)
+    }
+
+    pub async fn grant_document_version_group_role(
+        &self,
+        group_id: Uuid,
+        document_id: Uuid,
+        version_id: Uuid,
+        role: DocumentVersionRole,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let modified = self
+            .database
+            .execute(
+                "INSERT INTO group_document_version_roles VALUES ($1, $2, $3, $4) ON CONFLICT DO NOTHING",
+                &[&group_id, &document_id, &version_id, &i16::from(role)],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    pub async fn revoke_document_version_group_role(
+        &self,
+        group_id: Uuid,
+        document_id: Uuid,
+        version_id: Uuid,
+        role: DocumentVersionRole,
+    ) -> Result<bool, Box<dyn Error + Send + Sync>> {
+        let modified = self
+            .database
+            .execute(
+                "DELETE FROM group_document_version_roles WHERE group_id = $1 AND document_id = $2 AND version_id = $3 AND role_id = $4",
+                &[&group_id, &document_id, &version_id, &i16::from(role)],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    pub async fn get_document_version_groups(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<Vec<GroupWithRoles>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT g.group_id, g.group_name, g.created_at, array_agg(r.role_id)
+                FROM group_document_version_roles r
+                JOIN groups g ON r.group_id = g.group_id
+                WHERE r.document_id = $1
+                AND r.version_id = $2
+                GROUP BY g.group_id
+                ",
+                &[&document_id, &version_id],
+            )
+            .await?;
+        let groups = rows
+            .into_iter()
+            .map(GroupWithRoles::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(groups)
+    }
+
+    /// Deletes grants past their validity and notifies their holders,
+    /// returns the number of removed grants
+    pub async fn remove_expired_document_version_roles(
+        &mut self,
+    ) -> Result<usize, Box<dyn Error + Send + Sync>> {
+        let transaction = self.database.transaction().await?;
+        let expired = transaction
+            .query(
+                "
+                DELETE FROM user_document_version_roles
+                WHERE valid_until <= now()
+                RETURNING user_id, document_id, version_id, role_id
+                ",
+                &[],
+            )
+            .await?;
+        for row in &expired {
+            let user_id: Uuid = row.try_get(0)?;
+            let document_id: Uuid = row.try_get(1)?;
+            let version_id: Uuid = row.try_get(2)?;
+            let role_id: i16 = row.try_get(3)?;
+            let role = DocumentVersionRole::try_from(role_id)
+                .map_err(|v| format!("Unknown role {}", v))?;
+            let (event_type, role_id, state_id) = to_sql(&EventType::RoleRemoved(role));
+            transaction
+                .execute(
+                    "
+                    INSERT INTO events (event_id, user_id, document_id, version_id, event_type, role_id, state_id)
+                    VALUES ($1, $2, $3, $4, $5, $6, $7)
+                    ",
+                    &[
+                        &Uuid::new_v4(),
+                        &user_id,
+                        &document_id,
+                        &version_id,
+                        &event_type,
+                        &role_id,
+                        &state_id,
+                    ],
+                )
+                .await?;
+        }
+        transaction.commit().await?;
+        Ok(expired.len())
+    }
+
+    /// Grants the role to every user on the selected versions in one transaction,
+    /// users get a single event covering the versions they gained the role on
+    pub async fn bulk_grant(
+        &mut self,
+        owner_id: Uuid,
+        document_id: Uuid,
+        grant: &BulkGrant,
+    ) -> Result<BulkOutcome, Box<dyn Error + Send + Sync>> {
+        let transaction = self.database.transaction().await?;
+        let version_ids =
+            match Self::select_owned_versions(&transaction, owner_id, document_id, grant.versions)
+                .await?
+            {
+                Ok(version_ids) => version_ids,
+                Err(outcome) => return Ok(outcome),
+            };
+        let GrantValidity {
+            valid_from,
+            valid_until,
+        } = grant.validity;
+        let mut changes = vec![];
+        for user_id in &grant.users {
+            let rows = transaction
+                .query(
+                    "
+                    INSERT INTO user_document_version_roles (user_id, document_id, version_id, role_id, valid_from, valid_until)
+                    SELECT $1, $2, v, $3, $4, $5
+                    FROM unnest($6::uuid[]) v
+                    ON CONFLICT (user_id, document_id, version_id, role_id) DO UPDATE
+                    SET valid_from = EXCLUDED.valid_from, valid_until = EXCLUDED.valid_until
+                    RETURNING version_id, xmax = 0
+                    ",
+                    &[
+                        user_id,
+                        &document_id,
+                        &i16::from(grant.role),
+                        &valid_from,
+                        &valid_until,
+                        &version_ids,
+                    ],
+                )
+                .await?;
+            let mut granted = vec![];
+            let mut updated = vec![];
+            for row in rows {
+                match row.try_get(1)? {
+                    true => granted.push(row.try_get(0)?),
+                    false => updated.push(row.try_get(0)?),
+                }
+            }
+            if !granted.is_empty() {
+                Self::insert_bulk_event(
+                    &transaction,
+                    *user_id,
+                    document_id,
+                    &granted,
+                    EventType::RolesAdded(grant.role),
+                )
+                .await?;
+            }
+            changes.push(BulkRoleChange {
+                user_id: *user_id,
+                versions: granted,
+                updated_versions: updated,
+                document_roles: vec![],
+                retained_roles: vec![],
+            });
+        }
+        transaction.commit().await?;
+        Ok(BulkOutcome::Changed(changes))
+    }
+
+    /// Revokes the role, or every role but owner, from every user on the selected versions
+    /// in one transaction, users get a single event covering the versions they lost roles on
+    pub async fn bulk_revoke(
+        &mut self,
+        owner_id: Uuid,
+        document_id: Uuid,
+        revoke: &BulkRevoke,
+    ) -> Result<BulkOutcome, Box<dyn Error + Send + Sync>> {
+        let transaction = self.database.transaction().await?;
+        let version_ids =
+            match Self::select_owned_versions(&transaction, owner_id, document_id, revoke.versions)
+                .await?
+            {
+                Ok(version_ids) => version_ids,
+                Err(outcome) => return Ok(outcome),
+            };
+        let role = revoke.role.map(i16::from);
+        let mut changes = vec![];
+        for user_id in &revoke.users {
+            let revoked = transaction
+                .query(
+                    "
+                    DELETE FROM user_document_version_roles
+                    WHERE user_id = $1
+                    AND document_id = $2
+                    AND version_id = ANY($3)
+                    AND role_id <> $4
+                    AND ($5::smallint IS NULL OR role_id = $5)
+                    RETURNING version_id
+                    ",
+                    &[
+                        user_id,
+                        &document_id,
+                        &version_ids,
+                        &i16::from(DocumentVersionRole::Owner),
+                        &role,
+                    ],
+                )
+                .await?;
+            let mut revoked = revoked
+                .into_iter()
+                .map(|row| row.try_get(0))
+                .collect::<Result<Vec<Uuid>, _>>()?;
+            // Document roles cover every version, so they only go when all versions are selected
+            let document_roles = if matches!(revoke.versions, VersionSelector::All) {
+                let rows = transaction
+                    .query(
+                        "
+                        DELETE FROM user_document_roles
+                        WHERE user_id = $1
+                        AND document_id = $2
+                        AND role_id <> $3
+                        AND ($4::smallint IS NULL OR role_id = $4)
+                        RETURNING role_id
+                        ",
+                        &[
+                            user_id,
+                            &document_id,
+                            &i16::from(DocumentVersionRole::Owner),
+                            &role,
+                        ],
+                    )
+                    .await?;
+                Self::roles_from_rows(rows).map_err(|e| e.to_string())?
+            } else {
+                vec![]
+            };
+            if !document_roles.is_empty() {
+                revoked.extend(&version_ids);
+            }
+            revoked.sort();
+            revoked.dedup();
+            let retained = transaction
+                .query(
+                    "
+                    SELECT DISTINCT role_id
+                    FROM effective_document_version_roles
+                    WHERE user_id = $1
+                    AND document_id = $2
+                    AND version_id = ANY($3)
+                    AND role_id <> $4
+                    AND ($5::smallint IS NULL OR role_id = $5)
+                    ",
+                    &[
+                        user_id,
+                        &document_id,
+                        &version_ids,
+                        &i16::from(DocumentVersionRole::Owner),
+                        &role,
+                    ],
+                )
+                .await?;
+            let retained_roles = Self::roles_from_rows(retained).map_err(|e| e.to_string())?;
+            if !revoked.is_empty() {
+                Self::insert_bulk_event(
+                    &transaction,
+                    *user_id,
+                    document_id,
+                    &revoked,
+                    EventType::RolesRemoved(revoke.role),
+                )
+                .await?;
+            }
+            changes.push(BulkRoleChange {
+                user_id: *user_id,
+                versions: revoked,
+                updated_versions: vec![],
+                document_roles,
+                retained_roles,
+            });
+        }
+        transaction.commit().await?;
+        Ok(BulkOutcome::Changed(changes))
     }
 
-    pub async fn get_all_users(&self) -> Result<Vec<PublicUser>, Box<dyn Error>> {
+    /// Versions of the document matching the selector, or the outcome to stop with
+    /// if there are none or the user doesn't own all of them
+    async fn select_owned_versions<'a>(
+        db: &Transaction<'a>,
+        user_id: Uuid,
+        document_id: Uuid,
+        selector: VersionSelector,
+    ) -> Result<Result<Vec<Uuid>, BulkOutcome>, tokio_postgres::Error> {
+        let (heads, state) = match selector {
+            VersionSelector::All => (false, None),
+            VersionSelector::Heads => (true, None),
+            VersionSelector::State(state) => (false, Some(i16::from(state))),
+        };
+        let rows = db
+            .query(
+                "
+                SELECT v.version_id, EXISTS (
+                    SELECT 1 FROM effective_document_version_roles r
+                    WHERE r.user_id = $2
+                    AND r.document_id = v.document_id
+                    AND r.version_id = v.version_id
+                    AND r.role_id = $5
+                )
+                FROM document_versions v
+                WHERE v.document_id = $1
+                AND (NOT $3 OR NOT EXISTS (
+                    SELECT 1 FROM documents_dependencies c
+                    WHERE c.document_id = v.document_id
+                    AND c.parent_version_id = v.version_id
+                ))
+                AND ($4::smallint IS NULL OR v.version_state = $4)
+                ORDER BY v.created_at
+                ",
+                &[
+                    &document_id,
+                    &user_id,
+                    &heads,
+                    &state,
+                    &i16::from(DocumentVersionRole::Owner),
+                ],
+            )
+            .await?;
+        if rows.is_empty() {
+            return Ok(Err(BulkOutcome::NoVersions));
+        }
+        let mut version_ids = vec![];
+        for row in rows {
+            let owned: bool = row.try_get(1)?;
+            if !owned {
+                return Ok(Err(BulkOutcome::NotOwner));
+            }
+            version_ids.push(row.try_get(0)?);
+        }
+        Ok(Ok(version_ids))
+    }
+
+    async fn insert_bulk_event<'a>(
+        db: &Transaction<'a>,
+        user_id: Uuid,
+        document_id: Uuid,
+        version_ids: &[Uuid],
+        event_type: EventType,
+    ) -> Result<(), tokio_postgres::Error> {
+        let (event_type, role_id, state_id) = to_sql(&event_type);
+        db.execute(
+            "
+            INSERT INTO events (event_id, user_id, document_id, version_id, event_type, role_id, state_id, version_ids)
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            ",
+            &[
+                &Uuid::new_v4(),
+                &user_id,
+                &document_id,
+                &version_ids[0],
+                &event_type,
+                &role_id,
+                &state_id,
+                &version_ids,
+            ],
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Grants a role on every current and future version of a document,
+    /// returns the versions it now applies to or `None` if the user already had it
+    pub async fn grant_document_role(
+        &mut self,
+        user_id: Uuid,
+        document_id: Uuid,
+        role: DocumentVersionRole,
+    ) -> Result<Option<Vec<Uuid>>, Box<dyn Error + Send + Sync>> {
+        let transaction = self.database.transaction().await?;
+        let modified = transaction
+            .execute(
+                "
+                INSERT INTO user_document_roles (user_id, document_id, role_id)
+                SELECT user_id, $2, $3 FROM users WHERE user_id = $1 AND deleted_at IS NULL
+                ON CONFLICT DO NOTHING
+                ",
+                &[&user_id, &document_id, &i16::from(role)],
+            )
+            .await?;
+        if modified != 1 {
+            return Ok(None);
+        }
+        let versions = transaction
+            .query(
+                "SELECT version_id FROM document_versions WHERE document_id = $1",
+                &[&document_id],
+            )
+            .await?
+            .into_iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<_, _>>()?;
+        transaction.commit().await?;
+        Ok(Some(versions))
+    }
+
+    /// Revokes a document role, returns the versions it applied to or `None` if the user did not have it
+    pub async fn revoke_document_role(
+        &mut self,
+        user_id: Uuid,
+        document_id: Uuid,
+        role: DocumentVersionRole,
+    ) -> Result<Option<Vec<Uuid>>, Box<dyn Error + Send + Sync>> {
+        let transaction = self.database.transaction().await?;
+        let modified = transaction
+            .execute(
+                "DELETE FROM user_document_roles WHERE user_id = $1 AND document_id = $2 AND role_id = $3",
+                &[&user_id, &document_id, &i16::from(role)],
+            )
+            .await?;
+        if modified != 1 {
+            return Ok(None);
+        }
+        let versions = transaction
+            .query(
+                "SELECT version_id FROM document_versions WHERE document_id = $1",
+                &[&document_id],
+            )
+            .await?
+            .into_iter()
+            .map(|row| row.try_get(0))
+            .collect::<Result<_, _>>()?;
+        transaction.commit().await?;
+        Ok(Some(versions))
+    }
+
+    /// Members holding roles on the whole document
+    pub async fn get_document_users(
+        &self,
+        document_id: Uuid,
+    ) -> Result<Vec<PublicUserWithRoles>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT u.user_id, u.username, u.display_name, array_agg(r.role_id)
+                FROM user_document_roles r
+                JOIN users u ON r.user_id = u.user_id
+                WHERE r.document_id = $1
+                GROUP BY u.user_id
+                ",
+                &[&document_id],
+            )
+            .await?;
+        let users = rows
+            .into_iter()
+            .map(PublicUserWithRoles::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(users)
+    }
+
+    /// Members granted their roles on every new version of a document
+    pub async fn get_document_default_members(
+        &self,
+        document_id: Uuid,
+    ) -> Result<Vec<PublicUserWithRoles>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT u.user_id, u.username, u.display_name, array_agg(d.role_id)
+                FROM document_default_members d
+                JOIN users u ON d.user_id = u.user_id
+                WHERE d.document_id = $1
+                GROUP BY u.user_id
+                ",
+                &[&document_id],
+            )
+            .await?;
+        let users = rows
+            .into_iter()
+            .map(PublicUserWithRoles::try_from)
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(users)
+    }
+
+    /// Returns whether the default member was newly added
+    pub async fn add_document_default_member(
+        &self,
+        document_id: Uuid,
+        user_id: Uuid,
+        role: DocumentVersionRole,
+    ) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "
+                INSERT INTO document_default_members (document_id, user_id, role_id)
+                SELECT $1, user_id, $3 FROM users WHERE user_id = $2 AND deleted_at IS NULL
+                ON CONFLICT DO NOTHING
+                ",
+                &[&document_id, &user_id, &i16::from(role)],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    /// Returns whether the user was a default member with the role
+    pub async fn remove_document_default_member(
+        &self,
+        document_id: Uuid,
+        user_id: Uuid,
+        role: DocumentVersionRole,
+    ) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "DELETE FROM document_default_members WHERE document_id = $1 AND user_id = $2 AND role_id = $3",
+                &[&document_id, &user_id, &i16::from(role)],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    pub async fn get_all_users(&self) -> Result<Vec<UserProfile>, Box<dyn Error>> {
         let users = self
             .database
-            .query("SELECT user_id, username FROM users", &[])
+            .query(
+                "SELECT user_id, username, display_name FROM users WHERE deleted_at IS NULL",
+                &[],
+            )
             .await?;
         let users = users
             .into_iter()
-            .map(PublicUser::try_from)
+            .map(UserProfile::try_from)
             .collect::<Result<Vec<_>, _>>()?;
         Ok(users)
     }
@@ -182,3 +866,114 @@ where
         Ok(Self { database })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use chrono::{Duration, Utc};
+    use uuid::Uuid;
+
+    use super::{BulkOutcome, PermissionRepository};
+    use crate::{
+        models::role::{
+            BulkGrant,
+            DocumentVersionRole::{Owner, Viewer},
+            GrantValidity, VersionSelector,
+        },
+        services::database::testing::TestDatabase,
+    };
+
+    const D: &str = "65a45040-f418-11ed-a05b-0242ac120003";
+    const V: &str = "88c2e78e-f419-11ed-a05b-0242ac120003";
+    const W: &str = "65a452ca-f418-11ed-a05b-0242ac120003";
+    const E: &str = "65a45428-f418-11ed-a05b-0242ac120003";
+
+    #[tokio::test]
+    async fn granting_again_revives_an_expired_role() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let permissions = PermissionRepository::new(db.connection().await);
+        let (d, v, e) = (
+            Uuid::parse_str(D).unwrap(),
+            Uuid::parse_str(V).unwrap(),
+            Uuid::parse_str(E).unwrap(),
+        );
+        let expired = (
+            Some(Utc::now() - Duration::days(2)),
+            Some(Utc::now() - Duration::days(1)),
+        );
+        let granted = permissions
+            .grant_document_version_role(e, d, v, Viewer, expired.0, expired.1)
+            .await
+            .unwrap();
+        assert!(granted);
+        let roles = permissions.get_document_version_roles(e, d, v).await;
+        assert!(roles.unwrap().is_empty());
+        let granted = permissions
+            .grant_document_version_role(e, d, v, Viewer, None, None)
+            .await
+            .unwrap();
+        assert!(!granted);
+        let roles = permissions.get_document_version_roles(e, d, v).await;
+        assert_eq!(roles.unwrap(), vec![Viewer]);
+        db.finish().await;
+    }
+
+    #[tokio::test]
+    async fn bulk_granting_again_revives_an_expired_role() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let mut permissions = PermissionRepository::new(db.connection().await);
+        let (d, v, w, e) = (
+            Uuid::parse_str(D).unwrap(),
+            Uuid::parse_str(V).unwrap(),
+            Uuid::parse_str(W).unwrap(),
+            Uuid::parse_str(E).unwrap(),
+        );
+        let versions: Vec<Uuid> = db
+            .connection()
+            .await
+            .query(
+                "SELECT version_id FROM document_versions WHERE document_id = $1",
+                &[&d],
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|row| row.get(0))
+            .collect();
+        for version_id in &versions {
+            permissions
+                .grant_document_version_role(e, d, *version_id, Owner, None, None)
+                .await
+                .unwrap();
+        }
+        permissions
+            .grant_document_version_role(
+                w,
+                d,
+                v,
+                Viewer,
+                Some(Utc::now() - Duration::days(2)),
+                Some(Utc::now() - Duration::days(1)),
+            )
+            .await
+            .unwrap();
+        let grant = BulkGrant {
+            users: vec![w],
+            role: Viewer,
+            versions: VersionSelector::All,
+            validity: GrantValidity::default(),
+        };
+        let BulkOutcome::Changed(changes) = permissions.bulk_grant(e, d, &grant).await.unwrap()
+        else {
+            panic!("Bulk grant was refused");
+        };
+        assert_eq!(changes[0].updated_versions, vec![v]);
+        assert_eq!(changes[0].versions.len(), versions.len() - 1);
+        let roles = permissions.get_document_version_roles(w, d, v).await;
+        assert!(roles.unwrap().contains(&Viewer));
+        db.finish().await;
+    }
+}
@@ -0,0 +1,162 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use chrono::{Duration, Utc};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::personal_token::{
+        AuthenticatedPersonalToken, CreatedPersonalToken, PersonalToken, TokenScope,
+        PERSONAL_TOKEN_PREFIX,
+    },
+    services::{
+        auth::secrets::{generate_secret, hash_secret},
+        database::{DbConn, DbPool},
+    },
+};
+
+use super::documents::UniqueError;
+
+pub struct PersonalTokensRepository {
+    database: DbConn,
+}
+
+impl PersonalTokensRepository {
+    pub async fn create_token(
+        &self,
+        user_id: Uuid,
+        token_name: String,
+        scopes: Vec<TokenScope>,
+        lifetime: Duration,
+    ) -> Result<CreatedPersonalToken, UniqueError> {
+        let token_id = Uuid::new_v4();
+        let token = format!("{}{}", PERSONAL_TOKEN_PREFIX, generate_secret());
+        let created_at = Utc::now();
+        let expires_at = created_at + lifetime;
+        let scope_ids: Vec<i16> = scopes.iter().map(|s| i16::from(*s)).collect();
+        self.database
+            .execute(
+                "
+                INSERT INTO personal_tokens (token_id, user_id, token_name, token_hash, scopes, created_at, expires_at)
+                VALUES ($1, $2, $3, $4, $5, $6, $7)
+                ",
+                &[
+                    &token_id,
+                    &user_id,
+                    &token_name,
+                    &hash_secret(&token),
+                    &scope_ids,
+                    &created_at,
+                    &expires_at,
+                ],
+            )
+            .await
+            .map_err(|error| {
+                if let Some(db_error) = error.as_db_error() {
+                    if let Some(constraint) = db_error.constraint() {
+                        if constraint == "personal_tokens_user_id_token_name_key" {
+                            return UniqueError::UniqueValueViolation;
+                        }
+                    }
+                }
+                error.into()
+            })?;
+        Ok(CreatedPersonalToken {
+            personal_token: PersonalToken {
+                token_id,
+                token_name,
+                scopes,
+                created_at,
+                expires_at,
+                last_used_at: None,
+            },
+            token,
+        })
+    }
+
+    pub async fn get_tokens(&self, user_id: Uuid) -> Result<Vec<PersonalToken>, Box<dyn Error>> {
+        let tokens = self
+            .database
+            .query(
+                "
+                SELECT token_id, token_name, scopes, created_at, expires_at, last_used_at
+                FROM personal_tokens
+                WHERE user_id = $1
+                ORDER BY created_at DESC
+                ",
+                &[&user_id],
+            )
+            .await?;
+        let tokens = tokens
+            .into_iter()
+            .map(PersonalToken::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(tokens)
+    }
+
+    pub async fn revoke_token(
+        &self,
+        user_id: Uuid,
+        token_id: Uuid,
+    ) -> Result<bool, Box<dyn Error>> {
+        let revoked = self
+            .database
+            .execute(
+                "
+                DELETE FROM personal_tokens
+                WHERE token_id = $1
+                AND user_id = $2
+                ",
+                &[&token_id, &user_id],
+            )
+            .await?;
+        Ok(revoked == 1)
+    }
+
+    /// Resolves a presented token and records its use
+    pub async fn authenticate(
+        &self,
+        token: &str,
+    ) -> Result<Option<AuthenticatedPersonalToken>, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_opt(
+                "
+                UPDATE personal_tokens t
+                SET last_used_at = now()
+                FROM users u
+                WHERE t.token_hash = $1
+                AND t.expires_at > now()
+                AND u.user_id = t.user_id
+                AND u.disabled = FALSE
+                RETURNING t.token_id, t.user_id, u.username, t.scopes, t.created_at, t.expires_at
+                ",
+                &[&hash_secret(token)],
+            )
+            .await?;
+        let token = row.map(AuthenticatedPersonalToken::try_from).transpose()?;
+        Ok(token)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for PersonalTokensRepository
+where
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let database = DbPool::from_ref(state).get_owned().await.map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(Self { database })
+    }
+}
@@ -0,0 +1,273 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use chrono::{Duration, Utc};
+use tokio_postgres::Transaction;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    models::session::Session,
+    services::{
+        auth::secrets::{generate_secret, hash_secret},
+        database::{DbConn, DbPool},
+    },
+};
+
+pub struct SessionsRepository {
+    database: DbConn,
+}
+
+impl SessionsRepository {
+    async fn issue_refresh_token<'a>(
+        db: &Transaction<'a>,
+        session_id: Uuid,
+    ) -> Result<String, tokio_postgres::Error> {
+        let refresh_token = generate_secret();
+        db.execute(
+            "INSERT INTO refresh_tokens (token_hash, session_id) VALUES ($1, $2)",
+            &[&hash_secret(&refresh_token), &session_id],
+        )
+        .await?;
+        Ok(refresh_token)
+    }
+
+    pub async fn create_session(
+        &mut self,
+        user_id: Uuid,
+        username: String,
+        lifetime: Duration,
+    ) -> Result<Session, Box<dyn Error>> {
+        let session_id = Uuid::new_v4();
+        let expires_at = Utc::now() + lifetime;
+        let transaction = self.database.transaction().await?;
+        transaction
+            .execute(
+                "INSERT INTO sessions (session_id, user_id, expires_at) VALUES ($1, $2, $3)",
+                &[&session_id, &user_id, &expires_at],
+            )
+            .await?;
+        let refresh_token = Self::issue_refresh_token(&transaction, session_id).await?;
+        transaction.commit().await?;
+        Ok(Session {
+            session_id,
+            user_id,
+            username,
+            refresh_token,
+        })
+    }
+
+    /// Exchanges a refresh token for a new one.
+    /// Presenting an already used token revokes the whole session.
+    pub async fn refresh_session(
+        &mut self,
+        refresh_token: &str,
+        lifetime: Duration,
+    ) -> Result<Option<Session>, Box<dyn Error>> {
+        let transaction = self.database.transaction().await?;
+        let row = transaction
+            .query_opt(
+                "
+                SELECT r.session_id, r.used, s.user_id, u.username
+                FROM refresh_tokens r
+                JOIN sessions s ON s.session_id = r.session_id
+                JOIN users u ON u.user_id = s.user_id
+                WHERE r.token_hash = $1
+                AND s.revoked = FALSE
+                AND u.disabled = FALSE
+                AND s.expires_at > now()
+                FOR UPDATE OF r, s
+                ",
+                &[&hash_secret(refresh_token)],
+            )
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let session_id: Uuid = row.try_get(0)?;
+        let used: bool = row.try_get(1)?;
+        let user_id: Uuid = row.try_get(2)?;
+        let username: String = row.try_get(3)?;
+        if used {
+            warn!(
+                { session_id = session_id.to_string() },
+                "Refresh token reused, revoking session"
+            );
+            transaction
+                .execute(
+                    "UPDATE sessions SET revoked = TRUE WHERE session_id = $1",
+                    &[&session_id],
+                )
+                .await?;
+            transaction.commit().await?;
+            return Ok(None);
+        }
+        transaction
+            .execute(
+                "UPDATE refresh_tokens SET used = TRUE WHERE token_hash = $1",
+                &[&hash_secret(refresh_token)],
+            )
+            .await?;
+        transaction
+            .execute(
+                "UPDATE sessions SET expires_at = $1 WHERE session_id = $2",
+                &[&(Utc::now() + lifetime), &session_id],
+            )
+            .await?;
+        let refresh_token = Self::issue_refresh_token(&transaction, session_id).await?;
+        transaction.commit().await?;
+        Ok(Some(Session {
+            session_id,
+            user_id,
+            username,
+            refresh_token,
+        }))
+    }
+
+    pub async fn revoke_session(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<bool, Box<dyn Error>> {
+        let revoked = self
+            .database
+            .execute(
+                "UPDATE sessions SET revoked = TRUE WHERE session_id = $1 AND user_id = $2",
+                &[&session_id, &user_id],
+            )
+            .await?;
+        Ok(revoked == 1)
+    }
+
+    /// Revokes every session of a user apart from the current one
+    pub async fn revoke_other_sessions(
+        &self,
+        user_id: Uuid,
+        session_id: Uuid,
+    ) -> Result<u64, Box<dyn Error>> {
+        let revoked = self
+            .database
+            .execute(
+                "UPDATE sessions SET revoked = TRUE WHERE user_id = $1 AND session_id != $2 AND revoked = FALSE",
+                &[&user_id, &session_id],
+            )
+            .await?;
+        Ok(revoked)
+    }
+
+    /// Whether a session is still usable, which also requires its user to be enabled
+    pub async fn is_session_active(&self, session_id: Uuid) -> Result<bool, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_one(
+                "
+                SELECT count(*)
+                FROM sessions s
+                JOIN users u ON u.user_id = s.user_id
+                WHERE s.session_id = $1
+                AND s.revoked = FALSE
+                AND s.expires_at > now()
+                AND u.disabled = FALSE
+                ",
+                &[&session_id],
+            )
+            .await?;
+        let count: i64 = row.try_get(0)?;
+        Ok(count >= 1)
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for SessionsRepository
+where
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let database = DbPool::from_ref(state).get_owned().await.map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(Self { database })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+    use uuid::Uuid;
+
+    use super::SessionsRepository;
+    use crate::services::database::testing::TestDatabase;
+
+    const Q: &str = "65a45040-f418-11ed-a05b-0242ac120003";
+
+    #[tokio::test]
+    async fn refresh_rotates_token() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let mut sessions = SessionsRepository {
+            database: db.connection().await,
+        };
+        let lifetime = Duration::hours(1);
+        let session = sessions
+            .create_session(Uuid::parse_str(Q).unwrap(), "q".to_owned(), lifetime)
+            .await
+            .unwrap();
+        let first = session.refresh_token;
+        let second = sessions.refresh_session(&first, lifetime).await.unwrap();
+        let second = second.unwrap();
+        assert_eq!(second.session_id, session.session_id);
+        assert_ne!(second.refresh_token, first);
+        let third = sessions
+            .refresh_session(&second.refresh_token, lifetime)
+            .await
+            .unwrap();
+        assert!(third.is_some());
+        assert!(sessions
+            .is_session_active(session.session_id)
+            .await
+            .unwrap());
+        db.finish().await;
+    }
+
+    #[tokio::test]
+    async fn reusing_rotated_token_revokes_session() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let mut sessions = SessionsRepository {
+            database: db.connection().await,
+        };
+        let lifetime = Duration::hours(1);
+        let session = sessions
+            .create_session(Uuid::parse_str(Q).unwrap(), "q".to_owned(), lifetime)
+            .await
+            .unwrap();
+        let first = session.refresh_token;
+        let second = sessions
+            .refresh_session(&first, lifetime)
+            .await
+            .unwrap()
+            .unwrap();
+        let reused = sessions.refresh_session(&first, lifetime).await.unwrap();
+        assert!(reused.is_none());
+        assert!(!sessions
+            .is_session_active(session.session_id)
+            .await
+            .unwrap());
+        let after_reuse = sessions
+            .refresh_session(&second.refresh_token, lifetime)
+            .await
+            .unwrap();
+        assert!(after_reuse.is_none());
+        db.finish().await;
+    }
+}
@@ -0,0 +1,282 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use chrono::{DateTime, Utc};
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::{
+        attachment::File,
+        sensitivity_label::SensitivityLabel,
+        share_link::{CreatedShareLink, ResolvedShareLink, ShareLink, SharedVersion},
+        version_state::DocumentVersionState,
+    },
+    services::{
+        auth::{
+            password::{PasswordCheck, PasswordHashing},
+            secrets::{generate_secret, hash_secret},
+        },
+        content_storage::ContentStorage,
+        database::{DbConn, DbPool},
+    },
+};
+
+pub struct ShareLinksRepository {
+    database: DbConn,
+    password_hashing: PasswordHashing,
+    content_storage: ContentStorage,
+}
+
+impl ShareLinksRepository {
+    /// Creates a link to a published version of a public document, `None` otherwise
+    pub async fn create_link(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+        created_by: Uuid,
+        expires_at: Option<DateTime<Utc>>,
+        password: Option<String>,
+    ) -> Result<Option<CreatedShareLink>, Box<dyn Error + Send + Sync>> {
+        let token = generate_secret();
+        let password_hash = match password {
+            Some(password) => Some(self.password_hashing.hash(&password).await?),
+            None => None,
+        };
+        let row = self
+            .database
+            .query_opt(
+                "
+                INSERT INTO share_links (link_id, document_id, version_id, token_hash, password_hash, created_by, expires_at)
+                SELECT $1, v.document_id, v.version_id, $4, $5, $6, $7
+                FROM document_versions v
+                JOIN documents d ON d.document_id = v.document_id
+                WHERE v.document_id = $2
+                AND v.version_id = $3
+                AND v.version_state = $8
+                AND d.label_id = $9
+                RETURNING link_id, document_id, version_id, created_by, created_at, expires_at, password_hash IS NOT NULL
+                ",
+                &[
+                    &Uuid::new_v4(),
+                    &document_id,
+                    &version_id,
+                    &hash_secret(&token),
+                    &password_hash,
+                    &created_by,
+                    &expires_at,
+                    &i16::from(DocumentVersionState::Published),
+                    &i16::from(SensitivityLabel::Public),
+                ],
+            )
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        Ok(Some(CreatedShareLink {
+            share_link: ShareLink::try_from(row)?,
+            token,
+        }))
+    }
+
+    pub async fn get_links(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<Vec<ShareLink>, Box<dyn Error>> {
+        let links = self
+            .database
+            .query(
+                "
+                SELECT link_id, document_id, version_id, created_by, created_at, expires_at, password_hash IS NOT NULL
+                FROM share_links
+                WHERE document_id = $1
+                AND version_id = $2
+                ORDER BY created_at DESC
+                ",
+                &[&document_id, &version_id],
+            )
+            .await?;
+        let links = links
+            .into_iter()
+            .map(ShareLink::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(links)
+    }
+
+    pub async fn revoke_link(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+        link_id: Uuid,
+    ) -> Result<bool, Box<dyn Error>> {
+        let revoked = self
+            .database
+            .execute(
+                "
+                DELETE FROM share_links
+                WHERE link_id = $1
+                AND document_id = $2
+                AND version_id = $3
+                ",
+                &[&link_id, &document_id, &version_id],
+            )
+            .await?;
+        Ok(revoked == 1)
+    }
+
+    /// Resolves a presented token, links stop working once expired,
+    /// the version is no longer published or the document is labeled above public
+    pub async fn resolve(
+        &self,
+        token: &str,
+    ) -> Result<Option<ResolvedShareLink>, Box<dyn Error + Send + Sync>> {
+        let row = self
+            .database
+            .query_opt(
+                "
+                SELECT l.link_id, l.document_id, l.version_id, l.password_hash
+                FROM share_links l
+                JOIN document_versions v ON v.document_id = l.document_id AND v.version_id = l.version_id
+                JOIN documents d ON d.document_id = l.document_id
+                WHERE l.token_hash = $1
+                AND (l.expires_at IS NULL OR l.expires_at > now())
+                AND v.version_state = $2
+                AND d.label_id = $3
+                ",
+                &[
+                    &hash_secret(token),
+                    &i16::from(DocumentVersionState::Published),
+                    &i16::from(SensitivityLabel::Public),
+                ],
+            )
+            .await?;
+        let link = row.map(ResolvedShareLink::try_from).transpose()?;
+        Ok(link)
+    }
+
+    /// Whether the password opens the link, always true for links without one
+    pub async fn check_password(
+        &self,
+        link: &ResolvedShareLink,
+        password: &str,
+    ) -> Result<bool, argon2::password_hash::Error> {
+        let Some(password_hash) = &link.password_hash else {
+            return Ok(true);
+        };
+        let check = self
+            .password_hashing
+            .check(&link.link_id, None, password, password_hash)
+            .await?;
+        Ok(check != PasswordCheck::Mismatch)
+    }
+
+    pub async fn get_shared_version(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<Option<SharedVersion>, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_opt(
+                "
+                SELECT d.document_name, v.version_name, v.updated_at
+                FROM document_versions v
+                JOIN documents d ON d.document_id = v.document_id
+                WHERE v.document_id = $1
+                AND v.version_id = $2
+                ",
+                &[&document_id, &version_id],
+            )
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let content = self
+            .content_storage
+            .load_version(&*self.database, document_id, version_id)
+            .await?;
+        Ok(Some(SharedVersion {
+            document_name: row.try_get(0)?,
+            version_name: row.try_get(1)?,
+            updated_at: row.try_get(2)?,
+            content,
+        }))
+    }
+
+    pub async fn get_shared_files(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+    ) -> Result<Vec<File>, Box<dyn Error>> {
+        let files = self
+            .database
+            .query(
+                "
+                SELECT f.file_id, f.file_name, f.file_mime_type, f.file_hash
+                FROM file_attachments a
+                JOIN files f ON a.file_id = f.file_id
+                WHERE a.document_id = $1
+                AND a.version_id = $2
+                ",
+                &[&document_id, &version_id],
+            )
+            .await?;
+        let files = files
+            .into_iter()
+            .map(File::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(files)
+    }
+
+    pub async fn is_file_shared(
+        &self,
+        document_id: Uuid,
+        version_id: Uuid,
+        file_id: Uuid,
+    ) -> Result<bool, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_opt(
+                "
+                SELECT 1
+                FROM file_attachments
+                WHERE document_id = $1
+                AND version_id = $2
+                AND file_id = $3
+                ",
+                &[&document_id, &version_id, &file_id],
+            )
+            .await?;
+        Ok(row.is_some())
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ShareLinksRepository
+where
+    PasswordHashing: FromRef<S>,
+    ContentStorage: FromRef<S>,
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let database = DbPool::from_ref(state).get_owned().await.map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        let password_hashing = PasswordHashing::from_ref(state);
+        let content_storage = ContentStorage::from_ref(state);
+        Ok(Self {
+            database,
+            password_hashing,
+            content_storage,
+        })
+    }
+}
@@ -0,0 +1,267 @@
+use std::error::Error;
+
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{request::Parts, StatusCode},
+};
+use tokio_postgres::Transaction;
+use tracing::error;
+use uuid::Uuid;
+
+use crate::{
+    models::totp::UserTotp,
+    services::{
+        auth::{
+            secrets::{generate_secret, hash_secret},
+            totp::{check_code, generate_recovery_code, is_totp_code, RECOVERY_CODE_COUNT},
+        },
+        database::{DbConn, DbPool},
+    },
+};
+
+/// How long a password-verified login may wait for its second step
+const CHALLENGE_TIMEOUT_MINUTES: i32 = 5;
+/// Wrong codes allowed per challenge before the password has to be entered again
+const CHALLENGE_MAX_ATTEMPTS: i16 = 5;
+
+pub struct TotpRepository {
+    database: DbConn,
+}
+
+impl TotpRepository {
+    pub async fn get_totp(&self, user_id: Uuid) -> Result<Option<UserTotp>, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_opt(
+                "SELECT secret, enabled, last_used_step FROM user_totp WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?;
+        let totp = row.map(UserTotp::try_from).transpose()?;
+        Ok(totp)
+    }
+
+    pub async fn is_enabled(&self, user_id: Uuid) -> Result<bool, Box<dyn Error>> {
+        let totp = self.get_totp(user_id).await?;
+        Ok(matches!(totp, Some(totp) if totp.enabled))
+    }
+
+    /// Stores a new secret unless 2FA is already enabled
+    pub async fn start_enrolment(
+        &self,
+        user_id: Uuid,
+        secret: &str,
+    ) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "
+                INSERT INTO user_totp (user_id, secret) VALUES ($1, $2)
+                ON CONFLICT (user_id) DO UPDATE
+                SET secret = EXCLUDED.secret, last_used_step = NULL, created_at = now()
+                WHERE user_totp.enabled = FALSE
+                ",
+                &[&user_id, &secret],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    /// Enables 2FA and replaces any previous recovery codes
+    pub async fn confirm_enrolment(
+        &mut self,
+        user_id: Uuid,
+        step: i64,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let transaction = self.database.transaction().await?;
+        transaction
+            .execute(
+                "UPDATE user_totp SET enabled = TRUE, last_used_step = $2 WHERE user_id = $1",
+                &[&user_id, &step],
+            )
+            .await?;
+        let recovery_codes = Self::replace_recovery_codes(&transaction, user_id).await?;
+        transaction.commit().await?;
+        Ok(recovery_codes)
+    }
+
+    pub async fn regenerate_recovery_codes(
+        &mut self,
+        user_id: Uuid,
+    ) -> Result<Vec<String>, Box<dyn Error>> {
+        let transaction = self.database.transaction().await?;
+        let recovery_codes = Self::replace_recovery_codes(&transaction, user_id).await?;
+        transaction.commit().await?;
+        Ok(recovery_codes)
+    }
+
+    async fn replace_recovery_codes<'a>(
+        db: &Transaction<'a>,
+        user_id: Uuid,
+    ) -> Result<Vec<String>, tokio_postgres::Error> {
+        db.execute(
+            "DELETE FROM totp_recovery_codes WHERE user_id = $1",
+            &[&user_id],
+        )
+        .await?;
+        let recovery_codes: Vec<String> = (0..RECOVERY_CODE_COUNT)
+            .map(|_| generate_recovery_code())
+            .collect();
+        for code in &recovery_codes {
+            db.execute(
+                "INSERT INTO totp_recovery_codes (code_hash, user_id) VALUES ($1, $2)",
+                &[&hash_secret(code), &user_id],
+            )
+            .await?;
+        }
+        Ok(recovery_codes)
+    }
+
+    /// Marks a time step as used, fails if it (or a later one) already was
+    async fn use_step(&self, user_id: Uuid, step: i64) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "
+                UPDATE user_totp
+                SET last_used_step = $2
+                WHERE user_id = $1
+                AND enabled = TRUE
+                AND (last_used_step IS NULL OR last_used_step < $2)
+                ",
+                &[&user_id, &step],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    async fn use_recovery_code(&self, user_id: Uuid, code: &str) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "
+                UPDATE totp_recovery_codes
+                SET used = TRUE
+                WHERE code_hash = $1
+                AND user_id = $2
+                AND used = FALSE
+                ",
+                &[&hash_secret(code), &user_id],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    /// Removes the secret and recovery codes, returns whether anything was removed
+    pub async fn disable(&mut self, user_id: Uuid) -> Result<bool, Box<dyn Error>> {
+        let transaction = self.database.transaction().await?;
+        transaction
+            .execute(
+                "DELETE FROM totp_recovery_codes WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?;
+        transaction
+            .execute(
+                "DELETE FROM login_challenges WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?;
+        let removed = transaction
+            .execute("DELETE FROM user_totp WHERE user_id = $1", &[&user_id])
+            .await?;
+        transaction.commit().await?;
+        Ok(removed == 1)
+    }
+
+    /// Checks a TOTP or recovery code of a user with 2FA enabled, consuming it
+    pub async fn verify_code(&self, user_id: Uuid, code: &str) -> Result<bool, Box<dyn Error>> {
+        if !is_totp_code(code) {
+            return self.use_recovery_code(user_id, code).await;
+        }
+        let Some(totp) = self.get_totp(user_id).await? else {
+            return Ok(false);
+        };
+        if !totp.enabled {
+            return Ok(false);
+        }
+        match check_code(&totp.secret, code, totp.last_used_step) {
+            Some(step) => self.use_step(user_id, step).await,
+            None => Ok(false),
+        }
+    }
+
+    pub async fn create_challenge(&self, user_id: Uuid) -> Result<String, Box<dyn Error>> {
+        let challenge = generate_secret();
+        self.database
+            .execute(
+                "INSERT INTO login_challenges (challenge_hash, user_id) VALUES ($1, $2)",
+                &[&hash_secret(&challenge), &user_id],
+            )
+            .await?;
+        Ok(challenge)
+    }
+
+    /// Counts an attempt at answering a challenge, returning the challenged user's id and name.
+    /// Expired or exhausted challenges yield `None`.
+    pub async fn attempt_challenge(
+        &self,
+        challenge: &str,
+    ) -> Result<Option<(Uuid, String)>, Box<dyn Error>> {
+        self.database
+            .execute(
+                "DELETE FROM login_challenges WHERE created_at < now() - make_interval(mins => $1)",
+                &[&CHALLENGE_TIMEOUT_MINUTES],
+            )
+            .await?;
+        let row = self
+            .database
+            .query_opt(
+                "
+                UPDATE login_challenges c
+                SET attempts = c.attempts + 1
+                FROM users u
+                WHERE c.challenge_hash = $1
+                AND c.attempts < $2
+                AND u.user_id = c.user_id
+                AND u.disabled = FALSE
+                RETURNING c.user_id, u.username
+                ",
+                &[&hash_secret(challenge), &CHALLENGE_MAX_ATTEMPTS],
+            )
+            .await?;
+        let user = match row {
+            Some(row) => Some((row.try_get(0)?, row.try_get(1)?)),
+            None => None,
+        };
+        Ok(user)
+    }
+
+    pub async fn finish_challenge(&self, challenge: &str) -> Result<(), Box<dyn Error>> {
+        self.database
+            .execute(
+                "DELETE FROM login_challenges WHERE challenge_hash = $1",
+                &[&hash_secret(challenge)],
+            )
+            .await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for TotpRepository
+where
+    DbPool: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(_: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let database = DbPool::from_ref(state).get_owned().await.map_err(|e| {
+            error!("{}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        Ok(Self { database })
+    }
+}
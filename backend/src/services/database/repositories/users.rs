@@ -5,27 +5,34 @@ use axum::{
     extract::{FromRef, FromRequestParts},
     http::{request::Parts, StatusCode},
 };
-use sha2::{Digest, Sha256};
-use tracing::error;
+use chrono::{DateTime, Duration, Utc};
+use tracing::{error, info};
 use uuid::Uuid;
 
 use crate::{
-    models::user::User,
-    services::database::{DbConn, DbPool},
+    models::{
+        role::Role,
+        sensitivity_label::SensitivityLabel,
+        user::{AccountDetails, ManagedUser, PasswordReset, UpdateProfile, User},
+        RowError,
+    },
+    services::{
+        auth::{
+            password::{PasswordCheck, PasswordHashing},
+            secrets::{generate_secret, hash_secret},
+        },
+        database::{DbConn, DbPool},
+    },
 };
 
+use super::documents::UniqueError;
+
+/// How long an admin issued password reset stays valid
+const PASSWORD_RESET_HOURS: i64 = 24;
+
 pub struct UsersRepository {
     database: DbConn,
-}
-
-fn hash_password(user_id: &Uuid, password: &str, salt: &Uuid) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(user_id.as_bytes());
-    hasher.update("|");
-    hasher.update(password.as_bytes());
-    hasher.update("|");
-    hasher.update(salt.as_bytes());
-    hex::encode(hasher.finalize())
+    password_hashing: PasswordHashing,
 }
 
 impl UsersRepository {
@@ -35,19 +42,20 @@ impl UsersRepository {
         password: String,
     ) -> Result<User, Box<dyn Error>> {
         let user_id = Uuid::new_v4();
-        let salt = Uuid::new_v4();
-        let password_hash = hash_password(&user_id, &password, &salt);
+        let password_hash = self.password_hashing.hash(&password).await?;
         self.database
             .execute(
-                "INSERT INTO users VALUES ($1, $2,  $3, $4)",
-                &[&user_id, &salt, &username, &password_hash],
+                "INSERT INTO users (user_id, username, password_hash) VALUES ($1, $2, $3)",
+                &[&user_id, &username, &password_hash],
             )
             .await?;
         Ok(User {
             user_id,
-            salt,
+            salt: None,
             username,
             password_hash,
+            disabled: false,
+            locked_until: None,
         })
     }
 
@@ -55,17 +63,455 @@ impl UsersRepository {
         &self,
         username: &str,
         password: &str,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_opt("SELECT * FROM users WHERE username = $1", &[&username])
+            .await?;
+        let Some(row) = row else {
+            self.password_hashing.check_nothing(password).await;
+            return Ok(None);
+        };
+        self.verify_password(row.try_into()?, password).await
+    }
+
+    /// Checks the password of an already authenticated user, e.g. before sensitive changes
+    pub async fn verify_by_id(
+        &self,
+        user_id: Uuid,
+        password: &str,
     ) -> Result<Option<User>, Box<dyn Error>> {
         let user: User = self
             .database
-            .query_one("SELECT * FROM users WHERE username = $1", &[&username])
+            .query_one(
+                "SELECT * FROM users WHERE user_id = $1 AND deleted_at IS NULL",
+                &[&user_id],
+            )
             .await?
             .try_into()?;
-        let password_hash = hash_password(&user.user_id, password, &user.salt);
-        Ok(if password_hash == user.password_hash {
-            Some(user)
-        } else {
-            None
+        self.verify_password(user, password).await
+    }
+
+    async fn verify_password(
+        &self,
+        mut user: User,
+        password: &str,
+    ) -> Result<Option<User>, Box<dyn Error>> {
+        let check = self
+            .password_hashing
+            .check(
+                &user.user_id,
+                user.salt.as_ref(),
+                password,
+                &user.password_hash,
+            )
+            .await?;
+        match check {
+            PasswordCheck::Mismatch => Ok(None),
+            PasswordCheck::Match => Ok(Some(user)),
+            PasswordCheck::MatchOutdated => {
+                let password_hash = self.password_hashing.hash(password).await?;
+                self.database
+                    .execute(
+                        "UPDATE users SET password_hash = $1, salt = NULL WHERE user_id = $2",
+                        &[&password_hash, &user.user_id],
+                    )
+                    .await?;
+                info!(
+                    { user_id = user.user_id.to_string() },
+                    "Upgraded password hash"
+                );
+                user.salt = None;
+                user.password_hash = password_hash;
+                Ok(Some(user))
+            }
+        }
+    }
+
+    pub async fn set_password(&self, user_id: Uuid, password: &str) -> Result<(), Box<dyn Error>> {
+        let password_hash = self.password_hashing.hash(password).await?;
+        self.database
+            .execute(
+                "UPDATE users SET password_hash = $1, salt = NULL WHERE user_id = $2",
+                &[&password_hash, &user_id],
+            )
+            .await?;
+        Ok(())
+    }
+
+    pub async fn get_account(&self, user_id: Uuid) -> Result<AccountDetails, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_one(
+                "
+                SELECT user_id, username, display_name, email
+                FROM users
+                WHERE user_id = $1
+                AND deleted_at IS NULL
+                ",
+                &[&user_id],
+            )
+            .await?;
+        Ok(row.try_into()?)
+    }
+
+    pub async fn update_profile(
+        &self,
+        user_id: Uuid,
+        update: UpdateProfile,
+    ) -> Result<AccountDetails, UniqueError> {
+        let row = self
+            .database
+            .query_one(
+                "
+                UPDATE users
+                SET display_name = CASE WHEN $2 THEN $3 ELSE display_name END,
+                email = CASE WHEN $4 THEN $5 ELSE email END
+                WHERE user_id = $1
+                AND deleted_at IS NULL
+                RETURNING user_id, username, display_name, email
+                ",
+                &[
+                    &user_id,
+                    &update.display_name.is_some(),
+                    &update.display_name.flatten(),
+                    &update.email.is_some(),
+                    &update.email.flatten(),
+                ],
+            )
+            .await
+            .map_err(|error| {
+                if let Some(db_error) = error.as_db_error() {
+                    if let Some(constraint) = db_error.constraint() {
+                        if constraint == "users_email_key" {
+                            return UniqueError::UniqueValueViolation;
+                        }
+                    }
+                }
+                error.into()
+            })?;
+        Ok(AccountDetails::try_from(row)?)
+    }
+
+    /// Anonymizes an account and removes everything it could sign in with.
+    /// The row stays, so authored documents, comments and events keep a valid author.
+    pub async fn delete_user(&mut self, user_id: Uuid) -> Result<(), Box<dyn Error>> {
+        let transaction = self.database.transaction().await?;
+        transaction
+            .execute(
+                "
+                UPDATE users
+                SET username = 'deleted-' || user_id,
+                password_hash = '',
+                salt = NULL,
+                display_name = NULL,
+                email = NULL,
+                deleted_at = now()
+                WHERE user_id = $1
+                ",
+                &[&user_id],
+            )
+            .await?;
+        transaction
+            .execute(
+                "UPDATE sessions SET revoked = TRUE WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?;
+        for table in [
+            "personal_tokens",
+            "totp_recovery_codes",
+            "login_challenges",
+            "user_totp",
+            "user_identities",
+            "user_roles",
+            "password_resets",
+            "group_members",
+        ] {
+            transaction
+                .execute(
+                    &format!("DELETE FROM {} WHERE user_id = $1", table),
+                    &[&user_id],
+                )
+                .await?;
+        }
+        transaction.commit().await?;
+        info!({ user_id = user_id.to_string() }, "Deleted user");
+        Ok(())
+    }
+
+    pub async fn get_managed_users(&self) -> Result<Vec<ManagedUser>, Box<dyn Error>> {
+        let rows = self
+            .database
+            .query(
+                "
+                SELECT u.user_id, u.username, u.display_name, u.email,
+                EXISTS (SELECT * FROM user_roles r WHERE r.user_id = u.user_id AND r.role_id = $1),
+                u.disabled, u.locked_until, u.clearance_id
+                FROM users u
+                WHERE u.deleted_at IS NULL
+                ORDER BY u.username
+                ",
+                &[&i16::from(Role::Admin)],
+            )
+            .await?;
+        let users = rows
+            .into_iter()
+            .map(ManagedUser::try_from)
+            .collect::<Result<_, _>>()?;
+        Ok(users)
+    }
+
+    pub async fn get_managed_user(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<ManagedUser>, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_opt(
+                "
+                SELECT u.user_id, u.username, u.display_name, u.email,
+                EXISTS (SELECT * FROM user_roles r WHERE r.user_id = u.user_id AND r.role_id = $2),
+                u.disabled, u.locked_until, u.clearance_id
+                FROM users u
+                WHERE u.user_id = $1
+                AND u.deleted_at IS NULL
+                ",
+                &[&user_id, &i16::from(Role::Admin)],
+            )
+            .await?;
+        let user = row.map(ManagedUser::try_from).transpose()?;
+        Ok(user)
+    }
+
+    /// Returns whether the user exists
+    pub async fn set_disabled(
+        &self,
+        user_id: Uuid,
+        disabled: bool,
+    ) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "UPDATE users SET disabled = $2 WHERE user_id = $1 AND deleted_at IS NULL",
+                &[&user_id, &disabled],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    /// Returns whether the user exists
+    pub async fn set_clearance(
+        &self,
+        user_id: Uuid,
+        clearance: SensitivityLabel,
+    ) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "UPDATE users SET clearance_id = $2 WHERE user_id = $1 AND deleted_at IS NULL",
+                &[&user_id, &i16::from(clearance)],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    pub async fn get_clearance(
+        &self,
+        user_id: Uuid,
+    ) -> Result<Option<SensitivityLabel>, Box<dyn Error>> {
+        let row = self
+            .database
+            .query_opt(
+                "SELECT clearance_id FROM users WHERE user_id = $1 AND deleted_at IS NULL",
+                &[&user_id],
+            )
+            .await?;
+        let clearance = row
+            .map(|row| row.try_get::<_, i16>(0))
+            .transpose()?
+            .map(|clearance| {
+                SensitivityLabel::try_from(clearance)
+                    .map_err(RowError::unknown_value("clearance_id"))
+            })
+            .transpose()?;
+        Ok(clearance)
+    }
+
+    /// Blocks logins until the given time, `None` unlocks.
+    /// Returns whether the user exists
+    pub async fn set_locked_until(
+        &self,
+        user_id: Uuid,
+        locked_until: Option<DateTime<Utc>>,
+    ) -> Result<bool, Box<dyn Error>> {
+        let modified = self
+            .database
+            .execute(
+                "UPDATE users SET locked_until = $2 WHERE user_id = $1 AND deleted_at IS NULL",
+                &[&user_id, &locked_until],
+            )
+            .await?;
+        Ok(modified == 1)
+    }
+
+    /// Invalidates the current password and signs the user out everywhere.
+    /// The returned token is the only way to set a new password.
+    pub async fn create_password_reset(
+        &mut self,
+        user_id: Uuid,
+    ) -> Result<Option<PasswordReset>, Box<dyn Error>> {
+        let transaction = self.database.transaction().await?;
+        let modified = transaction
+            .execute(
+                "UPDATE users SET password_hash = '', salt = NULL WHERE user_id = $1 AND deleted_at IS NULL",
+                &[&user_id],
+            )
+            .await?;
+        if modified == 0 {
+            return Ok(None);
+        }
+        transaction
+            .execute(
+                "UPDATE sessions SET revoked = TRUE WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?;
+        transaction
+            .execute(
+                "DELETE FROM password_resets WHERE user_id = $1",
+                &[&user_id],
+            )
+            .await?;
+        let reset_token = generate_secret();
+        let expires_at = Utc::now() + Duration::hours(PASSWORD_RESET_HOURS);
+        transaction
+            .execute(
+                "INSERT INTO password_resets (token_hash, user_id, expires_at) VALUES ($1, $2, $3)",
+                &[&hash_secret(&reset_token), &user_id, &expires_at],
+            )
+            .await?;
+        transaction.commit().await?;
+        info!({ user_id = user_id.to_string() }, "Password reset required");
+        Ok(Some(PasswordReset {
+            reset_token,
+            expires_at,
+        }))
+    }
+
+    /// Sets a new password with a reset token, returns whether the token was valid
+    pub async fn redeem_password_reset(
+        &mut self,
+        reset_token: &str,
+        password: &str,
+    ) -> Result<bool, Box<dyn Error>> {
+        let password_hash = self.password_hashing.hash(password).await?;
+        let transaction = self.database.transaction().await?;
+        let row = transaction
+            .query_opt(
+                "DELETE FROM password_resets WHERE token_hash = $1 AND expires_at > now() RETURNING user_id",
+                &[&hash_secret(reset_token)],
+            )
+            .await?;
+        let Some(row) = row else {
+            return Ok(false);
+        };
+        let user_id: Uuid = row.try_get(0)?;
+        transaction
+            .execute(
+                "UPDATE users SET password_hash = $1, salt = NULL WHERE user_id = $2",
+                &[&password_hash, &user_id],
+            )
+            .await?;
+        transaction.commit().await?;
+        Ok(true)
+    }
+
+    /// Finds the local account linked to an external identity, provisioning one on first login
+    pub async fn get_or_create_external_user(
+        &mut self,
+        issuer: &str,
+        subject: &str,
+        username: &str,
+    ) -> Result<User, Box<dyn Error + Send + Sync>> {
+        if let Some(user) = self.get_external_user(issuer, subject).await? {
+            return Ok(user);
+        }
+
+        let transaction = self.database.transaction().await?;
+        let user_id = Uuid::new_v4();
+        // External accounts have no usable local password
+        let password_hash = String::new();
+        let mut candidate = username.to_owned();
+        // Insert and check in one statement, so a username taken concurrently gets a suffix instead of failing
+        while transaction
+            .execute(
+                "
+                INSERT INTO users (user_id, username, password_hash)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (username) DO NOTHING
+                ",
+                &[&user_id, &candidate, &password_hash],
+            )
+            .await?
+            == 0
+        {
+            candidate = format!("{}-{}", username, &Uuid::new_v4().simple().to_string()[..8]);
+        }
+        let linked = transaction
+            .execute(
+                "
+                INSERT INTO user_identities (issuer, subject, user_id)
+                VALUES ($1, $2, $3)
+                ON CONFLICT (issuer, subject) DO NOTHING
+                ",
+                &[&issuer, &subject, &user_id],
+            )
+            .await?;
+        if linked == 0 {
+            // A concurrent first login provisioned this identity, drop our user and use theirs
+            transaction.rollback().await?;
+            return self
+                .get_external_user(issuer, subject)
+                .await?
+                .ok_or_else(|| "External identity disappeared".into());
+        }
+        transaction.commit().await?;
+        info!(
+            { user_id = user_id.to_string(), issuer = issuer },
+            "Provisioned external user"
+        );
+        Ok(User {
+            user_id,
+            salt: None,
+            username: candidate,
+            password_hash,
+            disabled: false,
+            locked_until: None,
+        })
+    }
+
+    async fn get_external_user(
+        &self,
+        issuer: &str,
+        subject: &str,
+    ) -> Result<Option<User>, Box<dyn Error + Send + Sync>> {
+        let user = self
+            .database
+            .query_opt(
+                "
+                SELECT u.*
+                FROM user_identities i
+                JOIN users u ON u.user_id = i.user_id
+                WHERE i.issuer = $1
+                AND i.subject = $2
+                ",
+                &[&issuer, &subject],
+            )
+            .await?;
+        Ok(match user {
+            Some(user) => Some(User::try_from(user)?),
+            None => None,
         })
     }
 }
@@ -74,6 +520,7 @@ impl UsersRepository {
 impl<S> FromRequestParts<S> for UsersRepository
 where
     DbPool: FromRef<S>,
+    PasswordHashing: FromRef<S>,
     S: Send + Sync,
 {
     type Rejection = StatusCode;
@@ -83,22 +530,49 @@ where
             error!("{}", e);
             StatusCode::INTERNAL_SERVER_ERROR
         })?;
-        Ok(Self { database })
+        let password_hashing = PasswordHashing::from_ref(state);
+        Ok(Self {
+            database,
+            password_hashing,
+        })
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use uuid::Uuid;
+    use super::UsersRepository;
+    use crate::services::{
+        auth::password::{PasswordHashing, PasswordHashingConfig},
+        database::testing::TestDatabase,
+    };
 
-    use crate::services::database::repositories::users::hash_password;
+    async fn repository(db: &TestDatabase) -> UsersRepository {
+        let config = PasswordHashingConfig {
+            memory_cost: 1024,
+            time_cost: 1,
+            parallelism: 1,
+        };
+        UsersRepository {
+            database: db.connection().await,
+            password_hashing: PasswordHashing::try_from(&config).unwrap(),
+        }
+    }
 
-    #[test]
-    fn hashing() {
-        let user_id = Uuid::parse_str("81721217-8f19-4c3b-8b25-a2af68875018").unwrap();
-        let password = "admin";
-        let salt = Uuid::parse_str("8ce53b43-a248-4abf-a76e-d79d21a820cf").unwrap();
-        let hashed = hash_password(&user_id, password, &salt);
-        println!("Hash: `{}`", hashed);
+    #[tokio::test]
+    async fn concurrent_first_logins_share_one_user() {
+        let Some(db) = TestDatabase::new().await else {
+            return;
+        };
+        let mut first = repository(&db).await;
+        let mut second = repository(&db).await;
+        let (first, second) = tokio::join!(
+            first.get_or_create_external_user("https://idp", "subject", "q"),
+            second.get_or_create_external_user("https://idp", "subject", "q"),
+        );
+        let (first, second) = (first.unwrap(), second.unwrap());
+        assert_eq!(first.user_id, second.user_id);
+        // "q" is a seeded local user
+        assert!(first.username.starts_with("q-"));
+        db.finish().await;
     }
 }
@@ -0,0 +1,70 @@
+use bb8::{ManageConnection, Pool};
+use bb8_postgres::PostgresConnectionManager;
+use tokio_postgres::NoTls;
+use uuid::Uuid;
+
+use super::{migrator::migrations, DbConn, DbPool};
+
+/// Schema of its own with every migration and the seed applied, for repository tests.
+/// The server comes from `TEST_DATABASE_URL`, e.g. `host=127.0.0.1 user=admin password=admin dbname=admin`,
+/// tests return early when it isn't set.
+pub struct TestDatabase {
+    pub pool: DbPool,
+    config: tokio_postgres::Config,
+    schema: String,
+}
+
+impl TestDatabase {
+    pub async fn new() -> Option<Self> {
+        let Ok(url) = std::env::var("TEST_DATABASE_URL") else {
+            eprintln!("TEST_DATABASE_URL is not set, skipping database test");
+            return None;
+        };
+        let config: tokio_postgres::Config = url.parse().expect("Invalid TEST_DATABASE_URL");
+        let schema = format!("test_{}", Uuid::new_v4().simple());
+        let mut schema_config = config.clone();
+        schema_config.options(&format!("-c search_path={schema}"));
+        let manager = PostgresConnectionManager::new(schema_config, NoTls);
+        let mut client = manager.connect().await.expect("Failed to connect");
+        client
+            .batch_execute(&format!("CREATE SCHEMA {schema}"))
+            .await
+            .unwrap();
+        migrations::runner().run_async(&mut client).await.unwrap();
+        let mut seeds: Vec<_> = std::fs::read_dir("seeding")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        seeds.sort();
+        for seed in seeds {
+            let seed = std::fs::read_to_string(seed).unwrap();
+            client.batch_execute(&seed).await.unwrap();
+        }
+        let pool = Pool::builder().build(manager).await.unwrap();
+        Some(Self {
+            pool,
+            config,
+            schema,
+        })
+    }
+
+    pub async fn connection(&self) -> DbConn {
+        self.pool.get_owned().await.unwrap()
+    }
+
+    /// Drops the schema, tests call it once they are done
+    pub async fn finish(self) {
+        let Self {
+            pool,
+            config,
+            schema,
+        } = self;
+        drop(pool);
+        let manager = PostgresConnectionManager::new(config, NoTls);
+        let client = manager.connect().await.expect("Failed to connect");
+        client
+            .batch_execute(&format!("DROP SCHEMA {schema} CASCADE"))
+            .await
+            .unwrap();
+    }
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use tracing::{error, info};
+
+use super::database::{repositories::permission::PermissionRepository, DbPool};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct GrantExpiryConfig {
+    /// How often expired role grants are removed in seconds
+    pub sweep_interval: u64,
+}
+
+/// Periodically removes expired document version roles,
+/// their holders get a `RoleRemoved` event
+pub fn spawn_grant_expiry(database: DbPool, config: &GrantExpiryConfig) {
+    let mut interval = tokio::time::interval(Duration::from_secs(config.sweep_interval));
+    tokio::spawn(async move {
+        loop {
+            interval.tick().await;
+            let database = match database.get_owned().await {
+                Ok(database) => database,
+                Err(error) => {
+                    error!({ error = error.to_string() }, "Error when sweeping grants");
+                    continue;
+                }
+            };
+            let mut permission_repository = PermissionRepository::new(database);
+            match permission_repository
+                .remove_expired_document_version_roles()
+                .await
+            {
+                Ok(0) => {}
+                Ok(removed) => info!({ removed = removed }, "Removed expired grants"),
+                Err(error) => {
+                    error!({ error = error.to_string() }, "Error when sweeping grants")
+                }
+            }
+        }
+    });
+}
@@ -1,8 +1,14 @@
 pub mod auth;
 pub mod config;
+pub mod content_delta;
+pub mod content_repack;
+pub mod content_storage;
 pub mod database;
+pub mod grant_expiry;
 pub mod s3storage;
 pub mod signals;
 pub mod state;
+pub mod text_diff;
+pub mod text_merge;
 pub mod tracing;
 pub mod util;
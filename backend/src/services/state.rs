@@ -1,11 +1,23 @@
 use axum::extract::FromRef;
 use s3::Bucket;
 
-use super::{auth::auth_keys::AuthKeys, database::DbPool};
+use super::{
+    auth::{
+        auth_keys::AuthKeys, oidc::OidcClient, password::PasswordHashing, session::SessionConfig,
+        throttling::ThrottlingConfig,
+    },
+    content_storage::ContentStorage,
+    database::DbPool,
+};
 
 #[derive(FromRef, Clone)]
 pub struct AppState {
     pub auth_keys: AuthKeys,
+    pub password_hashing: PasswordHashing,
+    pub session: SessionConfig,
+    pub throttling: ThrottlingConfig,
+    pub oidc: Option<OidcClient>,
     pub database: DbPool,
     pub s3storage: Bucket,
+    pub content_storage: ContentStorage,
 }
@@ -0,0 +1,141 @@
+use similar::{ChangeTag, TextDiff};
+
+use crate::models::{
+    diff::{DiffHunk, DiffLine, DiffTag, DiffWord, VersionDiff},
+    version::DocumentVersion,
+};
+
+use super::content_delta::DIFF_DEADLINE;
+
+/// Unchanged lines kept around every hunk
+const CONTEXT_LINES: usize = 3;
+
+/// Line diff of the content of two versions, lines replacing others also get a word diff.
+/// Without `from` the content of `to` is compared with empty content
+pub fn diff_versions(from: Option<&DocumentVersion>, to: &DocumentVersion) -> VersionDiff {
+    let old = from.and_then(|from| from.content.as_deref()).unwrap_or("");
+    let new = to.content.as_deref().unwrap_or("");
+    let old_name = from.map_or("/dev/null", |from| from.version_name.as_str());
+    let diff = TextDiff::configure()
+        .timeout(DIFF_DEADLINE)
+        .diff_lines(old, new);
+    let unified = diff
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(old_name, &to.version_name)
+        .to_string();
+    let hunks = diff
+        .grouped_ops(CONTEXT_LINES)
+        .iter()
+        .map(|ops| {
+            let first = ops.first().unwrap();
+            let last = ops.last().unwrap();
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            let lines = ops
+                .iter()
+                .flat_map(|op| diff.iter_inline_changes(op))
+                .map(|change| {
+                    let words: Vec<DiffWord> = change
+                        .iter_strings_lossy()
+                        .map(|(changed, value)| DiffWord {
+                            changed,
+                            value: value.into_owned(),
+                        })
+                        .collect();
+                    let replaced = words.iter().any(|word| word.changed);
+                    DiffLine {
+                        tag: match change.tag() {
+                            ChangeTag::Equal => DiffTag::Equal,
+                            ChangeTag::Delete => DiffTag::Delete,
+                            ChangeTag::Insert => DiffTag::Insert,
+                        },
+                        old_line: change.old_index().map(|index| index + 1),
+                        new_line: change.new_index().map(|index| index + 1),
+                        content: words.iter().map(|word| word.value.as_str()).collect(),
+                        words: if replaced { words } else { Vec::new() },
+                    }
+                })
+                .collect();
+            DiffHunk {
+                old_start: old_range.start + 1,
+                old_lines: old_range.len(),
+                new_start: new_range.start + 1,
+                new_lines: new_range.len(),
+                lines,
+            }
+        })
+        .collect();
+    VersionDiff {
+        from: from.map(|from| from.version_id),
+        to: to.version_id,
+        unified,
+        hunks,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use uuid::Uuid;
+
+    use crate::models::{
+        diff::{DiffTag, DiffWord},
+        version::DocumentVersion,
+        version_state::DocumentVersionState,
+    };
+
+    use super::diff_versions;
+
+    fn version(version_name: &str, content: &str) -> DocumentVersion {
+        DocumentVersion {
+            document_id: Uuid::nil(),
+            version_id: Uuid::new_v4(),
+            version_name: version_name.to_owned(),
+            created_at: Utc::now(),
+            content: Some(content.to_owned()),
+            content_length: content.len() as i64,
+            version_state: DocumentVersionState::InProgress,
+            children: Vec::new(),
+            parents: Vec::new(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn changed_lines_get_word_diffs() {
+        let from = version("1", "title\nthe quick fox\nend\n");
+        let to = version("2", "title\nthe slow fox\nend\nmore\n");
+        let diff = diff_versions(Some(&from), &to);
+        assert_eq!(
+            diff.unified,
+            "--- 1\n+++ 2\n@@ -1,3 +1,4 @@\n title\n-the quick fox\n+the slow fox\n end\n+more\n"
+        );
+        assert_eq!(diff.hunks.len(), 1);
+        let hunk = &diff.hunks[0];
+        assert_eq!((hunk.old_start, hunk.old_lines), (1, 3));
+        assert_eq!((hunk.new_start, hunk.new_lines), (1, 4));
+        let tags: Vec<&DiffTag> = hunk.lines.iter().map(|line| &line.tag).collect();
+        assert_eq!(
+            tags,
+            [
+                &DiffTag::Equal,
+                &DiffTag::Delete,
+                &DiffTag::Insert,
+                &DiffTag::Equal,
+                &DiffTag::Insert
+            ]
+        );
+        let inserted = &hunk.lines[2];
+        assert_eq!((inserted.old_line, inserted.new_line), (None, Some(2)));
+        assert!(inserted.words.contains(&DiffWord {
+            changed: true,
+            value: "slow".to_owned(),
+        }));
+        assert!(hunk.lines[4].words.is_empty());
+
+        let initial = diff_versions(None, &from);
+        assert_eq!(initial.from, None);
+        assert_eq!(initial.hunks[0].new_lines, 3);
+    }
+}
@@ -0,0 +1,177 @@
+use std::time::Instant;
+
+use similar::{capture_diff_slices_deadline, Algorithm, DiffOp};
+use uuid::Uuid;
+
+use crate::models::{
+    merge::{MergeConflict, MergePreview},
+    version::DocumentVersion,
+};
+
+use super::content_delta::DIFF_DEADLINE;
+
+/// Lines of one side replacing the base lines from `start` to `end`
+struct Change<'a> {
+    start: usize,
+    end: usize,
+    lines: &'a [&'a str],
+    ours: bool,
+}
+
+fn changes<'a>(base: &[&str], lines: &'a [&'a str], ours: bool) -> Vec<Change<'a>> {
+    let deadline = Instant::now() + DIFF_DEADLINE;
+    capture_diff_slices_deadline(Algorithm::Myers, base, lines, Some(deadline))
+        .into_iter()
+        .filter(|op| !matches!(op, DiffOp::Equal { .. }))
+        .map(|op| Change {
+            start: op.old_range().start,
+            end: op.old_range().end,
+            lines: &lines[op.new_range()],
+            ours,
+        })
+        .collect()
+}
+
+/// Base lines from `start` to `end` with the changes of one side applied
+fn apply(base: &[&str], start: usize, end: usize, changes: &[&Change]) -> String {
+    let mut content = String::new();
+    let mut position = start;
+    for change in changes {
+        content.push_str(&base[position..change.start].concat());
+        content.push_str(&change.lines.concat());
+        position = change.end;
+    }
+    content.push_str(&base[position..end].concat());
+    content
+}
+
+fn push_marked(content: &mut String, text: &str) {
+    content.push_str(text);
+    if !text.is_empty() && !text.ends_with('\n') {
+        content.push('\n');
+    }
+}
+
+/// Line based three-way merge, changes of both sides touching the same base lines
+/// conflict unless they are identical
+pub fn merge(
+    base: &str,
+    ours: &str,
+    theirs: &str,
+    ours_name: &str,
+    theirs_name: &str,
+) -> (String, Vec<MergeConflict>) {
+    let base_lines: Vec<&str> = base.split_inclusive('\n').collect();
+    let our_lines: Vec<&str> = ours.split_inclusive('\n').collect();
+    let their_lines: Vec<&str> = theirs.split_inclusive('\n').collect();
+    let mut all_changes = changes(&base_lines, &our_lines, true);
+    all_changes.extend(changes(&base_lines, &their_lines, false));
+    all_changes.sort_by_key(|change| (change.start, change.end));
+
+    let mut content = String::new();
+    let mut conflicts = Vec::new();
+    let mut position = 0;
+    let mut index = 0;
+    while index < all_changes.len() {
+        let start = all_changes[index].start;
+        let mut end = all_changes[index].end;
+        let mut cluster = vec![&all_changes[index]];
+        index += 1;
+        while index < all_changes.len() && all_changes[index].start <= end {
+            end = end.max(all_changes[index].end);
+            cluster.push(&all_changes[index]);
+            index += 1;
+        }
+        content.push_str(&base_lines[position..start].concat());
+        position = end;
+        let (our_changes, their_changes): (Vec<&Change>, Vec<&Change>) =
+            cluster.into_iter().partition(|change| change.ours);
+        let our_text = apply(&base_lines, start, end, &our_changes);
+        let their_text = apply(&base_lines, start, end, &their_changes);
+        if their_changes.is_empty() || our_text == their_text {
+            content.push_str(&our_text);
+        } else if our_changes.is_empty() {
+            content.push_str(&their_text);
+        } else {
+            if !content.is_empty() && !content.ends_with('\n') {
+                content.push('\n');
+            }
+            conflicts.push(MergeConflict {
+                line: content.matches('\n').count() + 1,
+                ours: our_text.clone(),
+                theirs: their_text.clone(),
+            });
+            content.push_str(&format!("<<<<<<< {}\n", ours_name));
+            push_marked(&mut content, &our_text);
+            content.push_str("=======\n");
+            push_marked(&mut content, &their_text);
+            content.push_str(&format!(">>>>>>> {}\n", theirs_name));
+        }
+    }
+    content.push_str(&base_lines[position..].concat());
+    (content, conflicts)
+}
+
+/// Merges the content of `theirs` into `ours` starting from the content of their common ancestor
+pub fn merge_versions(
+    base: Option<Uuid>,
+    base_content: &str,
+    ours: &DocumentVersion,
+    theirs: &DocumentVersion,
+) -> MergePreview {
+    let (content, conflicts) = merge(
+        base_content,
+        ours.content.as_deref().unwrap_or(""),
+        theirs.content.as_deref().unwrap_or(""),
+        &ours.version_name,
+        &theirs.version_name,
+    );
+    MergePreview {
+        base,
+        parents: vec![ours.version_id, theirs.version_id],
+        clean: conflicts.is_empty(),
+        content,
+        conflicts,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::merge::MergeConflict;
+
+    use super::merge;
+
+    #[test]
+    fn separate_changes_merge_cleanly() {
+        let base = "one\ntwo\nthree\nfour\nfive\n";
+        let ours = "ONE\ntwo\nthree\nfour\nfive\n";
+        let theirs = "one\ntwo\nthree\nfour\nFIVE\nsix\n";
+        let (content, conflicts) = merge(base, ours, theirs, "2", "3");
+        assert_eq!(content, "ONE\ntwo\nthree\nfour\nFIVE\nsix\n");
+        assert!(conflicts.is_empty());
+
+        let (content, conflicts) = merge(base, ours, ours, "2", "3");
+        assert_eq!(content, ours);
+        assert!(conflicts.is_empty());
+    }
+
+    #[test]
+    fn overlapping_changes_conflict() {
+        let base = "one\ntwo\nthree\n";
+        let ours = "one\n2\nthree\n";
+        let theirs = "one\nzwei\nthree";
+        let (content, conflicts) = merge(base, ours, theirs, "2", "3");
+        assert_eq!(
+            content,
+            "one\n<<<<<<< 2\n2\nthree\n=======\nzwei\nthree\n>>>>>>> 3\n"
+        );
+        assert_eq!(
+            conflicts,
+            vec![MergeConflict {
+                line: 2,
+                ours: "2\nthree\n".to_owned(),
+                theirs: "zwei\nthree".to_owned(),
+            }]
+        );
+    }
+}
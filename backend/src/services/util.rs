@@ -87,7 +87,6 @@ where
 {
     type Rejection = ValidatedJsonRecjection;
 
-    #[must_use]
     async fn from_request(req: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
         let Json(json) = Json::<T>::from_request(req, state).await?;
         json.validate()?;
@@ -24,6 +24,7 @@ const API_BASE_URL = 'http://localhost:3000/api/';
 
 export type LoginData = {
   token: string;
+  refreshToken?: string;
   username: string;
   userId: string;
 };
@@ -31,9 +32,10 @@ export type LoginData = {
 export type LoginState = {
   isLoggedIn: boolean;
   token?: string;
+  refreshToken?: string;
   username?: string;
   userId?: string;
-  setToken: (token: string | undefined) => void;
+  setToken: (token: string | undefined, refreshToken?: string) => void;
 };
 
 type ModalError = {
@@ -58,7 +60,8 @@ function parseJwt(token: string) {
 
 function App() {
   const cookieName = 'jwt-token';
-  const [cookies, setCookie, removeCookie] = useCookies([cookieName]);
+  const refreshCookieName = 'refresh-token';
+  const [cookies, setCookie, removeCookie] = useCookies([cookieName, refreshCookieName]);
 
   const [loginData, setLoginData] = useState<LoginData | undefined>();
   const isLoggedIn = loginData !== undefined;
@@ -66,7 +69,7 @@ function App() {
   const [modalError, setModalError] = useState<ModalError>();
   const isModalErrorSet = modalError !== undefined;
 
-  const setLoginDataUsingToken: (token: string | undefined) => void = (token) => {
+  const setLoginDataUsingToken: (token: string | undefined, refreshToken?: string) => void = (token, refreshToken) => {
     if (token === undefined) {
       setLoginData(undefined);
       return;
@@ -74,19 +77,22 @@ function App() {
     const parsedToken = parseJwt(token);
     setLoginData({
       token,
+      refreshToken,
       username: parsedToken.username as string,
       userId: parsedToken.userId as string,
     });
   };
 
   useEffect(() => {
-    setLoginDataUsingToken(cookies[cookieName]);
+    setLoginDataUsingToken(cookies[cookieName], cookies[refreshCookieName]);
   }, [cookies]);
 
-  const setToken: (token: string | undefined) => void = (token) => {
+  const setToken: (token: string | undefined, refreshToken?: string) => void = (token, refreshToken) => {
     if (token !== undefined) setCookie(cookieName, token);
     else removeCookie(cookieName);
-    setLoginDataUsingToken(token);
+    if (token !== undefined && refreshToken !== undefined) setCookie(refreshCookieName, refreshToken);
+    else removeCookie(refreshCookieName);
+    setLoginDataUsingToken(token, refreshToken);
   };
 
   const loginState: LoginState = {
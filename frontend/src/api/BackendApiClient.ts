@@ -28,6 +28,9 @@ type BackendError = {
   error: string;
 };
 
+// Refresh tokens are single use, so concurrent requests have to share one refresh
+let pendingRefresh: { refreshToken: string; response: Promise<AuthResponse | undefined> } | undefined;
+
 class BackendApiClient implements ApiClient {
   private apiBaseUrl: string;
   private loginState: LoginState;
@@ -63,20 +66,44 @@ class BackendApiClient implements ApiClient {
       body: data,
     } as RequestInit);
 
-  private addCredentialsToRequestOptions = (options: RequestInit, authenticated: boolean) => {
-    if (!authenticated) return options;
-    if (this.loginState.token === undefined) throw new Error('Not authenticated');
-    return {
+  private addTokenToRequestOptions = (options: RequestInit, token: string) =>
+    ({
       ...options,
       headers: {
         ...(options.headers ?? {}),
-        Authorization: `Bearer ${this.loginState.token}`,
+        Authorization: `Bearer ${token}`,
       },
-    } as RequestInit;
+    } as RequestInit);
+
+  private addCredentialsToRequestOptions = (options: RequestInit, authenticated: boolean) => {
+    if (!authenticated) return options;
+    if (this.loginState.token === undefined) throw new Error('Not authenticated');
+    return this.addTokenToRequestOptions(options, this.loginState.token);
   };
 
-  private fetchThrowing = async (input: RequestInfo | URL, options?: RequestInit) => {
-    const response = await fetch(input, options);
+  private refreshAccessToken = async () => {
+    const refreshToken = this.loginState.refreshToken;
+    if (refreshToken === undefined) return undefined;
+    if (pendingRefresh?.refreshToken !== refreshToken) {
+      let reqOptions = this.addMethodToRequestOptions(this.baseRequestOptions, 'POST');
+      reqOptions = this.addJsonBodyToRequestOptions(reqOptions, { refreshToken });
+      const response = fetch(new URL('auth/refresh', this.apiBaseUrl), reqOptions)
+        .then(async (res) => (res.ok ? ((await res.json()) as AuthResponse) : undefined))
+        .catch(() => undefined);
+      pendingRefresh = { refreshToken, response };
+    }
+    const authResponse = await pendingRefresh.response;
+    if (authResponse === undefined) return undefined;
+    this.loginState.setToken(authResponse.token, authResponse.refreshToken);
+    return authResponse.token;
+  };
+
+  private fetchThrowing = async (input: RequestInfo | URL, options: RequestInit, authenticated: boolean) => {
+    let response = await fetch(input, options);
+    if (response.status === 401 && authenticated) {
+      const token = await this.refreshAccessToken();
+      if (token !== undefined) response = await fetch(input, this.addTokenToRequestOptions(options, token));
+    }
     if (response.ok) return response;
     const jsonResponse =
       response.headers.get('content-type')?.startsWith('application/json') === true ? await response.json() : undefined;
@@ -105,7 +132,7 @@ class BackendApiClient implements ApiClient {
     let postReqOptions = this.addMethodToRequestOptions(this.baseRequestOptions, 'POST');
     postReqOptions = this.addJsonBodyToRequestOptions(postReqOptions, data);
     postReqOptions = this.addCredentialsToRequestOptions(postReqOptions, authenticated);
-    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), postReqOptions);
+    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), postReqOptions, authenticated);
     if (returnBody) return (await response.json()) as TResponse;
   }
 
@@ -117,7 +144,7 @@ class BackendApiClient implements ApiClient {
     let reqOptions = this.addMethodToRequestOptions(this.baseRequestOptions, 'PATCH');
     reqOptions = this.addFormBodyToRequestOptions(reqOptions, form);
     reqOptions = this.addCredentialsToRequestOptions(reqOptions, authenticated);
-    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), reqOptions);
+    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), reqOptions, authenticated);
     if (returnBody) return (await response.json()) as TResponse;
   }
 
@@ -127,7 +154,7 @@ class BackendApiClient implements ApiClient {
     let postReqOptions = this.addMethodToRequestOptions(this.baseRequestOptions, 'PATCH');
     postReqOptions = this.addJsonBodyToRequestOptions(postReqOptions, data);
     postReqOptions = this.addCredentialsToRequestOptions(postReqOptions, authenticated);
-    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), postReqOptions);
+    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), postReqOptions, authenticated);
     if (returnBody) return (await response.json()) as TResponse;
   }
 
@@ -136,7 +163,7 @@ class BackendApiClient implements ApiClient {
   private async delete<TResponse>(relPath: string, authenticated = true, returnBody = false) {
     let postReqOptions = this.addMethodToRequestOptions(this.baseRequestOptions, 'DELETE');
     postReqOptions = this.addCredentialsToRequestOptions(postReqOptions, authenticated);
-    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), postReqOptions);
+    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), postReqOptions, authenticated);
     if (returnBody) return (await response.json()) as TResponse;
   }
 
@@ -144,7 +171,7 @@ class BackendApiClient implements ApiClient {
   private async get(relPath: string, authenticated: boolean, returnBody: false): Promise<undefined>;
   private async get<TResponse>(relPath: string, authenticated = true, returnBody = true) {
     const getReqOptions = this.addCredentialsToRequestOptions(this.baseRequestOptions, authenticated);
-    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), getReqOptions);
+    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), getReqOptions, authenticated);
     if (returnBody) return (await response.json()) as TResponse;
   }
 
@@ -152,16 +179,20 @@ class BackendApiClient implements ApiClient {
   private async fetchFile(relPath: string, authenticated: boolean, returnBody: false): Promise<undefined>;
   private async fetchFile(relPath: string, authenticated = true, returnBody = true) {
     const getReqOptions = this.addCredentialsToRequestOptions(this.baseRequestOptions, authenticated);
-    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), getReqOptions);
+    const response = await this.fetchThrowing(new URL(relPath, this.apiBaseUrl), getReqOptions, authenticated);
     if (returnBody) return await response.blob();
   }
 
   register = async (username: string, password: string) => await this.post('auth/register', { username, password }, false, false);
   login = async (username: string, password: string) => {
     const authResponse = await this.post<AuthResponse>('auth/login', { username, password }, false);
-    this.loginState.setToken(authResponse.token);
+    this.loginState.setToken(authResponse.token, authResponse.refreshToken);
+  };
+  logout = async () => {
+    // The local session ends even if the backend could not revoke it
+    await this.post('auth/logout', {}, true, false).catch(() => undefined);
+    this.loginState.setToken(undefined);
   };
-  logout = async () => this.loginState.setToken(undefined);
   getUsers = async () => await this.get<User[]>('auth/users');
 
   getDocuments = async () => await this.get<Document[]>('documents/documents');
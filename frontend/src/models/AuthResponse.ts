@@ -1,5 +1,6 @@
 type AuthResponse = {
   token: string;
+  refreshToken: string;
 };
 
 export default AuthResponse;
@@ -8,7 +8,10 @@ export type DocumentVersion = {
   versionId: string;
   versionName: string;
   createdAt: string;
-  content: string;
+  /** Missing when versions are listed. */
+  content?: string;
+  /** Size of the content in bytes. */
+  contentLength: number;
   versionState: DocumentVersionState;
   children: string[];
   parents: string[];
@@ -46,6 +46,7 @@ export const VersionCreator: FunctionComponent<VersionCreatorProps> = ({ loginSt
 
   const [document, setDocument] = useState<Document>();
   const [versions, setVersions] = useState<DocumentVersion[]>();
+  const [parentContent, setParentContent] = useState<string>();
   const [users, setUsers] = useState<User[]>();
 
   useEffect(() => {
@@ -54,7 +55,10 @@ export const VersionCreator: FunctionComponent<VersionCreatorProps> = ({ loginSt
     if (isCreatingNewVersion) {
       let documentPromise = apiClient.getDocument(documentId).then((response) => setDocument(response));
       let versionsPromise = apiClient.getVersions(documentId).then((response) => setVersions(response));
-      promises = [...promises, documentPromise, versionsPromise];
+      let parentPromise = apiClient
+        .getVersion(documentId, parentVersionId)
+        .then((response) => setParentContent(response.content ?? ''));
+      promises = [...promises, documentPromise, versionsPromise, parentPromise];
     }
     Promise.all(promises).then(() => setIsLoading(false));
   }, [apiClient, isCreatingNewVersion, documentId, parentVersionId]);
@@ -62,13 +66,13 @@ export const VersionCreator: FunctionComponent<VersionCreatorProps> = ({ loginSt
   const parentVersion = versions?.find((version) => version.versionId === parentVersionId);
   const versionsMinusParent = versions?.filter(({ versionId }) => versionId !== parentVersionId);
   useEffect(() => {
-    if (parentVersion === undefined) return;
+    if (parentVersion === undefined || parentContent === undefined) return;
     setCreatedVersion((createdVersion) => ({
       ...createdVersion,
-      content: createdVersion.content.length > 0 ? createdVersion.content : parentVersion.content,
+      content: createdVersion.content.length > 0 ? createdVersion.content : parentContent,
       parents: createdVersion.parents.length > 0 ? createdVersion.parents : [parentVersion.versionId],
     }));
-  }, [parentVersion]);
+  }, [parentVersion, parentContent]);
 
   const [createdDocument, setCreatedDocument] = useState<CreateDocument>({
     documentName: '',
@@ -135,7 +139,8 @@ export const VersionCreator: FunctionComponent<VersionCreatorProps> = ({ loginSt
   );
 
   const rolesDefined = users !== undefined && defaultRoles !== undefined;
-  const parentVersionDefined = versions !== undefined && versionsMinusParent !== undefined && parentVersion !== undefined;
+  const parentVersionDefined =
+    versions !== undefined && versionsMinusParent !== undefined && parentVersion !== undefined && parentContent !== undefined;
 
   return rolesDefined && (isCreatingNewDocument || (isCreatingNewVersion && parentVersionDefined)) ? (
     <>
@@ -153,6 +158,7 @@ export const VersionCreator: FunctionComponent<VersionCreatorProps> = ({ loginSt
           <>
             <VersionNameChooser versions={versions} parentVersion={parentVersion} onChange={changeVersion} />
             <VersionMergingOptions
+              apiClient={apiClient}
               versions={versionsMinusParent}
               onChange={(parents) => setCreatedVersion({ ...createdVersion, parents: [parentVersion.versionId, ...parents] })}
             />
@@ -161,7 +167,7 @@ export const VersionCreator: FunctionComponent<VersionCreatorProps> = ({ loginSt
           <></>
         )}
         <VersionContentEditor
-          defaultValue={parentVersion?.content}
+          defaultValue={parentContent}
           onChange={(content) => setCreatedVersion({ ...createdVersion, content })}
         />
         <Button disabled={isSubmitting} className="w-100" type="submit" onClick={createVersion}>
@@ -39,6 +39,7 @@ export const VersionEditor: FunctionComponent<VersionEditorProps> = ({ loginStat
   const [, setIsLoading] = useState(true);
   const [baseDocument, setBaseDocument] = useState<Document>();
   const [versions, setVersions] = useState<DocumentVersion[]>();
+  const [baseVersion, setBaseVersion] = useState<DocumentVersion>();
   const [users, setUsers] = useState<User[]>();
   const [originalMembers, setOriginalMembers] = useState<DocumentVersionMember[]>();
 
@@ -46,11 +47,11 @@ export const VersionEditor: FunctionComponent<VersionEditorProps> = ({ loginStat
     let usersPromise = apiClient.getUsers().then((response) => setUsers(response));
     let documentPromise = apiClient.getDocument(documentId!).then((response) => setBaseDocument(response));
     let versionsPromise = apiClient.getVersions(documentId!).then((response) => setVersions(response));
+    let versionPromise = apiClient.getVersion(documentId!, versionId!).then((response) => setBaseVersion(response));
     let membersPromise = apiClient.getMembers(documentId!, versionId!).then((response) => setOriginalMembers(response));
-    Promise.all([usersPromise, documentPromise, versionsPromise, membersPromise]).then(() => setIsLoading(false));
+    Promise.all([usersPromise, documentPromise, versionsPromise, versionPromise, membersPromise]).then(() => setIsLoading(false));
   }, [apiClient, documentId, versionId]);
 
-  const baseVersion = versions?.find((version) => version.versionId === versionId);
   const parents = baseVersion?.parents?.map((parentId) => versions?.find((version) => version.versionId === parentId));
   const parentNames = parents
     ?.map((parent) => parent?.versionName)
@@ -62,7 +63,7 @@ export const VersionEditor: FunctionComponent<VersionEditorProps> = ({ loginStat
   useEffect(() => {
     if (baseVersion === undefined) return;
     setUpdatedVersion({
-      content: baseVersion.content,
+      content: baseVersion.content ?? '',
       updatedAt: baseVersion.updatedAt,
     });
   }, [baseVersion]);
@@ -98,7 +99,7 @@ export const VersionEditor: FunctionComponent<VersionEditorProps> = ({ loginStat
       const prefix = 'Error modifying version: ';
       if (e instanceof ConcurrencyConflict) {
         const anotherVersion = e.value as DocumentVersion;
-        setConflictVersion(anotherVersion);
+        setConflictVersion({ content: anotherVersion.content ?? '', updatedAt: anotherVersion.updatedAt });
         setUpdatedVersion({ ...updatedVersion, updatedAt: anotherVersion.updatedAt });
         setError(prefix + 'another modification has been submitted! Compare changes and resubmit.');
       } else {
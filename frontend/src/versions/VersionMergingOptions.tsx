@@ -2,17 +2,29 @@ import { FunctionComponent, useState } from 'react';
 import { Form, Tab, Row, ListGroup, Col } from 'react-bootstrap';
 
 import styles from './docVer.module.css';
+import ApiClient from '../api/ApiClient';
 import DocumentVersion from '../models/DocumentVersion';
 
 type VersionMergingOptionsProps = {
+  apiClient: ApiClient;
   /** Without parent version. */
   versions: DocumentVersion[];
   disabled?: boolean;
   onChange?: (selected: string[]) => any;
 };
 
-export const VersionMergingOptions: FunctionComponent<VersionMergingOptionsProps> = ({ versions, disabled, onChange }) => {
+export const VersionMergingOptions: FunctionComponent<VersionMergingOptionsProps> = ({ apiClient, versions, disabled, onChange }) => {
   const [selected, setSelected] = useState<string[]>([]);
+  /** Content is not listed with the versions, so it is loaded once a preview is opened. */
+  const [previews, setPreviews] = useState<Record<string, string>>({});
+
+  const loadPreview = (eventKey: string | null) => {
+    const version = versions.find(({ versionId }) => eventKey === `#version-${versionId}`);
+    if (version === undefined || previews[version.versionId] !== undefined) return;
+    apiClient
+      .getVersion(version.documentId, version.versionId)
+      .then((response) => setPreviews((previews) => ({ ...previews, [version.versionId]: response.content ?? '' })));
+  };
 
   const isChecked = (value: string) => selected.indexOf(value) !== -1;
   const setSelectedAndOnChange = (newSelected: string[]) => {
@@ -25,7 +37,7 @@ export const VersionMergingOptions: FunctionComponent<VersionMergingOptionsProps
   return (
     <Form.Group className="mb-3" controlId="merged">
       <Form.Label>Merge versions</Form.Label>
-      <Tab.Container id="list-group-tabs-example">
+      <Tab.Container id="list-group-tabs-example" onSelect={loadPreview}>
         <Row>
           <Col sm={2}>
             <ListGroup>
@@ -49,10 +61,10 @@ export const VersionMergingOptions: FunctionComponent<VersionMergingOptionsProps
           </Col>
           <Col sm={8}>
             <Tab.Content>
-              {versions.map(({ versionId, content }) => (
+              {versions.map(({ versionId }) => (
                 <Tab.Pane key={versionId} eventKey={`#version-${versionId}`}>
                   <Form.Label>Version content preview</Form.Label>
-                  <div className={[styles.textblack, styles.versionContent].join(' ')}>{content}</div>
+                  <div className={[styles.textblack, styles.versionContent].join(' ')}>{previews[versionId]}</div>
                 </Tab.Pane>
               ))}
             </Tab.Content>